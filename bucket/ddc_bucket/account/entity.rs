@@ -132,6 +132,10 @@ impl Account {
         self.payable_schedule.add_schedule(payable_schedule);
     }
 
+    pub fn unlock_rate(&mut self, start_ms: u64, rate: Balance) {
+        self.payable_schedule.remove_rate(start_ms, rate);
+    }
+
     pub fn schedule_covered_until(&self, deposit_usd: USD) -> u64 {
         self.payable_schedule.time_of_value(deposit_usd)
     }
@@ -141,6 +145,17 @@ impl Account {
         self.pay(payable)
     }
 
+    /// Pay the scheduled amount from the deposit as far as it goes, and record the rest as owed.
+    /// Return the paid amount.
+    pub fn pay_scheduled_or_owe(&mut self, payable: Payable, payable_usd: USD) -> Balance {
+        self.unlock_scheduled_amount(payable_usd);
+        let owed = payable.peek().saturating_sub(self.deposit.peek());
+        let paid = payable.consume() - owed;
+        self.deposit.pay_unchecked(Payable(paid));
+        self.negative.increase(Cash(owed));
+        paid
+    }
+
     fn pay(&mut self, payable: Payable) -> Result<()> {
         if self.deposit.peek() >= payable.peek() {
             self.deposit.pay_unchecked(payable);
@@ -4,8 +4,10 @@ use ink_lang::codegen::{EmitEvent, StaticEnv};
 use ink_prelude::vec::Vec;
 
 use crate::ddc_bucket::perm::entity::Permission;
-use crate::ddc_bucket::Error::InsufficientBalance;
-use crate::ddc_bucket::{AccountId, Balance, Cash, DdcBucket, Deposit, Payable, Result, TOKEN};
+use crate::ddc_bucket::Error::{InsufficientBalance, TransferFailed};
+use crate::ddc_bucket::{
+    AccountId, Balance, Cash, DdcBucket, Deposit, EarningsClaimed, Payable, Result, TOKEN,
+};
 
 impl DdcBucket {
     // todo: remove this method as we can not support iterable data structures of arbitrary data size
@@ -30,6 +32,9 @@ impl DdcBucket {
         account.deposit(cash);
         self.accounts.save(&account_id, &account);
 
+        // Reactivate the buckets that are covered again.
+        self.bucket_refresh_unpaid(account_id);
+
         Ok(())
     }
 
@@ -69,6 +74,22 @@ impl DdcBucket {
         Ok(())
     }
 
+    pub fn message_claim_earnings(&mut self) -> Result<Balance> {
+        let account_id = Self::env().caller();
+
+        let value = self.accounts.get_earnings(account_id);
+        if value == 0 {
+            return Err(InsufficientBalance);
+        }
+        Self::send_cash(account_id, Cash(value))?;
+        // Only forget the earnings once they are transferred.
+        self.accounts.take_earnings(account_id);
+
+        Self::env().emit_event(EarningsClaimed { account_id, value });
+
+        Ok(value)
+    }
+
     pub fn message_account_get_usd_per_cere(&self) -> Balance {
         self.protocol.curr_converter.to_usd(1 * TOKEN)
     }
@@ -88,7 +109,7 @@ impl DdcBucket {
             return Ok(());
         }
         match Self::env().transfer(destination, cash.consume()) {
-            Err(_e) => panic!("Transfer failed"), // Err(Error::TransferFailed),
+            Err(_e) => Err(TransferFailed),
             Ok(_v) => Ok(()),
         }
     }
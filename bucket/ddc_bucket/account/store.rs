@@ -3,7 +3,11 @@
 use super::entity::Account;
 use crate::ddc_bucket::flow::Flow;
 use crate::ddc_bucket::{
-    cash::Cash, currency::CurrencyConverter, schedule::Schedule, AccountId, Balance, Error::*,
+    cash::{Cash, Payable},
+    currency::CurrencyConverter,
+    schedule::Schedule,
+    AccountId, Balance,
+    Error::*,
     Result,
 };
 use ink_prelude::vec::Vec;
@@ -20,6 +24,8 @@ pub struct AccountStore {
     pub accounts: Mapping<AccountId, Account>,
     // todo: remove this vector as it can store an arbitrary number of elements and easily exceed 16KB limit
     pub accounts_keys: Vec<AccountId>,
+    /// The earnings of providers, cluster managers and fee destinations, to be claimed by them.
+    pub earnings: Mapping<AccountId, Balance>,
 }
 
 impl AccountStore {
@@ -45,6 +51,26 @@ impl AccountStore {
         }
     }
 
+    pub fn get_earnings(&self, account_id: AccountId) -> Balance {
+        self.earnings.get(account_id).unwrap_or(0)
+    }
+
+    /// Credit the given cash to the earnings of the account.
+    pub fn credit_earnings(&mut self, account_id: AccountId, cash: Cash) {
+        if cash.peek() == 0 {
+            return;
+        }
+        let earnings = self.get_earnings(account_id) + cash.consume();
+        self.earnings.insert(account_id, &earnings);
+    }
+
+    /// Take all the earnings of the account.
+    pub fn take_earnings(&mut self, account_id: AccountId) -> Balance {
+        let earnings = self.get_earnings(account_id);
+        self.earnings.remove(account_id);
+        earnings
+    }
+
     pub fn get(&self, account_id: &AccountId) -> Result<Account> {
         self.accounts.get(account_id).ok_or(AccountDoesNotExist)
     }
@@ -71,6 +97,46 @@ impl AccountStore {
         Ok(())
     }
 
+    /// Decrease the rate of the given flow starting from the given time.
+    /// Unlock that part of the payment flow from the deposit of the payer account.
+    /// The flow must be settled beforehand, otherwise the due amount may be forgiven.
+    pub fn decrease_flow(
+        &mut self,
+        start_ms: u64,
+        removed_rate: Balance,
+        flow: &mut Flow,
+    ) -> Result<()> {
+        let removed_rate = removed_rate.min(flow.schedule.get_rate());
+        flow.schedule.remove_rate(start_ms, removed_rate);
+
+        let mut from_account = self.get(&flow.from)?;
+        from_account.unlock_rate(start_ms, removed_rate);
+        self.save(&flow.from, &from_account);
+
+        Ok(())
+    }
+
+    /// Stop the given flow from the given time.
+    /// The flow must be settled beforehand, otherwise the due amount may be forgiven.
+    pub fn end_flow(&mut self, end_ms: u64, flow: &mut Flow) -> Result<()> {
+        let rate = flow.schedule.get_rate();
+        self.decrease_flow(end_ms, rate, flow)
+    }
+
+    /// Move the given flow to another payer starting from the given time.
+    /// The flow must be settled beforehand, otherwise the due amount may be forgiven.
+    pub fn change_flow_payer(
+        &mut self,
+        start_ms: u64,
+        payer_id: AccountId,
+        flow: &mut Flow,
+    ) -> Result<()> {
+        let rate = flow.schedule.get_rate();
+        self.end_flow(start_ms, flow)?;
+        flow.from = payer_id;
+        self.increase_flow(start_ms, rate, flow)
+    }
+
     pub fn settle_flow(
         &mut self,
         now_ms: u64,
@@ -88,6 +154,38 @@ impl AccountStore {
         Ok(cash)
     }
 
+    /// Settle the given flow as far as the deposit of the payer covers it.
+    /// The uncovered amount is recorded as owed by the payer instead of failing.
+    pub fn settle_flow_or_owe(
+        &mut self,
+        now_ms: u64,
+        flow: &mut Flow,
+        curr_converter: &CurrencyConverter,
+    ) -> Result<Cash> {
+        let flowed_usd = flow.schedule.take_value_at_time(now_ms);
+        let flowed_cere = curr_converter.to_cere(flowed_usd);
+
+        let mut account = self.get(&flow.from)?;
+        let paid = account.pay_scheduled_or_owe(Payable::new(flowed_cere), flowed_usd);
+        self.save(&flow.from, &account);
+
+        Ok(Cash(paid))
+    }
+
+    /// The time until which the payer would cover the given flow if it restarted at the given rate from now.
+    pub fn flow_covered_until_if_resumed(
+        &self,
+        now_ms: u64,
+        rate: Balance,
+        flow: &Flow,
+        curr_converter: &CurrencyConverter,
+    ) -> Result<u64> {
+        let mut account = self.get(&flow.from)?;
+        account.lock_schedule(Schedule::new(now_ms, rate));
+        let deposit_usd = curr_converter.to_usd(account.deposit.peek());
+        Ok(account.schedule_covered_until(deposit_usd))
+    }
+
     pub fn flow_covered_until(
         &self,
         flow: &Flow,
@@ -118,6 +118,27 @@ impl DdcBucket {
         Ok(())
     }
 
+    pub fn message_admin_set_bucket_grace_period_ms(&mut self, grace_period_ms: u64) -> Result<()> {
+        self.only_with_permission(Permission::SuperAdmin)?;
+        self.protocol.set_bucket_grace_period_ms(grace_period_ms);
+        Ok(())
+    }
+
+    pub fn message_admin_set_bucket_rerate_notice_ms(&mut self, notice_ms: u64) -> Result<()> {
+        self.only_with_permission(Permission::SuperAdmin)?;
+        self.protocol.set_bucket_rerate_notice_ms(notice_ms);
+        Ok(())
+    }
+
+    pub fn message_admin_set_cdn_report_quorum(&mut self, quorum: u32) -> Result<()> {
+        self.only_with_permission(Permission::SuperAdmin)?;
+        if quorum == 0 {
+            return Err(InvalidCdnReportQuorum);
+        }
+        self.protocol.set_cdn_report_quorum(quorum);
+        Ok(())
+    }
+
     pub fn message_admin_withdraw_revenues(&mut self, amount: u128) -> Result<()> {
         self.only_with_permission(Permission::SuperAdmin)?;
         self.protocol.withdraw_revenues(Payable(amount))?;
@@ -2,11 +2,11 @@
 
 use crate::ddc_bucket::flow::Flow;
 use crate::ddc_bucket::node::entity::Resource;
-use crate::ddc_bucket::{AccountId, ClusterId, Error::*, Result};
+use crate::ddc_bucket::{AccountId, Balance, ClusterId, Error::*, Result};
 use ink_prelude::string::String;
 use ink_prelude::vec::Vec;
 use ink_primitives::Key;
-use ink_storage::traits::{PackedAllocate, PackedLayout, SpreadAllocate, SpreadLayout};
+use ink_storage::traits::{KeyPtr, PackedAllocate, PackedLayout, SpreadAllocate, SpreadLayout};
 use scale::{Decode, Encode};
 
 pub type BucketId = u32;
@@ -22,6 +22,7 @@ pub struct Bucket {
     pub public_availability: bool,
     pub resource_consumption_cap: Resource,
     pub bucket_params: BucketParams,
+    pub state: BucketState,
 }
 
 // https://use.ink/3.x/ink-vs-solidity#nested-mappings--custom--advanced-structures
@@ -32,17 +33,86 @@ impl ink_storage::traits::PackedAllocate for Bucket {
     }
 }
 
+/// A permission on a bucket granted to an account, optionally until some time.
+#[derive(Clone, Copy, PartialEq, Encode, Decode, SpreadLayout, PackedLayout)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub struct BucketGrant {
+    pub account_id: AccountId,
+    pub expires_at_ms: Option<u64>,
+}
+
+impl BucketGrant {
+    pub fn is_active(&self, now_ms: u64) -> bool {
+        match self.expires_at_ms {
+            Some(expires_at_ms) => now_ms < expires_at_ms,
+            None => true,
+        }
+    }
+}
+
+/// The lifecycle of a bucket, driven by how long its payer can cover its rent.
+#[derive(Clone, Copy, PartialEq, Encode, Decode, SpreadLayout, PackedLayout)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub enum BucketState {
+    /// The rent is covered by the payer.
+    Active,
+    /// The rent is not covered anymore, but the bucket is still served for a grace period.
+    GracePeriod,
+    /// The rent is not covered since longer than the grace period. Nodes stop serving the bucket.
+    Suspended,
+}
+
+impl SpreadAllocate for BucketState {
+    fn allocate_spread(_: &mut KeyPtr) -> Self {
+        BucketState::Active
+    }
+}
+
+impl BucketState {
+    pub fn at_time(now_ms: u64, covered_until_ms: u64, grace_period_ms: u64) -> Self {
+        if now_ms < covered_until_ms {
+            BucketState::Active
+        } else if now_ms < covered_until_ms.saturating_add(grace_period_ms) {
+            BucketState::GracePeriod
+        } else {
+            BucketState::Suspended
+        }
+    }
+}
+
+/// An announced increase of the rate of a bucket, following an increase of the rent of its cluster.
+#[derive(Clone, Copy, PartialEq, Encode, Decode, SpreadLayout, PackedLayout)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub struct PendingRate {
+    pub rate: Balance,
+    pub effective_at_ms: u64,
+}
+
+/// The kind of access to a bucket. Each kind includes the ones before it.
+#[derive(Clone, Copy, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub enum AccessKind {
+    /// Read the data of the bucket.
+    Read,
+    /// Write data into the bucket.
+    Write,
+    /// Manage the bucket and its permissions.
+    Admin,
+}
+
 // Add to status field bucket availability
 #[derive(Clone, PartialEq, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
 pub struct BucketInStatus {
     pub owner_id: AccountId,
+    pub payer_id: AccountId,
     pub cluster_id: ClusterId,
     // The field "flow" is not included because it triggers a bug in polkadot.js.
     // TODO: find a fix, then return the entire Bucket structure.
     pub resource_reserved: Resource,
     pub public_availability: bool,
     pub resource_consumption_cap: Resource,
+    pub state: BucketState,
 }
 
 #[derive(Clone, PartialEq, Encode, Decode)]
@@ -67,10 +137,35 @@ impl Bucket {
         }
     }
 
+    pub fn only_owner_or_payer(&self, caller: AccountId) -> Result<()> {
+        if self.owner_id == caller || self.flow.from == caller {
+            Ok(())
+        } else {
+            Err(OnlyOwnerOrPayer)
+        }
+    }
+
+    pub fn only_not_suspended(&self) -> Result<()> {
+        if self.state == BucketState::Suspended {
+            Err(BucketIsSuspended)
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn put_resource(&mut self, amount: Resource) {
         self.resource_reserved += amount;
     }
 
+    pub fn release_resource(&mut self, amount: Resource) -> Result<()> {
+        if self.resource_reserved >= amount {
+            self.resource_reserved -= amount;
+            Ok(())
+        } else {
+            Err(InsufficientBucketResources)
+        }
+    }
+
     pub fn set_cap(&mut self, amount: Resource) {
         self.resource_consumption_cap = amount;
     }
@@ -79,6 +174,10 @@ impl Bucket {
         self.public_availability = availability;
     }
 
+    pub fn set_cluster(&mut self, cluster_id: ClusterId) {
+        self.cluster_id = cluster_id;
+    }
+
     pub fn change_owner(&mut self, owner_id: AccountId) {
         self.owner_id = owner_id;
     }
@@ -96,10 +195,12 @@ impl From<Bucket> for BucketInStatus {
     fn from(bucket: Bucket) -> Self {
         Self {
             owner_id: bucket.owner_id,
+            payer_id: bucket.flow.from,
             cluster_id: bucket.cluster_id,
             resource_reserved: bucket.resource_reserved,
             public_availability: bucket.public_availability,
             resource_consumption_cap: bucket.resource_consumption_cap,
+            state: bucket.state,
         }
     }
 }
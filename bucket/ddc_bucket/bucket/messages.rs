@@ -3,14 +3,22 @@
 use ink_lang::codegen::{EmitEvent, StaticEnv};
 use ink_prelude::vec::Vec;
 
-use crate::ddc_bucket::cluster::entity::{Cluster, ClusterId};
+use crate::ddc_bucket::cluster::entity::{Cluster, ClusterId, ClusterRole};
 use crate::ddc_bucket::node::entity::Resource;
 use crate::ddc_bucket::{
-    AccountId, Balance, BucketAllocated, BucketAvailabilityUpdated, BucketCreated, BucketParamsSet,
-    BucketSettlePayment, DdcBucket, Error::*, Result,
+    AccountId, Balance, BucketAllocated, BucketAvailabilityUpdated, BucketCreated, BucketMigrated,
+    BucketOwnershipTransferProposed, BucketOwnershipTransferred, BucketParamsSet,
+    BucketPayerProposed, BucketPayerSet, BucketReleased, BucketRemoved, BucketRerateFailed,
+    BucketRerateScheduled, BucketRerated, BucketSettlePayment, BucketStateChanged, DdcBucket,
+    Error::*, Result,
 };
 
-use super::entity::{Bucket, BucketId, BucketParams, BucketStatus};
+use super::entity::{
+    AccessKind, Bucket, BucketId, BucketParams, BucketState, BucketStatus, PendingRate,
+    BUCKET_PARAMS_MAX_LEN,
+};
+use super::params::BucketConfig;
+use super::store::MAX_UNPAID_BUCKETS_REFRESHED_PER_CALL;
 
 impl DdcBucket {
     pub fn message_bucket_create(
@@ -20,6 +28,8 @@ impl DdcBucket {
         owner_id: Option<AccountId>,
     ) -> Result<BucketId> {
         let owner_id = owner_id.unwrap_or(Self::env().caller());
+        self.clusters.get(cluster_id)?;
+        self.only_valid_bucket_params(cluster_id, &bucket_params)?;
         self.accounts.create_if_not_exist(owner_id)?;
         let bucket_id = self.buckets.create(owner_id, cluster_id, bucket_params);
         Self::env().emit_event(BucketCreated {
@@ -29,16 +39,119 @@ impl DdcBucket {
         Ok(bucket_id)
     }
 
-    pub fn message_bucket_change_owner(
+    pub fn message_bucket_transfer_ownership(
         &mut self,
         bucket_id: BucketId,
         owner_id: AccountId,
+        keep_payer: bool,
+    ) -> Result<()> {
+        let caller = Self::env().caller();
+        let bucket = self.buckets.get(bucket_id)?;
+        bucket.only_owner(caller)?;
+        self.buckets.propose_owner(bucket_id, owner_id, keep_payer);
+
+        Self::env().emit_event(BucketOwnershipTransferProposed {
+            bucket_id,
+            owner_id,
+        });
+        Ok(())
+    }
+
+    pub fn message_bucket_cancel_ownership_transfer(&mut self, bucket_id: BucketId) -> Result<()> {
+        let caller = Self::env().caller();
+        let bucket = self.buckets.get(bucket_id)?;
+        bucket.only_owner(caller)?;
+        self.buckets.cancel_owner_proposal(bucket_id);
+        Ok(())
+    }
+
+    pub fn message_bucket_accept_ownership(&mut self, bucket_id: BucketId) -> Result<()> {
+        let caller = Self::env().caller();
+        let mut bucket = self.buckets.get(bucket_id)?;
+        let keep_payer = self.buckets.accept_owner(bucket_id, caller)?;
+        self.accounts.create_if_not_exist(caller)?;
+
+        self.buckets
+            .change_owner_index(bucket_id, bucket.owner_id, caller);
+        bucket.change_owner(caller);
+        // Payer proposals were made by the previous owner.
+        self.buckets.cancel_payer_proposal(bucket_id);
+
+        if keep_payer {
+            self.buckets.update(bucket_id, &bucket)?;
+        } else {
+            self.bucket_change_payer(bucket_id, &mut bucket, caller)?;
+        }
+
+        Self::env().emit_event(BucketOwnershipTransferred {
+            bucket_id,
+            owner_id: caller,
+        });
+        Ok(())
+    }
+
+    pub fn message_bucket_set_payer(
+        &mut self,
+        bucket_id: BucketId,
+        payer_id: AccountId,
     ) -> Result<()> {
         let caller = Self::env().caller();
         let mut bucket = self.buckets.get(bucket_id)?;
+
+        // The owner is responsible for its bucket, so it can be made the payer without its consent.
+        if payer_id == bucket.owner_id {
+            bucket.only_owner_or_payer(caller)?;
+            self.buckets.cancel_payer_proposal(bucket_id);
+            return self.bucket_change_payer(bucket_id, &mut bucket, payer_id);
+        }
+
+        // Any other payer has to accept the bucket first.
         bucket.only_owner(caller)?;
-        bucket.change_owner(owner_id);
-        self.buckets.update(bucket_id, &bucket)?;
+        self.buckets.propose_payer(bucket_id, payer_id);
+
+        Self::env().emit_event(BucketPayerProposed {
+            bucket_id,
+            payer_id,
+        });
+        Ok(())
+    }
+
+    pub fn message_bucket_accept_payer(&mut self, bucket_id: BucketId) -> Result<()> {
+        let caller = Self::env().caller();
+        let mut bucket = self.buckets.get(bucket_id)?;
+        self.buckets.accept_payer(bucket_id, caller)?;
+        self.bucket_change_payer(bucket_id, &mut bucket, caller)
+    }
+
+    fn bucket_change_payer(
+        &mut self,
+        bucket_id: BucketId,
+        bucket: &mut Bucket,
+        payer_id: AccountId,
+    ) -> Result<()> {
+        self.accounts.create_if_not_exist(payer_id)?;
+
+        // Settle the payment flow under the previous payer, then move it to the new payer.
+        let now_ms = Self::env().block_timestamp();
+        let cash =
+            self.accounts
+                .settle_flow(now_ms, &mut bucket.flow, &self.protocol.curr_converter)?;
+
+        let mut cluster = self.clusters.get(bucket.cluster_id)?;
+        cluster.revenues.increase(cash);
+
+        // The state of the bucket now depends on the coverage of the new payer.
+        self.buckets.set_unpaid(bucket.flow.from, bucket_id, false);
+        self.accounts
+            .change_flow_payer(now_ms, payer_id, &mut bucket.flow)?;
+        self.bucket_update_state(bucket_id, bucket, &mut cluster)?;
+        self.clusters.update(bucket.cluster_id, &cluster)?;
+        self.buckets.update(bucket_id, bucket)?;
+
+        Self::env().emit_event(BucketPayerSet {
+            bucket_id,
+            payer_id,
+        });
         Ok(())
     }
 
@@ -49,15 +162,9 @@ impl DdcBucket {
     ) -> Result<()> {
         let mut bucket = self.buckets.get(bucket_id)?;
         let mut cluster = self.clusters.get(bucket.cluster_id)?;
-        Self::only_owner_or_cluster_manager(&bucket, &cluster)?;
-
-        let cluster_v_nodes = self.get_v_nodes_by_cluster(bucket.cluster_id);
-        let cluster_v_nodes_len: u32 = cluster_v_nodes.len().try_into().unwrap();
-        let max_cluster_resource = cluster_v_nodes_len * cluster.resource_per_v_node;
-
-        if cluster.resource_used + resource > max_cluster_resource {
-            return Err(InsufficientClusterResources);
-        }
+        self.only_owner_or_cluster_manager(&bucket, &cluster)?;
+        bucket.only_not_suspended()?;
+        self.only_sufficient_cluster_resources(bucket.cluster_id, &cluster, resource)?;
 
         cluster.take_resource(resource);
         self.clusters.update(bucket.cluster_id, &cluster)?;
@@ -79,6 +186,104 @@ impl DdcBucket {
         Ok(())
     }
 
+    pub fn message_bucket_release_from_cluster(
+        &mut self,
+        bucket_id: BucketId,
+        resource: Resource,
+    ) -> Result<()> {
+        let mut bucket = self.buckets.get(bucket_id)?;
+        let mut cluster = self.clusters.get(bucket.cluster_id)?;
+        self.only_owner_or_cluster_manager(&bucket, &cluster)?;
+        bucket.only_not_suspended()?;
+
+        let resource_reserved = bucket.resource_reserved;
+        bucket.release_resource(resource)?;
+        cluster.release_resource(resource);
+
+        // The rate is reduced in proportion to the released part of the reservation.
+        let rate = bucket.flow.schedule.get_rate();
+        let removed_rate = if resource == resource_reserved {
+            rate
+        } else {
+            rate * resource as Balance / resource_reserved as Balance
+        };
+
+        // Settle the payment flow up to now, then continue at the reduced rate.
+        let now_ms = Self::env().block_timestamp();
+        let cash =
+            self.accounts
+                .settle_flow(now_ms, &mut bucket.flow, &self.protocol.curr_converter)?;
+        cluster.revenues.increase(cash);
+
+        self.accounts
+            .decrease_flow(now_ms, removed_rate, &mut bucket.flow)?;
+        self.clusters.update(bucket.cluster_id, &cluster)?;
+        self.buckets.update(bucket_id, &bucket)?;
+
+        Self::env().emit_event(BucketReleased {
+            bucket_id,
+            cluster_id: bucket.cluster_id,
+            resource,
+        });
+        Ok(())
+    }
+
+    pub fn message_bucket_migrate(
+        &mut self,
+        bucket_id: BucketId,
+        target_cluster_id: ClusterId,
+    ) -> Result<()> {
+        let caller = Self::env().caller();
+        let mut bucket = self.buckets.get(bucket_id)?;
+        bucket.only_owner(caller)?;
+        bucket.only_not_suspended()?;
+
+        let source_cluster_id = bucket.cluster_id;
+        if source_cluster_id == target_cluster_id {
+            return Err(BucketIsAlreadyInCluster);
+        }
+
+        let mut source_cluster = self.clusters.get(source_cluster_id)?;
+        let mut target_cluster = self.clusters.get(target_cluster_id)?;
+        let resource = bucket.resource_reserved;
+        self.only_sufficient_cluster_resources(target_cluster_id, &target_cluster, resource)?;
+        self.only_valid_bucket_params(target_cluster_id, &bucket.bucket_params)?;
+
+        // Settle the payment flow to the source cluster and stop it.
+        let now_ms = Self::env().block_timestamp();
+        let cash =
+            self.accounts
+                .settle_flow(now_ms, &mut bucket.flow, &self.protocol.curr_converter)?;
+        self.accounts.end_flow(now_ms, &mut bucket.flow)?;
+
+        source_cluster.revenues.increase(cash);
+        source_cluster.release_resource(resource);
+        self.clusters.update(source_cluster_id, &source_cluster)?;
+
+        // Move the reservation and restart the payment flow at the rent of the target cluster.
+        target_cluster.take_resource(resource);
+        self.clusters.update(target_cluster_id, &target_cluster)?;
+
+        let rate = target_cluster.total_rent * resource as Balance;
+        self.accounts
+            .increase_flow(now_ms, rate, &mut bucket.flow)?;
+        bucket.set_cluster(target_cluster_id);
+        self.buckets.update(bucket_id, &bucket)?;
+        self.buckets
+            .change_cluster_index(bucket_id, source_cluster_id, target_cluster_id);
+        // The rates announced or paused under the rent of the source cluster do not apply anymore.
+        self.buckets.pending_rates.remove(bucket_id);
+        self.buckets.paused_rates.remove(bucket_id);
+
+        Self::env().emit_event(BucketMigrated {
+            bucket_id,
+            source_cluster_id,
+            target_cluster_id,
+            resource,
+        });
+        Ok(())
+    }
+
     pub fn message_bucket_settle_payment(&mut self, bucket_id: BucketId) -> Result<()> {
         let mut bucket = self.buckets.get(bucket_id)?;
 
@@ -99,6 +304,148 @@ impl DdcBucket {
         Ok(())
     }
 
+    pub fn message_bucket_rerate(&mut self, bucket_id: BucketId) -> Result<()> {
+        let mut bucket = self.buckets.get(bucket_id)?;
+        let mut cluster = self.clusters.get(bucket.cluster_id)?;
+
+        self.bucket_apply_rent(bucket_id, &mut bucket, &mut cluster)?;
+        self.clusters.update(bucket.cluster_id, &cluster)?;
+        self.buckets.update(bucket_id, &bucket)?;
+        Ok(())
+    }
+
+    pub fn message_cluster_rerate_buckets(
+        &mut self,
+        cluster_id: ClusterId,
+        offset: u32,
+        limit: u32,
+    ) -> Result<u32> {
+        let mut cluster = self.clusters.get(cluster_id)?;
+
+        let (bucket_ids, total) = self
+            .buckets
+            .cluster_buckets
+            .get_page(cluster_id, offset, limit);
+        for bucket_id in bucket_ids {
+            let mut bucket = self.buckets.get(bucket_id)?;
+            match self.bucket_apply_rent(bucket_id, &mut bucket, &mut cluster) {
+                Ok(()) => self.buckets.update(bucket_id, &bucket)?,
+                // A bucket that cannot be settled is reported, and does not block the others.
+                Err(error) => Self::env().emit_event(BucketRerateFailed { bucket_id, error }),
+            };
+        }
+
+        self.clusters.update(cluster_id, &cluster)?;
+        Ok(total)
+    }
+
+    pub fn message_bucket_get_pending_rate(&self, bucket_id: BucketId) -> Option<PendingRate> {
+        self.buckets.pending_rates.get(bucket_id)
+    }
+
+    /// Bring the rate of a bucket to the current rent of its cluster.
+    ///
+    /// A decrease applies at once. An increase is announced first, and applies after the notice period.
+    /// The caller must save the bucket and the cluster.
+    fn bucket_apply_rent(
+        &mut self,
+        bucket_id: BucketId,
+        bucket: &mut Bucket,
+        cluster: &mut Cluster,
+    ) -> Result<()> {
+        // The paused flow of a suspended bucket is re-rated once it resumes.
+        if self.buckets.paused_rates.contains(bucket_id) {
+            return Ok(());
+        }
+
+        let now_ms = Self::env().block_timestamp();
+        let target_rate = cluster.total_rent * bucket.resource_reserved as Balance;
+        let rate = bucket.flow.schedule.get_rate();
+        let pending = self.buckets.pending_rates.get(bucket_id);
+
+        // An announced increase never exceeds the current rent.
+        let new_rate = match pending {
+            Some(pending) if now_ms >= pending.effective_at_ms => {
+                Some(pending.rate.min(target_rate))
+            }
+            _ if target_rate < rate => Some(target_rate),
+            _ => None,
+        };
+
+        if let Some(new_rate) = new_rate {
+            // Settle the payment flow at the old rate, then restart it at the new rate.
+            let cash = self.accounts.settle_flow(
+                now_ms,
+                &mut bucket.flow,
+                &self.protocol.curr_converter,
+            )?;
+            cluster.revenues.increase(cash);
+            self.accounts.end_flow(now_ms, &mut bucket.flow)?;
+            self.accounts
+                .increase_flow(now_ms, new_rate, &mut bucket.flow)?;
+            self.buckets.pending_rates.remove(bucket_id);
+            self.bucket_update_state(bucket_id, bucket, cluster)?;
+
+            Self::env().emit_event(BucketRerated {
+                bucket_id,
+                rate: new_rate,
+            });
+        }
+
+        let rate = bucket.flow.schedule.get_rate();
+        let pending = self.buckets.pending_rates.get(bucket_id);
+        if target_rate <= rate {
+            self.buckets.pending_rates.remove(bucket_id);
+        } else if pending.map(|pending| pending.rate) != Some(target_rate) {
+            // Announce the increase, or a new value of it.
+            let effective_at_ms = now_ms + self.protocol.get_bucket_rerate_notice_ms();
+            self.buckets.pending_rates.insert(
+                bucket_id,
+                &PendingRate {
+                    rate: target_rate,
+                    effective_at_ms,
+                },
+            );
+
+            Self::env().emit_event(BucketRerateScheduled {
+                bucket_id,
+                rate: target_rate,
+                effective_at_ms,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn message_bucket_remove(&mut self, bucket_id: BucketId) -> Result<()> {
+        let caller = Self::env().caller();
+        let mut bucket = self.buckets.get(bucket_id)?;
+        bucket.only_owner(caller)?;
+
+        // Settle the due costs, then stop the payment flow to the cluster.
+        // A payer that cannot cover them owes the rest, so that the flow always ends.
+        let now_ms = Self::env().block_timestamp();
+        let cash = self.accounts.settle_flow_or_owe(
+            now_ms,
+            &mut bucket.flow,
+            &self.protocol.curr_converter,
+        )?;
+        self.accounts.end_flow(now_ms, &mut bucket.flow)?;
+
+        // Give back the reserved resources to the cluster.
+        let mut cluster = self.clusters.get(bucket.cluster_id)?;
+        cluster.revenues.increase(cash);
+        cluster.release_resource(bucket.resource_reserved);
+        self.clusters.update(bucket.cluster_id, &cluster)?;
+
+        self.buckets.remove(bucket_id);
+
+        Self::env().emit_event(BucketRemoved {
+            bucket_id,
+            cluster_id: bucket.cluster_id,
+        });
+        Ok(())
+    }
+
     pub fn message_bucket_change_params(
         &mut self,
         bucket_id: BucketId,
@@ -107,6 +454,7 @@ impl DdcBucket {
         let caller = Self::env().caller();
         let mut bucket = self.buckets.get(bucket_id)?;
         bucket.only_owner(caller)?;
+        self.only_valid_bucket_params(bucket.cluster_id, &bucket_params)?;
         bucket.set_params(bucket_params.clone())?;
         self.buckets.update(bucket_id, &bucket)?;
         Self::env().emit_event(BucketParamsSet {
@@ -128,9 +476,10 @@ impl DdcBucket {
         filter_owner_id: Option<AccountId>,
     ) -> (Vec<BucketStatus>, u32) {
         let mut bucket_statuses = Vec::with_capacity(limit as usize);
-        for bucket_id in offset..offset + limit {
+        let end = (offset + limit).min(self.buckets.next_bucket_id);
+        for bucket_id in offset..end {
             let bucket = match self.buckets.buckets.get(bucket_id) {
-                None => break, // No more buckets, stop.
+                None => continue, // Skip removed buckets.
                 Some(bucket) => bucket,
             };
             // Apply the filter if given.
@@ -148,18 +497,23 @@ impl DdcBucket {
         (bucket_statuses, self.buckets.next_bucket_id)
     }
 
-    pub fn message_bucket_list_for_account(&self, owner_id: AccountId) -> Vec<Bucket> {
-        let mut result: Vec<Bucket> = Vec::new();
-
-        for bucket_id in 0..self.buckets.next_bucket_id {
-            let bucket = self.buckets.get(bucket_id).unwrap();
+    pub fn message_bucket_list_for_account(
+        &self,
+        owner_id: AccountId,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<BucketStatus>, u32) {
+        let (bucket_ids, total) = self.buckets.list_owner_buckets(owner_id, offset, limit);
 
-            if bucket.owner_id == owner_id {
-                result.push(bucket);
+        let mut bucket_statuses = Vec::with_capacity(bucket_ids.len());
+        for bucket_id in bucket_ids {
+            // Collect all the details of the bucket.
+            match self.message_bucket_get(bucket_id) {
+                Err(_) => continue, // Skip on unexpected error.
+                Ok(status) => bucket_statuses.push(status),
             };
         }
-
-        result
+        (bucket_statuses, total)
     }
 
     pub fn bucket_calculate_status(
@@ -167,12 +521,13 @@ impl DdcBucket {
         bucket_id: BucketId,
         bucket: Bucket,
     ) -> Result<BucketStatus> {
-        let mut writer_ids = self.buckets.get_bucket_readers(bucket_id);
+        let now_ms = Self::env().block_timestamp();
+        let mut writer_ids = self.buckets.get_bucket_writers(bucket_id, now_ms);
         writer_ids.push(bucket.owner_id);
         let rent_covered_until_ms = self
             .accounts
             .flow_covered_until(&bucket.flow, &self.protocol.curr_converter)?;
-        let reader_ids = self.buckets.get_bucket_readers(bucket_id);
+        let reader_ids = self.buckets.get_bucket_readers(bucket_id, now_ms);
         let bucket_params = bucket.bucket_params.clone();
 
         Ok(BucketStatus {
@@ -185,6 +540,128 @@ impl DdcBucket {
         })
     }
 
+    pub fn message_bucket_check_access(
+        &self,
+        bucket_id: BucketId,
+        account_id: AccountId,
+        access: AccessKind,
+    ) -> Result<bool> {
+        let bucket = self.buckets.get(bucket_id)?;
+        let cluster = self.clusters.get(bucket.cluster_id)?;
+        let now_ms = Self::env().block_timestamp();
+
+        let is_admin = bucket.only_owner(account_id).is_ok()
+            || self.is_cluster_manager_or_operator(
+                bucket.cluster_id,
+                &cluster,
+                account_id,
+                ClusterRole::ManageBuckets,
+            );
+        // Nodes stop serving the data of suspended buckets.
+        let is_served = bucket.state != BucketState::Suspended;
+        let can_write = is_served
+            && (is_admin
+                || self
+                    .buckets
+                    .get_bucket_writers(bucket_id, now_ms)
+                    .contains(&account_id));
+        let can_read = can_write
+            || is_served
+                && (bucket.public_availability
+                    || self
+                        .buckets
+                        .get_bucket_readers(bucket_id, now_ms)
+                        .contains(&account_id));
+
+        Ok(match access {
+            AccessKind::Read => can_read,
+            AccessKind::Write => can_write,
+            AccessKind::Admin => is_admin,
+        })
+    }
+
+    pub fn message_bucket_check_suspension(&mut self, bucket_id: BucketId) -> Result<BucketState> {
+        let mut bucket = self.buckets.get(bucket_id)?;
+        let mut cluster = self.clusters.get(bucket.cluster_id)?;
+        self.bucket_update_state(bucket_id, &mut bucket, &mut cluster)?;
+        self.clusters.update(bucket.cluster_id, &cluster)?;
+        self.buckets.update(bucket_id, &bucket)?;
+        Ok(bucket.state)
+    }
+
+    /// Update the state of some buckets of a payer that are not active, e.g. after a deposit.
+    ///
+    /// At most `MAX_UNPAID_BUCKETS_REFRESHED_PER_CALL` buckets are updated, continuing from the previous refresh.
+    /// A bucket that cannot be updated is skipped, and can be updated with `bucket_check_suspension`.
+    pub fn bucket_refresh_unpaid(&mut self, payer_id: AccountId) {
+        let bucket_ids = self
+            .buckets
+            .next_unpaid_buckets(payer_id, MAX_UNPAID_BUCKETS_REFRESHED_PER_CALL);
+        for bucket_id in bucket_ids {
+            // A bucket that is paid now leaves the list.
+            let _ = self.message_bucket_check_suspension(bucket_id);
+        }
+    }
+
+    /// Update the state of a bucket from the rent coverage of its payer.
+    ///
+    /// The payment flow of a suspended bucket is settled and paused, and resumes once the payer covers it again.
+    /// The caller must save the bucket and the cluster.
+    fn bucket_update_state(
+        &mut self,
+        bucket_id: BucketId,
+        bucket: &mut Bucket,
+        cluster: &mut Cluster,
+    ) -> Result<()> {
+        let now_ms = Self::env().block_timestamp();
+        let conv = &self.protocol.curr_converter;
+        let paused_rate = self.buckets.paused_rates.get(bucket_id);
+        let covered_until_ms = match paused_rate {
+            Some(rate) => {
+                self.accounts
+                    .flow_covered_until_if_resumed(now_ms, rate, &bucket.flow, conv)?
+            }
+            None => self.accounts.flow_covered_until(&bucket.flow, conv)?,
+        };
+        let mut state = BucketState::at_time(
+            now_ms,
+            covered_until_ms,
+            self.protocol.get_bucket_grace_period_ms(),
+        );
+        // A paused bucket stays suspended until its rent is covered again.
+        if paused_rate.is_some() && state != BucketState::Active {
+            state = BucketState::Suspended;
+        }
+
+        match paused_rate {
+            None if state == BucketState::Suspended => {
+                // The payer owes the due costs that it cannot cover, and stops paying for a bucket that is not served.
+                let rate = bucket.flow.schedule.get_rate();
+                let cash = self
+                    .accounts
+                    .settle_flow_or_owe(now_ms, &mut bucket.flow, conv)?;
+                cluster.revenues.increase(cash);
+                self.accounts.end_flow(now_ms, &mut bucket.flow)?;
+                self.buckets.paused_rates.insert(bucket_id, &rate);
+            }
+            Some(rate) if state == BucketState::Active => {
+                self.accounts
+                    .increase_flow(now_ms, rate, &mut bucket.flow)?;
+                self.buckets.paused_rates.remove(bucket_id);
+            }
+            _ => {}
+        }
+
+        self.buckets
+            .set_unpaid(bucket.flow.from, bucket_id, state != BucketState::Active);
+
+        if bucket.state != state {
+            bucket.state = state;
+            Self::env().emit_event(BucketStateChanged { bucket_id, state });
+        }
+        Ok(())
+    }
+
     pub fn message_bucket_set_resource_cap(
         &mut self,
         bucket_id: BucketId,
@@ -193,7 +670,7 @@ impl DdcBucket {
         let mut bucket = self.buckets.get(bucket_id)?;
         let cluster = self.clusters.get(bucket.cluster_id)?;
 
-        Self::only_owner_or_cluster_manager(&bucket, &cluster)?;
+        self.only_owner_or_cluster_manager(&bucket, &cluster)?;
         bucket.set_cap(new_resource_cap);
         self.buckets.update(bucket_id, &bucket)?;
 
@@ -208,7 +685,7 @@ impl DdcBucket {
         let mut bucket = self.buckets.get(bucket_id)?;
         let cluster = self.clusters.get(bucket.cluster_id)?;
 
-        Self::only_owner_or_cluster_manager(&bucket, &cluster)?;
+        self.only_owner_or_cluster_manager(&bucket, &cluster)?;
         bucket.set_availability(public_availability);
 
         self.buckets.update(bucket_id, &bucket)?;
@@ -221,21 +698,22 @@ impl DdcBucket {
     }
 
     pub fn message_get_bucket_writers(&mut self, bucket_id: BucketId) -> Vec<AccountId> {
-        self.buckets.get_bucket_writers(bucket_id)
+        let now_ms = Self::env().block_timestamp();
+        self.buckets.get_bucket_writers(bucket_id, now_ms)
     }
 
     pub fn message_grant_writer_permission(
         &mut self,
         bucket_id: BucketId,
         writer: AccountId,
+        expires_at_ms: Option<u64>,
     ) -> Result<()> {
         let bucket = self.buckets.get(bucket_id)?;
         let cluster = self.clusters.get(bucket.cluster_id)?;
 
-        Self::only_owner_or_cluster_manager(&bucket, &cluster)?;
+        self.only_owner_or_cluster_manager(&bucket, &cluster)?;
         self.buckets
-            .grant_writer_permission(bucket_id, writer)
-            .unwrap();
+            .grant_writer_permission(bucket_id, writer, expires_at_ms);
 
         Ok(())
     }
@@ -248,30 +726,29 @@ impl DdcBucket {
         let bucket = self.buckets.get(bucket_id)?;
         let cluster = self.clusters.get(bucket.cluster_id)?;
 
-        Self::only_owner_or_cluster_manager(&bucket, &cluster)?;
-        self.buckets
-            .revoke_writer_permission(bucket_id, writer)
-            .unwrap();
+        self.only_owner_or_cluster_manager(&bucket, &cluster)?;
+        self.buckets.revoke_writer_permission(bucket_id, writer);
 
         Ok(())
     }
 
     pub fn message_get_bucket_readers(&mut self, bucket_id: BucketId) -> Vec<AccountId> {
-        self.buckets.get_bucket_readers(bucket_id)
+        let now_ms = Self::env().block_timestamp();
+        self.buckets.get_bucket_readers(bucket_id, now_ms)
     }
 
     pub fn message_grant_reader_permission(
         &mut self,
         bucket_id: BucketId,
         reader: AccountId,
+        expires_at_ms: Option<u64>,
     ) -> Result<()> {
         let bucket = self.buckets.get(bucket_id)?;
         let cluster = self.clusters.get(bucket.cluster_id)?;
 
-        Self::only_owner_or_cluster_manager(&bucket, &cluster)?;
+        self.only_owner_or_cluster_manager(&bucket, &cluster)?;
         self.buckets
-            .grant_reader_permission(bucket_id, reader)
-            .unwrap();
+            .grant_reader_permission(bucket_id, reader, expires_at_ms);
 
         Ok(())
     }
@@ -284,18 +761,62 @@ impl DdcBucket {
         let bucket = self.buckets.get(bucket_id)?;
         let cluster = self.clusters.get(bucket.cluster_id)?;
 
-        Self::only_owner_or_cluster_manager(&bucket, &cluster)?;
-        self.buckets
-            .revoke_reader_permission(bucket_id, reader)
-            .unwrap();
+        self.only_owner_or_cluster_manager(&bucket, &cluster)?;
+        self.buckets.revoke_reader_permission(bucket_id, reader);
 
         Ok(())
     }
 
-    fn only_owner_or_cluster_manager(bucket: &Bucket, cluster: &Cluster) -> Result<()> {
+    pub fn message_bucket_purge_expired_grants(&mut self, bucket_id: BucketId) -> Result<u32> {
+        self.buckets.get(bucket_id)?;
+        let now_ms = Self::env().block_timestamp();
+        Ok(self.buckets.purge_expired_grants(bucket_id, now_ms))
+    }
+
+    fn only_sufficient_cluster_resources(
+        &self,
+        cluster_id: ClusterId,
+        cluster: &Cluster,
+        resource: Resource,
+    ) -> Result<()> {
+        let cluster_v_nodes_len = self.topology.count_v_nodes_by_cluster(cluster_id);
+        let max_cluster_resource = cluster_v_nodes_len * cluster.resource_per_v_node;
+
+        if cluster.resource_used + resource > max_cluster_resource {
+            Err(InsufficientClusterResources)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn only_valid_bucket_params(
+        &self,
+        cluster_id: ClusterId,
+        bucket_params: &BucketParams,
+    ) -> Result<()> {
+        if bucket_params.len() > BUCKET_PARAMS_MAX_LEN {
+            return Err(ParamsSizeExceedsLimit);
+        }
+        let config = BucketConfig::parse(bucket_params)?;
+
+        // Only active nodes serve replicas, as in `cluster_resolve_key`.
+        let active_nodes_len = self.clusters.count_active_nodes(cluster_id);
+
+        let replication_factor = config.replication_factor;
+        if replication_factor == 0 || replication_factor > active_nodes_len {
+            Err(InvalidReplicationFactor)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn only_owner_or_cluster_manager(&self, bucket: &Bucket, cluster: &Cluster) -> Result<()> {
         let caller = Self::env().caller();
-        cluster
-            .only_manager(caller)
-            .or_else(|_| bucket.only_owner(caller))
+        self.only_cluster_manager_or_operator(
+            bucket.cluster_id,
+            cluster,
+            ClusterRole::ManageBuckets,
+        )
+        .or_else(|_| bucket.only_owner(caller))
     }
 }
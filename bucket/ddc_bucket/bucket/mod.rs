@@ -2,4 +2,5 @@
 
 pub mod entity;
 pub mod messages;
+pub mod params;
 pub mod store;
@@ -0,0 +1,257 @@
+//! The settings of a bucket read from its params.
+//!
+//! The params are a JSON object, see the [data structure of BucketParams](https://docs.cere.network/ddc/protocols/contract-params-schema).
+//! Only the settings known to the contract are read, the other fields are free-form configuration for clients and nodes.
+
+use crate::ddc_bucket::{Error::*, Result};
+use ink_prelude::string::String;
+
+/// The settings of a bucket that the contract validates.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BucketConfig {
+    /// How many nodes of the cluster store each piece of data, `replicationFactor` in the params. 1 if not given.
+    pub replication_factor: u32,
+    /// Preferred region of the nodes, `region` in the params.
+    pub region: Option<String>,
+    /// Preferred storage tier, `tier` in the params.
+    pub tier: Option<String>,
+}
+
+impl Default for BucketConfig {
+    fn default() -> Self {
+        BucketConfig {
+            replication_factor: 1,
+            region: None,
+            tier: None,
+        }
+    }
+}
+
+/// How deeply the free-form fields can nest arrays and objects.
+const MAX_DEPTH: u32 = 16;
+
+impl BucketConfig {
+    /// Read the settings from the params of a bucket. Empty params give the default settings.
+    pub fn parse(bucket_params: &str) -> Result<Self> {
+        let mut config = BucketConfig::default();
+        let mut parser = Parser {
+            bytes: bucket_params.as_bytes(),
+            pos: 0,
+        };
+        parser.skip_whitespace();
+        if parser.is_at_end() {
+            return Ok(config);
+        }
+
+        parser.expect(b'{')?;
+        parser.skip_whitespace();
+        if !parser.accept(b'}') {
+            loop {
+                parser.skip_whitespace();
+                let key = parser.parse_string()?;
+                parser.skip_whitespace();
+                parser.expect(b':')?;
+                parser.skip_whitespace();
+                match key.as_str() {
+                    "replicationFactor" => config.replication_factor = parser.parse_u32()?,
+                    "region" => config.region = parser.parse_optional_string()?,
+                    "tier" => config.tier = parser.parse_optional_string()?,
+                    _ => parser.skip_value(0)?,
+                }
+                parser.skip_whitespace();
+                if parser.accept(b'}') {
+                    break;
+                }
+                parser.expect(b',')?;
+            }
+        }
+
+        parser.skip_whitespace();
+        if parser.is_at_end() {
+            Ok(config)
+        } else {
+            Err(InvalidBucketParams)
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn is_at_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<u8> {
+        let byte = self.peek().ok_or(InvalidBucketParams)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn accept(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        if self.accept(byte) {
+            Ok(())
+        } else {
+            Err(InvalidBucketParams)
+        }
+    }
+
+    fn expect_word(&mut self, word: &[u8]) -> Result<()> {
+        if self.bytes[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(())
+        } else {
+            Err(InvalidBucketParams)
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut bytes = ink_prelude::vec::Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => match self.next()? {
+                    b'"' => bytes.push(b'"'),
+                    b'\\' => bytes.push(b'\\'),
+                    b'/' => bytes.push(b'/'),
+                    b'b' => bytes.push(0x08),
+                    b'f' => bytes.push(0x0c),
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'u' => {
+                        let mut code = 0;
+                        for _ in 0..4 {
+                            let digit = (self.next()? as char)
+                                .to_digit(16)
+                                .ok_or(InvalidBucketParams)?;
+                            code = code * 16 + digit;
+                        }
+                        // Surrogate pairs are not supported.
+                        let c = char::from_u32(code).ok_or(InvalidBucketParams)?;
+                        let mut buf = [0; 4];
+                        bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    }
+                    _ => return Err(InvalidBucketParams),
+                },
+                byte if byte < 0x20 => return Err(InvalidBucketParams),
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| InvalidBucketParams)
+    }
+
+    fn parse_optional_string(&mut self) -> Result<Option<String>> {
+        if self.peek() == Some(b'n') {
+            self.expect_word(b"null")?;
+            Ok(None)
+        } else {
+            self.parse_string().map(Some)
+        }
+    }
+
+    fn parse_u32(&mut self) -> Result<u32> {
+        let start = self.pos;
+        let mut value: u32 = 0;
+        while let Some(byte @ b'0'..=b'9') = self.peek() {
+            value = value
+                .checked_mul(10)
+                .and_then(|value| value.checked_add((byte - b'0') as u32))
+                .ok_or(InvalidBucketParams)?;
+            self.pos += 1;
+        }
+        if self.pos == start {
+            Err(InvalidBucketParams)
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn skip_number(&mut self) -> Result<()> {
+        let start = self.pos;
+        while matches!(
+            self.peek(),
+            Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')
+        ) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            Err(InvalidBucketParams)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn skip_value(&mut self, depth: u32) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(InvalidBucketParams);
+        }
+        match self.peek().ok_or(InvalidBucketParams)? {
+            b'"' => self.parse_string().map(|_| ()),
+            b't' => self.expect_word(b"true"),
+            b'f' => self.expect_word(b"false"),
+            b'n' => self.expect_word(b"null"),
+            b'[' => {
+                self.pos += 1;
+                self.skip_whitespace();
+                if self.accept(b']') {
+                    return Ok(());
+                }
+                loop {
+                    self.skip_whitespace();
+                    self.skip_value(depth + 1)?;
+                    self.skip_whitespace();
+                    if self.accept(b']') {
+                        return Ok(());
+                    }
+                    self.expect(b',')?;
+                }
+            }
+            b'{' => {
+                self.pos += 1;
+                self.skip_whitespace();
+                if self.accept(b'}') {
+                    return Ok(());
+                }
+                loop {
+                    self.skip_whitespace();
+                    self.parse_string()?;
+                    self.skip_whitespace();
+                    self.expect(b':')?;
+                    self.skip_whitespace();
+                    self.skip_value(depth + 1)?;
+                    self.skip_whitespace();
+                    if self.accept(b'}') {
+                        return Ok(());
+                    }
+                    self.expect(b',')?;
+                }
+            }
+            _ => self.skip_number(),
+        }
+    }
+}
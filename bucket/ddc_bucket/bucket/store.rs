@@ -1,8 +1,9 @@
-use super::entity::{Bucket, BucketId, BucketParams};
+use super::entity::{Bucket, BucketGrant, BucketId, BucketParams, BucketState, PendingRate};
+use crate::ddc_bucket::chunked::ChunkedLists;
 use crate::ddc_bucket::cluster::entity::ClusterId;
 use crate::ddc_bucket::flow::Flow;
 use crate::ddc_bucket::schedule::Schedule;
-use crate::ddc_bucket::{AccountId, Error::*, Result};
+use crate::ddc_bucket::{AccountId, Balance, Error::*, Result};
 use ink_prelude::vec::Vec;
 use ink_storage::traits::{SpreadAllocate, SpreadLayout};
 use ink_storage::Mapping;
@@ -12,10 +13,28 @@ use ink_storage::Mapping;
 pub struct BucketStore {
     pub next_bucket_id: u32,
     pub buckets: Mapping<BucketId, Bucket>,
-    pub writers: Mapping<BucketId, Vec<AccountId>>,
-    pub readers: Mapping<BucketId, Vec<AccountId>>,
+    pub writers: Mapping<BucketId, Vec<BucketGrant>>,
+    pub readers: Mapping<BucketId, Vec<BucketGrant>>,
+    pub pending_payers: Mapping<BucketId, AccountId>,
+    /// The proposed owner of a bucket, and whether the current payer keeps paying for it.
+    pub pending_owners: Mapping<BucketId, (AccountId, bool)>,
+    /// The IDs of the buckets of each owner.
+    pub owner_buckets: ChunkedLists<AccountId, BucketId>,
+    /// The IDs of the buckets of each cluster.
+    pub cluster_buckets: ChunkedLists<ClusterId, BucketId>,
+    /// The IDs of the buckets in grace period or suspended, by payer.
+    pub unpaid_buckets: ChunkedLists<AccountId, BucketId>,
+    /// The position in the unpaid buckets of each payer where the next refresh starts.
+    pub unpaid_cursors: Mapping<AccountId, u32>,
+    /// The announced increases of the rates of buckets.
+    pub pending_rates: Mapping<BucketId, PendingRate>,
+    /// The rates of the payment flows of suspended buckets, paused until their payer covers them again.
+    pub paused_rates: Mapping<BucketId, Balance>,
 }
 
+/// How many unpaid buckets of a payer are updated on a deposit.
+pub const MAX_UNPAID_BUCKETS_REFRESHED_PER_CALL: u32 = 10;
+
 impl BucketStore {
     #[must_use]
     pub fn create(
@@ -38,9 +57,12 @@ impl BucketStore {
             resource_consumption_cap: 0,
             public_availability: false,
             bucket_params,
+            state: BucketState::Active,
         };
 
         self.buckets.insert(&bucket_id, &bucket);
+        self.owner_buckets.push(owner_id, bucket_id);
+        self.cluster_buckets.push(cluster_id, bucket_id);
         bucket_id
     }
 
@@ -57,76 +79,223 @@ impl BucketStore {
         }
     }
 
-    // get accounts with permission for bucket writing
-    pub fn get_bucket_writers(&self, key: BucketId) -> Vec<AccountId> {
-        let writers: Vec<AccountId> = self
-            .writers
-            .get(&key)
-            .unwrap_or(Vec::new())
-            .iter()
-            .cloned()
-            .collect();
-        return writers;
+    pub fn remove(&mut self, bucket_id: BucketId) {
+        if let Some(bucket) = self.buckets.get(bucket_id) {
+            self.owner_buckets.remove(bucket.owner_id, bucket_id);
+            self.unpaid_buckets.remove(bucket.flow.from, bucket_id);
+            self.cluster_buckets.remove(bucket.cluster_id, bucket_id);
+        }
+        self.buckets.remove(bucket_id);
+        self.writers.remove(bucket_id);
+        self.readers.remove(bucket_id);
+        self.pending_payers.remove(bucket_id);
+        self.pending_owners.remove(bucket_id);
+        self.pending_rates.remove(bucket_id);
+        self.paused_rates.remove(bucket_id);
     }
 
-    // grant permission for bucket writing for some account
-    pub fn grant_writer_permission(&mut self, key: BucketId, writer: AccountId) -> Result<()> {
-        if !self.writers.contains(&key) {
-            let empty_vec: Vec<AccountId> = Vec::new();
-            self.writers.insert(key, &empty_vec);
-        }
+    /// Move a bucket from the index of its previous owner to the index of its new owner.
+    pub fn change_owner_index(
+        &mut self,
+        bucket_id: BucketId,
+        previous_owner_id: AccountId,
+        owner_id: AccountId,
+    ) {
+        self.owner_buckets.remove(previous_owner_id, bucket_id);
+        self.owner_buckets.push(owner_id, bucket_id);
+    }
 
-        let mut writers = self.writers.get(&key).unwrap();
-        writers.push(writer);
-        self.writers.insert(key, &writers);
+    /// Move a bucket from the index of its previous cluster to the index of its new cluster.
+    pub fn change_cluster_index(
+        &mut self,
+        bucket_id: BucketId,
+        previous_cluster_id: ClusterId,
+        cluster_id: ClusterId,
+    ) {
+        self.cluster_buckets.remove(previous_cluster_id, bucket_id);
+        self.cluster_buckets.push(cluster_id, bucket_id);
+    }
 
-        Ok(())
+    /// Get the IDs of the buckets of an owner, starting at `offset` in the index.
+    /// Return also the total number of buckets of the owner.
+    pub fn list_owner_buckets(
+        &self,
+        owner_id: AccountId,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<BucketId>, u32) {
+        self.owner_buckets.get_page(owner_id, offset, limit)
     }
 
-    // revoke permission for bucket writing for some account
-    pub fn revoke_writer_permission(&mut self, key: BucketId, writer: AccountId) -> Result<()> {
-        let mut writers = self.writers.get(&key).unwrap();
-        if let Some(pos) = writers.iter().position(|x| *x == writer) {
-            writers.remove(pos);
-            self.writers.insert(key, &writers);
+    /// Track whether the payer of a bucket covers its rent.
+    pub fn set_unpaid(&mut self, payer_id: AccountId, bucket_id: BucketId, unpaid: bool) {
+        if unpaid {
+            self.unpaid_buckets.push(payer_id, bucket_id);
+        } else {
+            self.unpaid_buckets.remove(payer_id, bucket_id);
         }
+    }
 
-        Ok(())
+    pub fn get_unpaid_buckets(&self, payer_id: AccountId) -> Vec<BucketId> {
+        self.unpaid_buckets.get_all(payer_id)
     }
 
-    // get accounts with permission for bucket reading
-    pub fn get_bucket_readers(&self, key: BucketId) -> Vec<AccountId> {
-        self.readers
-            .get(&key)
-            .unwrap_or(Vec::new())
-            .iter()
-            .cloned()
-            .collect()
+    /// Get up to `limit` unpaid buckets of a payer, continuing from the previous call.
+    /// The list is walked in rounds, so that every bucket is eventually returned.
+    pub fn next_unpaid_buckets(&mut self, payer_id: AccountId, limit: u32) -> Vec<BucketId> {
+        let len = self.unpaid_buckets.len(payer_id);
+        let offset = match self.unpaid_cursors.get(payer_id) {
+            Some(cursor) if cursor < len => cursor,
+            _ => 0,
+        };
+        let (bucket_ids, _) = self.unpaid_buckets.get_page(payer_id, offset, limit);
+
+        let cursor = offset + bucket_ids.len() as u32;
+        if cursor < len {
+            self.unpaid_cursors.insert(payer_id, &cursor);
+        } else {
+            self.unpaid_cursors.remove(payer_id);
+        }
+        bucket_ids
+    }
+
+    pub fn propose_owner(&mut self, bucket_id: BucketId, owner_id: AccountId, keep_payer: bool) {
+        self.pending_owners
+            .insert(bucket_id, &(owner_id, keep_payer));
     }
 
-    // grant permission for bucket reading for some account
-    pub fn grant_reader_permission(&mut self, key: BucketId, reader: AccountId) -> Result<()> {
-        if !self.readers.contains(&key) {
-            let empty_vec: Vec<AccountId> = Vec::new();
-            self.readers.insert(key, &empty_vec);
+    pub fn cancel_owner_proposal(&mut self, bucket_id: BucketId) {
+        self.pending_owners.remove(bucket_id);
+    }
+
+    /// Accept the ownership of a bucket. Return whether the current payer keeps paying for it.
+    pub fn accept_owner(&mut self, bucket_id: BucketId, owner_id: AccountId) -> Result<bool> {
+        match self.pending_owners.get(bucket_id) {
+            Some((pending_owner_id, keep_payer)) if pending_owner_id == owner_id => {
+                self.pending_owners.remove(bucket_id);
+                Ok(keep_payer)
+            }
+            _ => Err(OnlyPendingOwner),
         }
+    }
+
+    pub fn propose_payer(&mut self, bucket_id: BucketId, payer_id: AccountId) {
+        self.pending_payers.insert(bucket_id, &payer_id);
+    }
 
-        let mut readers = self.readers.get(&key).unwrap();
-        readers.push(reader);
-        self.readers.insert(key, &readers);
+    pub fn cancel_payer_proposal(&mut self, bucket_id: BucketId) {
+        self.pending_payers.remove(bucket_id);
+    }
 
+    pub fn accept_payer(&mut self, bucket_id: BucketId, payer_id: AccountId) -> Result<()> {
+        if self.pending_payers.get(bucket_id) != Some(payer_id) {
+            return Err(OnlyPendingPayer);
+        }
+        self.pending_payers.remove(bucket_id);
         Ok(())
     }
 
+    // get accounts with an active permission for bucket writing
+    pub fn get_bucket_writers(&self, key: BucketId, now_ms: u64) -> Vec<AccountId> {
+        Self::active_grantees(&self.writers, key, now_ms)
+    }
+
+    // grant permission for bucket writing for some account, or change its expiry
+    pub fn grant_writer_permission(
+        &mut self,
+        key: BucketId,
+        writer: AccountId,
+        expires_at_ms: Option<u64>,
+    ) {
+        Self::grant(&mut self.writers, key, writer, expires_at_ms);
+    }
+
     // revoke permission for bucket writing for some account
-    pub fn revoke_reader_permission(&mut self, key: BucketId, reader: AccountId) -> Result<()> {
-        let mut readers = self.readers.get(&key).unwrap();
+    pub fn revoke_writer_permission(&mut self, key: BucketId, writer: AccountId) {
+        Self::revoke(&mut self.writers, key, writer);
+    }
+
+    // get accounts with an active permission for bucket reading
+    pub fn get_bucket_readers(&self, key: BucketId, now_ms: u64) -> Vec<AccountId> {
+        Self::active_grantees(&self.readers, key, now_ms)
+    }
+
+    // grant permission for bucket reading for some account, or change its expiry
+    pub fn grant_reader_permission(
+        &mut self,
+        key: BucketId,
+        reader: AccountId,
+        expires_at_ms: Option<u64>,
+    ) {
+        Self::grant(&mut self.readers, key, reader, expires_at_ms);
+    }
 
-        if let Some(pos) = readers.iter().position(|x| *x == reader) {
-            readers.remove(pos);
-            self.readers.insert(key, &readers);
+    // revoke permission for bucket reading for some account
+    pub fn revoke_reader_permission(&mut self, key: BucketId, reader: AccountId) {
+        Self::revoke(&mut self.readers, key, reader);
+    }
+
+    /// Remove the expired permissions of a bucket. Return how many were removed.
+    pub fn purge_expired_grants(&mut self, key: BucketId, now_ms: u64) -> u32 {
+        Self::purge_expired(&mut self.writers, key, now_ms)
+            + Self::purge_expired(&mut self.readers, key, now_ms)
+    }
+
+    fn active_grantees(
+        grants: &Mapping<BucketId, Vec<BucketGrant>>,
+        key: BucketId,
+        now_ms: u64,
+    ) -> Vec<AccountId> {
+        grants
+            .get(key)
+            .unwrap_or_default()
+            .iter()
+            .filter(|grant| grant.is_active(now_ms))
+            .map(|grant| grant.account_id)
+            .collect()
+    }
+
+    fn grant(
+        grants: &mut Mapping<BucketId, Vec<BucketGrant>>,
+        key: BucketId,
+        account_id: AccountId,
+        expires_at_ms: Option<u64>,
+    ) {
+        let mut list = grants.get(key).unwrap_or_default();
+        match list.iter_mut().find(|grant| grant.account_id == account_id) {
+            Some(grant) => grant.expires_at_ms = expires_at_ms,
+            None => list.push(BucketGrant {
+                account_id,
+                expires_at_ms,
+            }),
         }
+        grants.insert(key, &list);
+    }
 
-        Ok(())
+    fn revoke(
+        grants: &mut Mapping<BucketId, Vec<BucketGrant>>,
+        key: BucketId,
+        account_id: AccountId,
+    ) {
+        let mut list = grants.get(key).unwrap_or_default();
+        if let Some(pos) = list.iter().position(|grant| grant.account_id == account_id) {
+            list.remove(pos);
+            grants.insert(key, &list);
+        }
+    }
+
+    fn purge_expired(
+        grants: &mut Mapping<BucketId, Vec<BucketGrant>>,
+        key: BucketId,
+        now_ms: u64,
+    ) -> u32 {
+        let mut list = grants.get(key).unwrap_or_default();
+        let len_before = list.len();
+        list.retain(|grant| grant.is_active(now_ms));
+        let purged = (len_before - list.len()) as u32;
+        if purged > 0 {
+            grants.insert(key, &list);
+        }
+        purged
     }
 }
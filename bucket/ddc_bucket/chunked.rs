@@ -0,0 +1,168 @@
+//! The ChunkedLists data structure stores a list of items per key, split into chunks.
+//!
+//! A list is not limited by the buffer available to a single packed value, which holds a chunk only.
+
+use ink_prelude::vec::Vec;
+use ink_storage::traits::{PackedLayout, SpreadAllocate, SpreadLayout};
+use ink_storage::Mapping;
+use scale::EncodeLike;
+
+// https://use.ink/datastructures/storage-layout#packed-vs-non-packed-layout
+// There is a buffer with only limited capacity (around 16KB in the default configuration) available.
+pub const CHUNK_LEN: u32 = 256;
+
+#[derive(SpreadAllocate, SpreadLayout, Default)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ChunkedLists<K, T>
+where
+    K: PackedLayout + EncodeLike + Copy,
+    T: PackedLayout + EncodeLike + Copy,
+{
+    // the number of items of each list
+    lens: Mapping<K, u32>,
+    // the items of each list, by index of chunk
+    chunks: Mapping<(K, u32), Vec<T>>,
+    // the position of each item within its list
+    positions: Mapping<(K, T), u32>,
+}
+
+// The derived layout would not bound the generic types by TypeInfo.
+#[cfg(feature = "std")]
+impl<K, T> ink_storage::traits::StorageLayout for ChunkedLists<K, T>
+where
+    K: PackedLayout + EncodeLike + Copy + scale_info::TypeInfo + 'static,
+    T: PackedLayout + EncodeLike + Copy + scale_info::TypeInfo + 'static,
+{
+    fn layout(key_ptr: &mut ink_primitives::KeyPtr) -> ink_metadata::layout::Layout {
+        use ink_metadata::layout::{FieldLayout, Layout, StructLayout};
+        use ink_storage::traits::StorageLayout;
+
+        Layout::Struct(StructLayout::new([
+            FieldLayout::new("lens", <Mapping<K, u32> as StorageLayout>::layout(key_ptr)),
+            FieldLayout::new(
+                "chunks",
+                <Mapping<(K, u32), Vec<T>> as StorageLayout>::layout(key_ptr),
+            ),
+            FieldLayout::new(
+                "positions",
+                <Mapping<(K, T), u32> as StorageLayout>::layout(key_ptr),
+            ),
+        ]))
+    }
+}
+
+impl<K, T> ChunkedLists<K, T>
+where
+    K: PackedLayout + EncodeLike + Copy,
+    T: PackedLayout + EncodeLike + Copy,
+{
+    /// Whether a list was created for the key, even if it is empty.
+    pub fn exists(&self, key: K) -> bool {
+        self.lens.contains(key)
+    }
+
+    pub fn create(&mut self, key: K) {
+        self.lens.insert(key, &0);
+    }
+
+    pub fn len(&self, key: K) -> u32 {
+        self.lens.get(key).unwrap_or(0)
+    }
+
+    pub fn contains(&self, key: K, item: T) -> bool {
+        self.positions.contains((key, item))
+    }
+
+    /// Return up to `limit` items of the list starting at `offset`, and the total number of items.
+    pub fn get_page(&self, key: K, offset: u32, limit: u32) -> (Vec<T>, u32) {
+        let len = self.len(key);
+        let end = offset.saturating_add(limit).min(len);
+
+        let mut items = Vec::new();
+        let mut position = offset;
+        while position < end {
+            let chunk = self
+                .chunks
+                .get((key, position / CHUNK_LEN))
+                .unwrap_or_default();
+            let start = (position % CHUNK_LEN) as usize;
+            let stop = chunk.len().min(start + (end - position) as usize);
+            if start >= stop {
+                break;
+            }
+            items.extend_from_slice(&chunk[start..stop]);
+            position += (stop - start) as u32;
+        }
+        (items, len)
+    }
+
+    pub fn get_all(&self, key: K) -> Vec<T> {
+        self.get_page(key, 0, u32::MAX).0
+    }
+
+    /// Append an item at the end of the list. An item that is already in the list is not added again.
+    pub fn push(&mut self, key: K, item: T) {
+        if self.contains(key, item) {
+            return;
+        }
+        let position = self.len(key);
+        let mut chunk = self
+            .chunks
+            .get((key, position / CHUNK_LEN))
+            .unwrap_or_default();
+        chunk.push(item);
+        self.chunks.insert((key, position / CHUNK_LEN), &chunk);
+        self.positions.insert((key, item), &position);
+        self.lens.insert(key, &(position + 1));
+    }
+
+    /// Remove an item from the list, moving the last item into its position.
+    /// Return whether the item was in the list.
+    pub fn remove(&mut self, key: K, item: T) -> bool {
+        let position = match self.positions.get((key, item)) {
+            Some(position) => position,
+            None => return false,
+        };
+        let last = self.len(key).saturating_sub(1);
+
+        let mut last_chunk = self.chunks.get((key, last / CHUNK_LEN)).unwrap_or_default();
+        if let Some(last_item) = last_chunk.pop() {
+            if position != last {
+                if position / CHUNK_LEN == last / CHUNK_LEN {
+                    last_chunk[(position % CHUNK_LEN) as usize] = last_item;
+                } else {
+                    let mut chunk = self
+                        .chunks
+                        .get((key, position / CHUNK_LEN))
+                        .unwrap_or_default();
+                    chunk[(position % CHUNK_LEN) as usize] = last_item;
+                    self.chunks.insert((key, position / CHUNK_LEN), &chunk);
+                }
+                self.positions.insert((key, last_item), &position);
+            }
+        }
+
+        if last_chunk.is_empty() {
+            self.chunks.remove((key, last / CHUNK_LEN));
+        } else {
+            self.chunks.insert((key, last / CHUNK_LEN), &last_chunk);
+        }
+        self.positions.remove((key, item));
+        self.lens.insert(key, &last);
+        true
+    }
+
+    /// Remove the list with all its items.
+    pub fn clear(&mut self, key: K) {
+        let len = self.len(key);
+        let mut chunk_index = 0;
+        while chunk_index * CHUNK_LEN < len {
+            for item in self.chunks.get((key, chunk_index)).unwrap_or_default() {
+                self.positions.remove((key, item));
+            }
+            self.chunks.remove((key, chunk_index));
+            chunk_index += 1;
+        }
+        self.lens.remove(key);
+    }
+}
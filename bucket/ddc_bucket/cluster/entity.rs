@@ -1,9 +1,11 @@
 //! The data structure of Clusters.
+use crate::ddc_bucket::bucket::entity::BucketId;
 use crate::ddc_bucket::cash::{Cash, Payable};
 use crate::ddc_bucket::cdn_node::entity::CdnNodeKey;
 use crate::ddc_bucket::node::entity::{NodeKey, Resource};
 use crate::ddc_bucket::Error::{InsufficientBalance, OnlyClusterManager};
-use crate::ddc_bucket::{AccountId, Balance, Error::*, Result, VNodeToken};
+use crate::ddc_bucket::{AccountId, Balance, Error::*, Hash, Result, VNodeToken};
+use ink_env::hash::{Blake2x256, HashOutput};
 use ink_prelude::string::String;
 use ink_prelude::vec::Vec;
 use ink_primitives::Key;
@@ -15,7 +17,6 @@ pub type ClusterParams = String;
 
 // https://use.ink/datastructures/storage-layout#packed-vs-non-packed-layout
 // There is a buffer with only limited capacity (around 16KB in the default configuration) available.
-pub const MAX_CLUSTER_NODES_LEN_IN_VEC: usize = 200;
 pub const MAX_CLUSTER_CDN_NODES_LEN_IN_VEC: usize = 200;
 
 #[derive(Clone, PartialEq, Encode, Decode, SpreadAllocate, PackedLayout, SpreadLayout)]
@@ -24,8 +25,7 @@ pub struct Cluster {
     pub manager_id: AccountId,
     pub cluster_params: ClusterParams,
 
-    // storage nodes
-    pub nodes_keys: Vec<NodeKey>,
+    // storage nodes, whose keys are kept in the cluster store
     pub resource_per_v_node: Resource,
     pub resource_used: Resource,
     pub revenues: Cash,
@@ -50,7 +50,8 @@ impl ink_storage::traits::PackedAllocate for Cluster {
 pub struct ClusterInfo {
     pub cluster_id: ClusterId,
     pub cluster: Cluster,
-    pub cluster_v_nodes: Vec<NodeVNodesInfo>,
+    /// The number of Storage nodes, to list with `cluster_list_nodes_v_nodes`.
+    pub nodes_len: u32,
 }
 
 #[derive(Clone, PartialEq, Encode, Decode)]
@@ -60,6 +61,204 @@ pub struct NodeVNodesInfo {
     pub v_nodes: Vec<VNodeToken>,
 }
 
+/// A scoped role that the manager of a cluster can grant to operators.
+/// The manager has all roles, and only the manager can remove the cluster or transfer it.
+#[derive(Clone, Copy, PartialEq, Encode, Decode, SpreadLayout, PackedLayout)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub enum ClusterRole {
+    /// Add, remove, replace and reset the nodes and CDN nodes of the cluster.
+    ManageNodes,
+    /// Set the status of the nodes and CDN nodes in the cluster.
+    SetNodeStatus,
+    /// Set the rate of the CDN service.
+    SetCdnRate,
+    /// Set the params and the resource per vnode of the cluster.
+    SetParams,
+    /// Manage the buckets of the cluster on behalf of their owners.
+    ManageBuckets,
+}
+
+/// The share of revenues of a provider for all its nodes in a cluster.
+#[derive(Clone, PartialEq, Encode, Decode, SpreadLayout, PackedLayout)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub struct ProviderPayout {
+    pub provider_id: AccountId,
+    pub amount: Balance,
+}
+
+impl ProviderPayout {
+    /// Add the share of a node to the payout of its provider.
+    pub fn aggregate(payouts: &mut Vec<ProviderPayout>, provider_id: AccountId, amount: Balance) {
+        match payouts
+            .iter_mut()
+            .find(|payout| payout.provider_id == provider_id)
+        {
+            Some(payout) => payout.amount += amount,
+            None => payouts.push(ProviderPayout {
+                provider_id,
+                amount,
+            }),
+        }
+    }
+}
+
+/// The step of a round of distribution of revenues.
+#[derive(Clone, Copy, PartialEq, Encode, Decode, SpreadLayout, PackedLayout)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub enum DistributionPhase {
+    /// Sum the weights of the nodes.
+    Weighing,
+    /// Set aside the share of each node for its provider.
+    Sharing,
+    /// Pay the providers.
+    Paying,
+}
+
+/// A round of distribution of the revenues of a cluster to the providers, processed over several calls.
+/// The payouts of the providers are stored apart, in chunks.
+#[derive(Clone, PartialEq, Encode, Decode, SpreadLayout, PackedLayout)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub struct RevenueDistribution {
+    pub phase: DistributionPhase,
+    /// The revenues to share, net of fees, as of the start of the round.
+    pub revenues: Balance,
+    /// The total weight of the nodes, complete once they are weighed.
+    pub total_weight: Balance,
+    /// The position in the nodes of the cluster of the next node to weigh or to share.
+    pub next_node_index: u32,
+    /// The number of providers to pay, complete once the nodes are shared.
+    pub payouts_len: u32,
+    /// The position in the payouts of the next provider to pay.
+    pub next_index: u32,
+}
+
+impl RevenueDistribution {
+    pub fn new(revenues: Balance) -> Self {
+        RevenueDistribution {
+            phase: DistributionPhase::Weighing,
+            revenues,
+            total_weight: 0,
+            next_node_index: 0,
+            payouts_len: 0,
+            next_index: 0,
+        }
+    }
+
+    /// Whether the nodes of the cluster are being processed, so that they must not change.
+    pub fn is_over_nodes(&self) -> bool {
+        self.phase != DistributionPhase::Paying
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.phase == DistributionPhase::Paying && self.next_index >= self.payouts_len
+    }
+}
+
+/// The step of a change of the resource per vnode of a cluster.
+#[derive(Clone, Copy, PartialEq, Encode, Decode, SpreadLayout, PackedLayout)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub enum ResourceUpdatePhase {
+    /// Verify that every node has the resource for the new value.
+    Checking,
+    /// Move the resource reserved by every node to the new value.
+    Applying,
+}
+
+/// A change of the resource per vnode of a cluster, processed over several calls.
+#[derive(Clone, PartialEq, Encode, Decode, SpreadLayout, PackedLayout)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub struct ResourceUpdate {
+    pub phase: ResourceUpdatePhase,
+    /// The resource per vnode reserved by the nodes before the change.
+    pub from_resource_per_v_node: Resource,
+    /// The resource per vnode reserved by the nodes after the change.
+    pub to_resource_per_v_node: Resource,
+    /// The position in the nodes of the cluster of the next node to check or to apply.
+    pub next_node_index: u32,
+}
+
+impl ResourceUpdate {
+    pub fn new(from_resource_per_v_node: Resource, to_resource_per_v_node: Resource) -> Self {
+        ResourceUpdate {
+            phase: ResourceUpdatePhase::Checking,
+            from_resource_per_v_node,
+            to_resource_per_v_node,
+            next_node_index: 0,
+        }
+    }
+}
+
+/// The votes of the validators on the CDN usage of a cluster during an era.
+#[derive(Clone, PartialEq, Encode, Decode, SpreadLayout, PackedLayout, Default)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub struct CdnReport {
+    /// The hash of the aggregates submitted by each validator.
+    pub votes: Vec<(AccountId, Hash)>,
+    /// The hash agreed by a quorum of validators.
+    pub agreed_hash: Option<Hash>,
+    /// Whether the agreed aggregates were charged.
+    pub applied: bool,
+    /// The validators that submitted another hash than the agreed one, for review.
+    pub dissenters: Vec<AccountId>,
+}
+
+impl CdnReport {
+    /// The hash of the aggregates of a cluster for an era, as submitted by validators.
+    pub fn hash_aggregates(
+        cluster_id: ClusterId,
+        era: u64,
+        aggregates_accounts: &[(AccountId, u128)],
+        aggregates_nodes: &[(CdnNodeKey, u128)],
+        aggregates_buckets: &[(BucketId, Resource)],
+    ) -> Hash {
+        let mut output = <Blake2x256 as HashOutput>::Type::default();
+        ink_env::hash_encoded::<Blake2x256, _>(
+            &(
+                cluster_id,
+                era,
+                aggregates_accounts,
+                aggregates_nodes,
+                aggregates_buckets,
+            ),
+            &mut output,
+        );
+        Hash::from(output)
+    }
+
+    /// Record the vote of a validator, replacing its previous vote.
+    /// Return whether this vote made a quorum agree on its hash.
+    pub fn vote(&mut self, validator_id: AccountId, report_hash: Hash, quorum: u32) -> bool {
+        self.votes.retain(|(voter_id, _)| *voter_id != validator_id);
+        self.votes.push((validator_id, report_hash));
+
+        if let Some(agreed_hash) = self.agreed_hash {
+            self.dissenters.retain(|voter_id| *voter_id != validator_id);
+            if report_hash != agreed_hash {
+                self.dissenters.push(validator_id);
+            }
+            return false;
+        }
+
+        let agreeing = self
+            .votes
+            .iter()
+            .filter(|(_, hash)| *hash == report_hash)
+            .count();
+        if agreeing < quorum as usize {
+            return false;
+        }
+
+        self.agreed_hash = Some(report_hash);
+        self.dissenters = self
+            .votes
+            .iter()
+            .filter(|(_, hash)| *hash != report_hash)
+            .map(|(voter_id, _)| *voter_id)
+            .collect();
+        true
+    }
+}
+
 pub const CLUSTER_PARAMS_MAX_LEN: usize = 100_000;
 pub const CDN_USD_PER_GB: Balance = 104_857_600;
 pub const KB_PER_GB: Balance = 1_000_000;
@@ -73,7 +272,6 @@ impl Cluster {
         let mut cluster = Cluster {
             manager_id,
             cluster_params: ClusterParams::default(),
-            nodes_keys: Vec::new(),
             resource_per_v_node,
             resource_used: Resource::from(0u32),
             revenues: Cash(0),
@@ -93,26 +291,8 @@ impl Cluster {
             .ok_or(OnlyClusterManager)
     }
 
-    pub fn only_without_nodes(&self) -> Result<()> {
-        if self.nodes_keys.is_empty() && self.cdn_nodes_keys.is_empty() {
-            Ok(())
-        } else {
-            Err(ClusterIsNotEmpty)
-        }
-    }
-
-    pub fn add_node(&mut self, node_key: NodeKey) -> Result<()> {
-        if self.nodes_keys.len() + 1 > MAX_CLUSTER_NODES_LEN_IN_VEC {
-            return Err(NodesSizeExceedsLimit);
-        }
-        self.nodes_keys.push(node_key);
-        Ok(())
-    }
-
-    pub fn remove_node(&mut self, node_key: NodeKey) {
-        if let Some(pos) = self.nodes_keys.iter().position(|x| *x == node_key) {
-            self.nodes_keys.remove(pos);
-        }
+    pub fn set_manager(&mut self, manager_id: AccountId) {
+        self.manager_id = manager_id;
     }
 
     pub fn add_cdn_node(&mut self, cdn_node_key: CdnNodeKey) -> Result<()> {
@@ -153,6 +333,10 @@ impl Cluster {
         self.resource_used = self.resource_used + amount;
     }
 
+    pub fn release_resource(&mut self, amount: Resource) {
+        self.resource_used -= amount;
+    }
+
     pub fn cdn_get_revenue_cere(&self) -> Cash {
         self.cdn_revenues
     }
@@ -5,21 +5,31 @@ use ink_prelude::vec::Vec;
 use crate::ddc_bucket::bucket::entity::BucketId;
 use crate::ddc_bucket::cash::{Cash, Payable};
 use crate::ddc_bucket::cdn_node::entity::{CdnNode, CdnNodeKey};
-use crate::ddc_bucket::cluster::entity::{ClusterInfo, NodeVNodesInfo, KB_PER_GB};
+use crate::ddc_bucket::cluster::entity::{
+    CdnReport, Cluster, ClusterInfo, ClusterRole, DistributionPhase, NodeVNodesInfo,
+    ProviderPayout, ResourceUpdate, ResourceUpdatePhase, RevenueDistribution, KB_PER_GB,
+};
+use crate::ddc_bucket::committer::store::Phase;
 use crate::ddc_bucket::node::entity::{Node, NodeKey, Resource};
 use crate::ddc_bucket::perm::entity::Permission;
 use crate::ddc_bucket::topology::store::VNodeToken;
 use crate::ddc_bucket::ClusterNodeReplaced;
 use crate::ddc_bucket::{
-    AccountId, Balance, ClusterCdnNodeAdded, ClusterCdnNodeRemoved, ClusterCdnNodeStatusSet,
-    ClusterCreated, ClusterDistributeCdnRevenues, ClusterDistributeRevenues, ClusterNodeAdded,
-    ClusterNodeRemoved, ClusterNodeReset, ClusterNodeStatusSet, ClusterParamsSet, ClusterRemoved,
-    ClusterReserveResource, DdcBucket, Error::*, NodeStatusInCluster, PermissionGranted,
+    AccountId, Balance, CdnReportAgreed, CdnReportApplied, CdnReportSubmitted, CdnRevenueSettled,
+    ClusterCdnNodeAdded, ClusterCdnNodeRemoved, ClusterCdnNodeStatusSet, ClusterCreated,
+    ClusterDistributeCdnRevenues, ClusterDistributeRevenues, ClusterDistributionCompleted,
+    ClusterDistributionProgress, ClusterManagerTransferProposed, ClusterManagerTransferred,
+    ClusterNodeAdded, ClusterNodeRemoved, ClusterNodeReset, ClusterNodeStatusSet,
+    ClusterOperatorRoleGranted, ClusterOperatorRoleRevoked, ClusterParamsSet, ClusterRemoved,
+    ClusterReserveResource, DdcBucket, Error::*, Hash, NodeStatusInCluster, PermissionGranted,
     PermissionRevoked, Result, BASIS_POINTS,
 };
 
 use super::entity::{ClusterId, ClusterParams};
 
+/// The payments computed from CDN usage aggregates, by account or node.
+type CdnPayments<K> = Vec<(K, Balance)>;
+
 impl DdcBucket {
     pub fn message_cluster_create(
         &mut self,
@@ -49,17 +59,15 @@ impl DdcBucket {
         node_key: NodeKey,
         v_nodes: Vec<VNodeToken>,
     ) -> Result<()> {
-        let caller = Self::env().caller();
-
         let mut node: Node = self.nodes.get(node_key)?;
         node.only_without_cluster()?;
-        self.only_trusted_cluster_manager(node.provider_id)?;
 
         let mut cluster = self.clusters.get(cluster_id)?;
-        cluster.only_manager(caller)?;
+        self.only_cluster_manager_or_operator(cluster_id, &cluster, ClusterRole::ManageNodes)?;
+        self.only_trusted_cluster_manager(cluster.manager_id, node.provider_id)?;
+        self.clusters.only_unlocked_nodes(cluster_id)?;
 
         node.set_cluster(cluster_id, NodeStatusInCluster::ADDING);
-        cluster.add_node(node_key)?;
         for _v_node in &v_nodes {
             node.reserve_resource(cluster.resource_per_v_node)?;
             cluster.increase_rent(node.rent_v_node_per_month);
@@ -67,6 +75,7 @@ impl DdcBucket {
 
         self.nodes.update(node_key, &node)?;
         self.clusters.update(cluster_id, &cluster)?;
+        self.clusters.add_node(cluster_id, node_key);
 
         self.topology
             .add_node(cluster_id, node_key, v_nodes.clone())?;
@@ -90,13 +99,22 @@ impl DdcBucket {
         let mut node = self.nodes.get(node_key)?;
         let mut cluster = self.clusters.get(cluster_id)?;
 
-        if !cluster.only_manager(caller).is_ok() && !node.only_provider(caller).is_ok() {
+        if !self.is_cluster_manager_or_operator(
+            cluster_id,
+            &cluster,
+            caller,
+            ClusterRole::ManageNodes,
+        ) && !node.only_provider(caller).is_ok()
+        {
             return Err(OnlyClusterManagerOrNodeProvider);
         }
         node.only_with_cluster(cluster_id)?;
+        self.clusters.only_unlocked_nodes(cluster_id)?;
 
+        let was_active = node.status_in_cluster == Some(NodeStatusInCluster::ACTIVE);
+        self.clusters
+            .update_active_nodes(cluster_id, was_active, false);
         node.unset_cluster();
-        cluster.remove_node(node_key);
         let v_nodes = self.topology.get_v_nodes_by_node(node_key);
         for _v_node in &v_nodes {
             node.release_resource(cluster.resource_per_v_node);
@@ -105,6 +123,7 @@ impl DdcBucket {
 
         self.nodes.update(node_key, &node)?;
         self.clusters.update(cluster_id, &cluster)?;
+        self.clusters.remove_node(cluster_id, node_key);
 
         self.topology.remove_node(cluster_id, node_key)?;
 
@@ -122,12 +141,11 @@ impl DdcBucket {
         v_nodes: Vec<VNodeToken>,
         new_node_key: NodeKey,
     ) -> Result<()> {
-        let caller = Self::env().caller();
-
         let cluster = self.clusters.get(cluster_id)?;
         let mut new_node = self.nodes.get(new_node_key)?;
         new_node.only_with_cluster(cluster_id)?;
-        cluster.only_manager(caller)?;
+        self.only_cluster_manager_or_operator(cluster_id, &cluster, ClusterRole::ManageNodes)?;
+        self.clusters.only_unlocked_nodes(cluster_id)?;
 
         // Give back resources to the old node for all its v_nodes
         for v_node in &v_nodes {
@@ -161,13 +179,12 @@ impl DdcBucket {
         node_key: NodeKey,
         new_v_nodes: Vec<VNodeToken>,
     ) -> Result<()> {
-        let caller = Self::env().caller();
-
         let mut cluster = self.clusters.get(cluster_id)?;
 
         let mut node = self.nodes.get(node_key)?;
         node.only_with_cluster(cluster_id)?;
-        cluster.only_manager(caller)?;
+        self.only_cluster_manager_or_operator(cluster_id, &cluster, ClusterRole::ManageNodes)?;
+        self.clusters.only_unlocked_nodes(cluster_id)?;
 
         let old_v_nodes = self.topology.get_v_nodes_by_node(node_key);
 
@@ -200,19 +217,43 @@ impl DdcBucket {
         Ok(())
     }
 
+    pub fn message_cluster_add_node_auto(
+        &mut self,
+        cluster_id: ClusterId,
+        node_key: NodeKey,
+        v_nodes_count: u32,
+    ) -> Result<Vec<VNodeToken>> {
+        let v_nodes = self
+            .topology
+            .generate_v_nodes(cluster_id, node_key, v_nodes_count)?;
+        self.message_cluster_add_node(cluster_id, node_key, v_nodes.clone())?;
+        Ok(v_nodes)
+    }
+
+    pub fn message_cluster_reset_node_auto(
+        &mut self,
+        cluster_id: ClusterId,
+        node_key: NodeKey,
+        v_nodes_count: u32,
+    ) -> Result<Vec<VNodeToken>> {
+        let v_nodes = self
+            .topology
+            .generate_v_nodes(cluster_id, node_key, v_nodes_count)?;
+        self.message_cluster_reset_node(cluster_id, node_key, v_nodes.clone())?;
+        Ok(v_nodes)
+    }
+
     pub fn message_cluster_add_cdn_node(
         &mut self,
         cluster_id: ClusterId,
         cdn_node_key: CdnNodeKey,
     ) -> Result<()> {
-        let caller = Self::env().caller();
-
         let mut cdn_node: CdnNode = self.cdn_nodes.get(cdn_node_key)?;
         cdn_node.only_without_cluster()?;
-        self.only_trusted_cluster_manager(cdn_node.provider_id)?;
 
         let mut cluster = self.clusters.get(cluster_id)?;
-        cluster.only_manager(caller)?;
+        self.only_cluster_manager_or_operator(cluster_id, &cluster, ClusterRole::ManageNodes)?;
+        self.only_trusted_cluster_manager(cluster.manager_id, cdn_node.provider_id)?;
 
         cdn_node.set_cluster(cluster_id, NodeStatusInCluster::ADDING);
         self.cdn_nodes.update(cdn_node_key, &cdn_node)?;
@@ -238,7 +279,13 @@ impl DdcBucket {
         let mut cdn_node: CdnNode = self.cdn_nodes.get(cdn_node_key)?;
         let mut cluster = self.clusters.get(cluster_id)?;
 
-        if !cluster.only_manager(caller).is_ok() && !cdn_node.only_provider(caller).is_ok() {
+        if !self.is_cluster_manager_or_operator(
+            cluster_id,
+            &cluster,
+            caller,
+            ClusterRole::ManageNodes,
+        ) && !cdn_node.only_provider(caller).is_ok()
+        {
             return Err(OnlyClusterManagerOrCdnNodeProvider);
         }
 
@@ -263,7 +310,7 @@ impl DdcBucket {
 
         let cluster = self.clusters.get(cluster_id)?;
         cluster.only_manager(caller)?;
-        cluster.only_without_nodes()?;
+        self.clusters.only_without_nodes(cluster_id, &cluster)?;
 
         self.clusters.remove(cluster_id);
         self.topology.remove_topology(cluster_id)?;
@@ -273,17 +320,146 @@ impl DdcBucket {
         Ok(())
     }
 
+    pub fn message_cluster_transfer_manager(
+        &mut self,
+        cluster_id: ClusterId,
+        manager_id: AccountId,
+    ) -> Result<()> {
+        let caller = Self::env().caller();
+        let cluster = self.clusters.get(cluster_id)?;
+        cluster.only_manager(caller)?;
+        self.clusters.propose_manager(cluster_id, manager_id);
+
+        Self::env().emit_event(ClusterManagerTransferProposed {
+            cluster_id,
+            manager_id,
+        });
+        Ok(())
+    }
+
+    pub fn message_cluster_cancel_manager_transfer(&mut self, cluster_id: ClusterId) -> Result<()> {
+        let caller = Self::env().caller();
+        let cluster = self.clusters.get(cluster_id)?;
+        cluster.only_manager(caller)?;
+        self.clusters.cancel_manager_proposal(cluster_id);
+        Ok(())
+    }
+
+    pub fn message_cluster_accept_manager(&mut self, cluster_id: ClusterId) -> Result<()> {
+        let caller = Self::env().caller();
+        let mut cluster = self.clusters.get(cluster_id)?;
+        self.clusters.accept_manager(cluster_id, caller)?;
+
+        cluster.set_manager(caller);
+        self.clusters.update(cluster_id, &cluster)?;
+
+        Self::env().emit_event(ClusterManagerTransferred {
+            cluster_id,
+            manager_id: caller,
+        });
+        Ok(())
+    }
+
+    pub fn message_cluster_grant_operator_role(
+        &mut self,
+        cluster_id: ClusterId,
+        operator_id: AccountId,
+        role: ClusterRole,
+    ) -> Result<()> {
+        let caller = Self::env().caller();
+        let cluster = self.clusters.get(cluster_id)?;
+        cluster.only_manager(caller)?;
+        self.clusters
+            .grant_operator_role(cluster_id, operator_id, role)?;
+
+        Self::env().emit_event(ClusterOperatorRoleGranted {
+            cluster_id,
+            operator_id,
+            role,
+        });
+        Ok(())
+    }
+
+    pub fn message_cluster_revoke_operator_role(
+        &mut self,
+        cluster_id: ClusterId,
+        operator_id: AccountId,
+        role: ClusterRole,
+    ) -> Result<()> {
+        let caller = Self::env().caller();
+        let cluster = self.clusters.get(cluster_id)?;
+        cluster.only_manager(caller)?;
+        self.clusters
+            .revoke_operator_role(cluster_id, operator_id, role);
+
+        Self::env().emit_event(ClusterOperatorRoleRevoked {
+            cluster_id,
+            operator_id,
+            role,
+        });
+        Ok(())
+    }
+
+    pub fn message_cluster_get_operator_roles(
+        &self,
+        cluster_id: ClusterId,
+        operator_id: AccountId,
+    ) -> Vec<ClusterRole> {
+        self.clusters.get_operator_roles(cluster_id, operator_id)
+    }
+
+    pub fn message_cluster_list_operators(
+        &self,
+        cluster_id: ClusterId,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<AccountId>, u32) {
+        self.clusters.list_operators(cluster_id, offset, limit)
+    }
+
+    /// Whether the account is the manager of the cluster, or one of its operators with the given role.
+    pub fn is_cluster_manager_or_operator(
+        &self,
+        cluster_id: ClusterId,
+        cluster: &Cluster,
+        account_id: AccountId,
+        role: ClusterRole,
+    ) -> bool {
+        cluster.only_manager(account_id).is_ok()
+            || self
+                .clusters
+                .has_operator_role(cluster_id, account_id, role)
+    }
+
+    pub fn only_cluster_manager_or_operator(
+        &self,
+        cluster_id: ClusterId,
+        cluster: &Cluster,
+        role: ClusterRole,
+    ) -> Result<()> {
+        let caller = Self::env().caller();
+        if self.is_cluster_manager_or_operator(cluster_id, cluster, caller, role) {
+            Ok(())
+        } else {
+            Err(OnlyClusterManager)
+        }
+    }
+
     pub fn message_cluster_set_node_status(
         &mut self,
         cluster_id: ClusterId,
         node_key: NodeKey,
         status_in_cluster: NodeStatusInCluster,
     ) -> Result<()> {
-        let caller = Self::env().caller();
-
         let mut node = self.nodes.get(node_key)?;
         let cluster = self.clusters.get(cluster_id)?;
-        cluster.only_manager(caller)?;
+        self.only_cluster_manager_or_operator(cluster_id, &cluster, ClusterRole::SetNodeStatus)?;
+        node.only_with_cluster(cluster_id)?;
+
+        let was_active = node.status_in_cluster == Some(NodeStatusInCluster::ACTIVE);
+        let is_active = status_in_cluster == NodeStatusInCluster::ACTIVE;
+        self.clusters
+            .update_active_nodes(cluster_id, was_active, is_active);
 
         node.change_status_in_cluster(status_in_cluster.clone());
         self.nodes.update(node_key, &node)?;
@@ -303,11 +479,9 @@ impl DdcBucket {
         cdn_node_key: CdnNodeKey,
         status_in_cluster: NodeStatusInCluster,
     ) -> Result<()> {
-        let caller = Self::env().caller();
-
         let mut cdn_node = self.cdn_nodes.get(cdn_node_key)?;
         let cluster = self.clusters.get(cluster_id)?;
-        cluster.only_manager(caller)?;
+        self.only_cluster_manager_or_operator(cluster_id, &cluster, ClusterRole::SetNodeStatus)?;
 
         cdn_node.change_status_in_cluster(status_in_cluster.clone());
         self.cdn_nodes.update(cdn_node_key, &cdn_node)?;
@@ -358,9 +532,8 @@ impl DdcBucket {
         cluster_id: ClusterId,
         cluster_params: ClusterParams,
     ) -> Result<()> {
-        let caller = Self::env().caller();
         let mut cluster = self.clusters.get(cluster_id)?;
-        cluster.only_manager(caller)?;
+        self.only_cluster_manager_or_operator(cluster_id, &cluster, ClusterRole::SetParams)?;
         cluster.set_params(cluster_params.clone())?;
         self.clusters.update(cluster_id, &cluster)?;
 
@@ -376,54 +549,92 @@ impl DdcBucket {
         &mut self,
         cluster_id: ClusterId,
         new_resource_per_v_node: Resource,
-    ) -> Result<()> {
-        let caller = Self::env().caller();
+        limit: u32,
+    ) -> Result<bool> {
         let mut cluster = self.clusters.get(cluster_id)?;
-        cluster.only_manager(caller)?;
+        self.only_cluster_manager_or_operator(cluster_id, &cluster, ClusterRole::SetParams)?;
 
-        let old_resource_per_v_node = cluster.resource_per_v_node;
-
-        cluster.set_resource_per_v_node(new_resource_per_v_node);
-        let cluster_v_nodes = self.topology.get_v_nodes_by_cluster(cluster_id);
-        let cluster_v_nodes_len: u32 = cluster_v_nodes.len().try_into().unwrap();
+        let mut update = match self.clusters.resource_updates.get(cluster_id) {
+            Some(update) if update.to_resource_per_v_node == new_resource_per_v_node => update,
+            // Nothing has changed yet, so a change being checked can be replaced by another one.
+            Some(update) if update.phase == ResourceUpdatePhase::Applying => {
+                return Err(ClusterNodesAreLocked)
+            }
+            _ => ResourceUpdate::new(cluster.resource_per_v_node, new_resource_per_v_node),
+        };
+        let from_resource = update.from_resource_per_v_node;
+        let to_resource = update.to_resource_per_v_node;
+        let nodes_len = self.clusters.count_nodes(cluster_id);
+        let mut budget = limit;
+
+        // Check the next page of nodes, without changing anything.
+        if update.phase == ResourceUpdatePhase::Checking {
+            let (nodes_keys, _) =
+                self.clusters
+                    .list_nodes_keys(cluster_id, update.next_node_index, budget);
+            for node_key in &nodes_keys {
+                let node = self.nodes.get(*node_key)?;
+                let v_nodes_len = self.topology.get_v_nodes_by_node(*node_key).len() as u32;
+                if node.free_resource + v_nodes_len * from_resource < v_nodes_len * to_resource {
+                    return Err(InsufficientNodeResources);
+                }
+            }
+            let page_len = nodes_keys.len() as u32;
+            update.next_node_index += page_len;
+            budget -= page_len;
+
+            if update.next_node_index >= nodes_len {
+                let cluster_v_nodes_len = self.topology.count_v_nodes_by_cluster(cluster_id);
+                if cluster.resource_used > cluster_v_nodes_len * to_resource {
+                    return Err(InsufficientClusterResources);
+                }
+                cluster.set_resource_per_v_node(to_resource);
+                self.clusters.update(cluster_id, &cluster)?;
 
-        let new_max_cluster_resource = cluster_v_nodes_len * new_resource_per_v_node;
-        if cluster.resource_used > new_max_cluster_resource {
-            return Err(InsufficientClusterResources);
+                update.phase = ResourceUpdatePhase::Applying;
+                update.next_node_index = 0;
+            }
         }
 
-        for v_node in cluster_v_nodes {
-            let node_key = self.topology.get_node_by_v_node(cluster_id, v_node)?;
-            let mut node = self.nodes.get(node_key)?;
-            node.release_resource(old_resource_per_v_node);
-            node.reserve_resource(new_resource_per_v_node)?;
-            self.nodes.update(node_key, &node)?;
+        // Move the resource reserved by the next page of nodes.
+        if update.phase == ResourceUpdatePhase::Applying {
+            let (nodes_keys, _) =
+                self.clusters
+                    .list_nodes_keys(cluster_id, update.next_node_index, budget);
+            for node_key in &nodes_keys {
+                let mut node = self.nodes.get(*node_key)?;
+                for _v_node in self.topology.get_v_nodes_by_node(*node_key) {
+                    node.release_resource(from_resource);
+                    node.reserve_resource(to_resource)?;
+                }
+                self.nodes.update(*node_key, &node)?;
+            }
+            update.next_node_index += nodes_keys.len() as u32;
         }
 
-        self.clusters.update(cluster_id, &cluster)?;
+        let completed =
+            update.phase == ResourceUpdatePhase::Applying && update.next_node_index >= nodes_len;
+        if completed {
+            self.clusters.resource_updates.remove(cluster_id);
 
-        Self::env().emit_event(ClusterReserveResource {
-            cluster_id,
-            resource: new_resource_per_v_node,
-        });
+            Self::env().emit_event(ClusterReserveResource {
+                cluster_id,
+                resource: to_resource,
+            });
+        } else {
+            self.clusters.resource_updates.insert(cluster_id, &update);
+        }
 
-        Ok(())
+        Ok(completed)
     }
 
     pub fn message_cluster_get(&self, cluster_id: ClusterId) -> Result<ClusterInfo> {
         let cluster = self.clusters.get(cluster_id)?;
 
-        let mut cluster_v_nodes: Vec<NodeVNodesInfo> = Vec::new();
-        for node_key in cluster.nodes_keys.clone() {
-            let v_nodes = self.topology.get_v_nodes_by_node(node_key.clone());
-            let v_nodes_info = NodeVNodesInfo { node_key, v_nodes };
-            cluster_v_nodes.push(v_nodes_info)
-        }
-
         Ok(ClusterInfo {
             cluster_id,
             cluster,
-            cluster_v_nodes,
+            nodes_len: self.clusters.count_nodes(cluster_id),
         })
     }
 
@@ -449,18 +660,11 @@ impl DdcBucket {
                 }
             }
 
-            let mut cluster_v_nodes: Vec<NodeVNodesInfo> = Vec::new();
-            for node_key in cluster.nodes_keys.clone() {
-                let v_nodes = self.topology.get_v_nodes_by_node(node_key.clone());
-                let v_nodes_info = NodeVNodesInfo { node_key, v_nodes };
-                cluster_v_nodes.push(v_nodes_info)
-            }
-
             // Include the complete status of matched items.
             let cluster_info = ClusterInfo {
                 cluster_id,
                 cluster,
-                cluster_v_nodes,
+                nodes_len: self.clusters.count_nodes(cluster_id),
             };
 
             clusters.push(cluster_info);
@@ -471,9 +675,133 @@ impl DdcBucket {
         )
     }
 
-    pub fn message_cluster_distribute_revenues(&mut self, cluster_id: ClusterId) -> Result<()> {
+    pub fn message_cluster_list_nodes(
+        &self,
+        cluster_id: ClusterId,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<NodeKey>, u32) {
+        self.clusters.list_nodes_keys(cluster_id, offset, limit)
+    }
+
+    pub fn message_cluster_list_nodes_v_nodes(
+        &self,
+        cluster_id: ClusterId,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<NodeVNodesInfo>, u32) {
+        let (nodes_keys, nodes_len) = self.clusters.list_nodes_keys(cluster_id, offset, limit);
+        let cluster_v_nodes = nodes_keys
+            .into_iter()
+            .map(|node_key| NodeVNodesInfo {
+                node_key,
+                v_nodes: self.topology.get_v_nodes_by_node(node_key),
+            })
+            .collect();
+        (cluster_v_nodes, nodes_len)
+    }
+
+    pub fn message_cluster_distribute_revenues(
+        &mut self,
+        cluster_id: ClusterId,
+        limit: u32,
+    ) -> Result<bool> {
         let mut cluster = self.clusters.get(cluster_id)?;
 
+        let mut distribution = match self.clusters.distributions.get(cluster_id) {
+            Some(distribution) => distribution,
+            None => self.cluster_start_distribution(&mut cluster)?,
+        };
+        let nodes_len = self.clusters.count_nodes(cluster_id);
+        let mut budget = limit;
+
+        // Weigh the next page of nodes by their contribution to the rent of the cluster,
+        // i.e. their number of vnodes times their rent per vnode.
+        if distribution.phase == DistributionPhase::Weighing {
+            let (nodes_keys, _) =
+                self.clusters
+                    .list_nodes_keys(cluster_id, distribution.next_node_index, budget);
+            for node_key in &nodes_keys {
+                let (_, weight) = self.cluster_node_weight(*node_key)?;
+                distribution.total_weight += weight;
+            }
+            distribution.next_node_index += nodes_keys.len() as u32;
+            budget -= nodes_keys.len() as u32;
+
+            if distribution.next_node_index >= nodes_len {
+                distribution.phase = DistributionPhase::Sharing;
+                distribution.next_node_index = 0;
+            }
+        }
+
+        // Set aside the share of the next page of nodes for their providers.
+        // The remainder of the integer division stays in the revenues for the next round.
+        if distribution.phase == DistributionPhase::Sharing {
+            let (nodes_keys, _) =
+                self.clusters
+                    .list_nodes_keys(cluster_id, distribution.next_node_index, budget);
+            for node_key in &nodes_keys {
+                let (provider_id, weight) = self.cluster_node_weight(*node_key)?;
+                let share = match distribution.total_weight {
+                    0 => 0,
+                    total_weight => distribution.revenues * weight / total_weight,
+                };
+                cluster.revenues.pay(Payable(share))?;
+                self.clusters.add_payout(
+                    cluster_id,
+                    &mut distribution,
+                    provider_id,
+                    *node_key,
+                    share,
+                );
+            }
+            distribution.next_node_index += nodes_keys.len() as u32;
+            budget -= nodes_keys.len() as u32;
+
+            if distribution.next_node_index >= nodes_len {
+                distribution.phase = DistributionPhase::Paying;
+            }
+        }
+
+        // Pay the next page of providers, once for all their nodes.
+        if distribution.phase == DistributionPhase::Paying {
+            for (payout, nodes_keys) in
+                self.clusters
+                    .take_payouts(cluster_id, &mut distribution, budget)
+            {
+                self.accounts
+                    .credit_earnings(payout.provider_id, Cash(payout.amount));
+
+                Self::env().emit_event(ClusterDistributeRevenues {
+                    cluster_id,
+                    provider_id: payout.provider_id,
+                    amount: payout.amount,
+                    nodes_keys,
+                });
+            }
+        }
+        self.clusters.update(cluster_id, &cluster)?;
+
+        Self::env().emit_event(ClusterDistributionProgress {
+            cluster_id,
+            paid_providers: distribution.next_index,
+            total_providers: distribution.payouts_len,
+        });
+
+        if distribution.is_completed() {
+            self.clusters.distributions.remove(cluster_id);
+            Self::env().emit_event(ClusterDistributionCompleted { cluster_id });
+            Ok(true)
+        } else {
+            self.clusters
+                .distributions
+                .insert(cluster_id, &distribution);
+            Ok(false)
+        }
+    }
+
+    /// Take the fees from the revenues of the cluster, and share the rest in a new round.
+    fn cluster_start_distribution(&mut self, cluster: &mut Cluster) -> Result<RevenueDistribution> {
         // Charge the network fee from the cluster.
         self.capture_network_fee(&mut cluster.revenues)?;
 
@@ -484,27 +812,14 @@ impl DdcBucket {
             &mut cluster.revenues,
         )?;
 
-        // Charge the provider payments from the cluster.
-        let num_shares = cluster.nodes_keys.len() as Balance;
-        let per_share = cluster.revenues.peek() / num_shares;
-        cluster.revenues.pay(Payable(per_share * num_shares))?;
-
-        for node_key in &cluster.nodes_keys {
-            let node = self.nodes.get(*node_key)?;
-            Self::send_cash(node.provider_id, Cash(per_share))?;
-
-            Self::env().emit_event(ClusterDistributeRevenues {
-                cluster_id,
-                provider_id: node.provider_id,
-            });
-        }
-
-        self.clusters.update(cluster_id, &cluster)?;
-
-        // TODO: set a maximum node count, or support paging.
-        // TODO: aggregate the payments per node_id or per provider_id.
+        Ok(RevenueDistribution::new(cluster.revenues.peek()))
+    }
 
-        Ok(())
+    /// The provider of a node and the weight of the node in the distribution of revenues.
+    fn cluster_node_weight(&self, node_key: NodeKey) -> Result<(AccountId, Balance)> {
+        let node = self.nodes.get(node_key)?;
+        let v_nodes_len = self.topology.get_v_nodes_by_node(node_key).len() as Balance;
+        Ok((node.provider_id, v_nodes_len * node.rent_v_node_per_month))
     }
 
     // Set the price usd per gb
@@ -513,10 +828,8 @@ impl DdcBucket {
         cluster_id: ClusterId,
         cdn_usd_per_gb: Balance,
     ) -> Result<()> {
-        let caller = Self::env().caller();
-
         let mut cluster = self.clusters.get(cluster_id)?;
-        cluster.only_manager(caller)?;
+        self.only_cluster_manager_or_operator(cluster_id, &cluster, ClusterRole::SetCdnRate)?;
         cluster.cdn_set_rate(cdn_usd_per_gb);
         self.clusters.update(cluster_id, &cluster)?;
 
@@ -539,48 +852,198 @@ impl DdcBucket {
         aggregates_buckets: Vec<(BucketId, Resource)>,
         era: u64,
     ) -> Result<()> {
-        self.only_validator()?;
+        // An unbalanced report is rejected before it counts as a vote.
+        let (payments_accounts, payments_nodes) = self.cluster_cdn_payments(
+            cluster_id,
+            &aggregates_accounts,
+            &aggregates_nodes,
+            &aggregates_buckets,
+        )?;
 
-        let mut cluster = self.clusters.get(cluster_id)?;
-        let mut cluster_payment = 0;
-        let mut _undistributed_payment_accounts = 0;
+        let report_hash = CdnReport::hash_aggregates(
+            cluster_id,
+            era,
+            &aggregates_accounts,
+            &aggregates_nodes,
+            &aggregates_buckets,
+        );
+        let mut report = self.cluster_vote_cdn_report(cluster_id, era, report_hash)?;
+
+        // The usage of a settled era is not charged again, the vote is only recorded for review.
+        if report.applied || report.agreed_hash != Some(report_hash) {
+            return Ok(());
+        }
 
-        let aggregate_payments_accounts;
-        {
-            let conv = &self.protocol.curr_converter;
-            aggregate_payments_accounts = aggregates_accounts
-                .iter()
-                .map(|(client_id, resources_used)| {
-                    let account_id = *client_id;
-                    let cere_payment: Balance = conv
-                        .to_cere(*resources_used as Balance * cluster.cdn_usd_per_gb / KB_PER_GB);
-                    (account_id, cere_payment)
-                })
-                .collect::<Vec<(AccountId, Balance)>>();
+        self.cluster_charge_cdn_revenue(
+            cluster_id,
+            payments_accounts,
+            payments_nodes,
+            aggregates_buckets,
+            era,
+        )?;
+
+        report.applied = true;
+        self.clusters.cdn_reports.insert((cluster_id, era), &report);
+        self.clusters.last_settled_cdn_eras.insert(cluster_id, &era);
+
+        Self::env().emit_event(CdnReportApplied {
+            cluster_id,
+            era,
+            report_hash,
+        });
+
+        Ok(())
+    }
+
+    pub fn message_cluster_submit_cdn_report(
+        &mut self,
+        cluster_id: ClusterId,
+        era: u64,
+        report_hash: Hash,
+    ) -> Result<()> {
+        self.cluster_vote_cdn_report(cluster_id, era, report_hash)?;
+        Ok(())
+    }
+
+    pub fn message_cluster_get_cdn_report(
+        &self,
+        cluster_id: ClusterId,
+        era: u64,
+    ) -> Option<CdnReport> {
+        self.clusters.cdn_reports.get((cluster_id, era))
+    }
+
+    /// Record the vote of the calling validator on the CDN usage of a cluster during an era.
+    fn cluster_vote_cdn_report(
+        &mut self,
+        cluster_id: ClusterId,
+        era: u64,
+        report_hash: Hash,
+    ) -> Result<CdnReport> {
+        let validator_id = self.only_validator()?;
+        self.clusters.get(cluster_id)?;
+
+        // The usage of an era is validated once its commit phase is over.
+        let now_ms = Self::env().block_timestamp();
+        let current_era = match self.committer.try_get_era(now_ms) {
+            Some(status) if status.current_phase != Phase::Commit => status.current_era,
+            _ => return Err(EraIsNotInValidationOrPayoutPhase),
+        };
+        if era > current_era {
+            return Err(EraIsInTheFuture);
+        }
+
+        let mut report = self
+            .clusters
+            .cdn_reports
+            .get((cluster_id, era))
+            .unwrap_or_default();
+        // Votes on a settled era still count, so that late dissenters are listed for review.
+        // An era skipped by a later settled era cannot be settled anymore.
+        match self.clusters.last_settled_cdn_eras.get(cluster_id) {
+            Some(last_era) if era < last_era && !report.applied => {
+                return Err(EraIsOlderThanLastSettled)
+            }
+            _ => {}
+        }
+        let agreed = report.vote(
+            validator_id,
+            report_hash,
+            self.protocol.get_cdn_report_quorum(),
+        );
+        self.clusters.cdn_reports.insert((cluster_id, era), &report);
+
+        Self::env().emit_event(CdnReportSubmitted {
+            cluster_id,
+            era,
+            validator_id,
+            report_hash,
+        });
+        if agreed {
+            Self::env().emit_event(CdnReportAgreed {
+                cluster_id,
+                era,
+                report_hash,
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// Convert the CDN usage of a cluster into the payments of the accounts and of the nodes,
+    /// checking that they reconcile.
+    fn cluster_cdn_payments(
+        &self,
+        cluster_id: ClusterId,
+        aggregates_accounts: &[(AccountId, u128)],
+        aggregates_nodes: &[(CdnNodeKey, u128)],
+        aggregates_buckets: &[(BucketId, Resource)],
+    ) -> Result<(CdnPayments<AccountId>, CdnPayments<CdnNodeKey>)> {
+        let cluster = self.clusters.get(cluster_id)?;
+        let conv = &self.protocol.curr_converter;
+        let to_cere = |resources_used: u128| {
+            conv.to_cere(resources_used * cluster.cdn_usd_per_gb / KB_PER_GB)
+        };
+
+        let aggregate_payments_accounts = aggregates_accounts
+            .iter()
+            .map(|&(client_id, resources_used)| (client_id, to_cere(resources_used)))
+            .collect::<CdnPayments<AccountId>>();
+        let aggregate_payments_nodes = aggregates_nodes
+            .iter()
+            .map(|&(cdn_node_key, resources_used)| (cdn_node_key, to_cere(resources_used)))
+            .collect::<CdnPayments<CdnNodeKey>>();
+
+        // The nodes are paid what the accounts are charged, up to the rounding of each payment.
+        let accounts_payment: Balance = aggregate_payments_accounts.iter().map(|(_, p)| p).sum();
+        let nodes_payment: Balance = aggregate_payments_nodes.iter().map(|(_, p)| p).sum();
+        let rounding_tolerance = (aggregates_accounts.len() + aggregates_nodes.len()) as Balance;
+        if accounts_payment.abs_diff(nodes_payment) > rounding_tolerance {
+            return Err(UnbalancedCdnReport);
         }
 
+        // The buckets, if any, break down the usage of the accounts.
+        if !aggregates_buckets.is_empty() {
+            let accounts_usage: u128 = aggregates_accounts.iter().map(|(_, r)| r).sum();
+            let buckets_usage: u128 = aggregates_buckets.iter().map(|(_, r)| *r as u128).sum();
+            if accounts_usage != buckets_usage {
+                return Err(UnbalancedCdnReport);
+            }
+        }
+
+        Ok((aggregate_payments_accounts, aggregate_payments_nodes))
+    }
+
+    /// Charge the agreed CDN usage of a cluster from the accounts and credit the CDN nodes.
+    fn cluster_charge_cdn_revenue(
+        &mut self,
+        cluster_id: ClusterId,
+        aggregate_payments_accounts: CdnPayments<AccountId>,
+        aggregate_payments_nodes: CdnPayments<CdnNodeKey>,
+        aggregates_buckets: Vec<(BucketId, Resource)>,
+        era: u64,
+    ) -> Result<()> {
+        let mut cluster = self.clusters.get(cluster_id)?;
+        let mut cluster_payment = 0;
+        let mut protocol_payments = 0;
+        let accounts_payment = aggregate_payments_accounts.iter().map(|(_, p)| p).sum();
+
         for &(client_id, payment) in aggregate_payments_accounts.iter() {
             if let Ok(mut account) = self.accounts.get(&client_id) {
                 account.withdraw_bonded(Payable(payment))?;
-                _undistributed_payment_accounts += payment;
                 self.accounts.save(&client_id, &account);
             } else {
                 return Err(InsufficientBalance);
             }
         }
 
-        let conv = self.protocol.curr_converter.clone();
         let committer = &mut self.committer;
 
-        for &(cdn_node_key, resources_used) in aggregates_nodes.iter() {
+        for &(cdn_node_key, payment) in aggregate_payments_nodes.iter() {
             let mut cdn_node = self.cdn_nodes.get(cdn_node_key)?;
             let protocol_fee = self.protocol.get_protocol_fee_bp();
             let protocol = &mut self.protocol;
 
-            let payment =
-                conv.to_cere(resources_used as Balance * cluster.cdn_usd_per_gb / KB_PER_GB);
-
-            // let protocol_payment = payment * protocol_fee as u128/ BASIS_POINTS;
             let node_payment = payment * (BASIS_POINTS - protocol_fee) as u128 / BASIS_POINTS;
             let protocol_payment = payment - node_payment;
 
@@ -589,10 +1052,10 @@ impl DdcBucket {
             protocol.put_revenues(Cash(protocol_payment));
             self.cdn_nodes.update(cdn_node_key, &cdn_node)?;
 
-            committer.set_validated_commit(cdn_node_key, era).unwrap();
+            committer.set_validated_commit(cdn_node_key, era);
             cluster_payment += node_payment;
+            protocol_payments += protocol_payment;
         }
-        // Add check that two payments should equal?
 
         // Go through buckets and deduct used resources
         for &(bucket_id, resources_used) in aggregates_buckets.iter() {
@@ -608,6 +1071,14 @@ impl DdcBucket {
         cluster.cdn_put_revenues(Cash(cluster_payment));
         self.clusters.update(cluster_id, &cluster)?;
 
+        Self::env().emit_event(CdnRevenueSettled {
+            cluster_id,
+            era,
+            accounts_payment,
+            nodes_payment: cluster_payment,
+            protocol_fee: protocol_payments,
+        });
+
         Ok(())
     }
 
@@ -624,28 +1095,39 @@ impl DdcBucket {
             &mut cluster.cdn_revenues,
         )?;
 
-        // First accumulated revenues to distribute.
+        // Collect the undistributed payments of the nodes, per provider.
+        let mut payouts = Vec::new();
+        let mut payees = Vec::with_capacity(cluster.cdn_nodes_keys.len());
         let mut distributed_revenue = 0;
 
         for cdn_node_key in &cluster.cdn_nodes_keys {
-            let cdn_node = self.cdn_nodes.get(*cdn_node_key)?;
-            distributed_revenue += cdn_node.undistributed_payment;
+            let mut cdn_node = self.cdn_nodes.get(*cdn_node_key)?;
+            let payment = cdn_node.undistributed_payment;
+            distributed_revenue += payment;
+
+            cdn_node.take_payment(payment)?;
+            self.cdn_nodes.update(*cdn_node_key, &cdn_node)?;
+            ProviderPayout::aggregate(&mut payouts, cdn_node.provider_id, payment);
+            payees.push((cdn_node.provider_id, *cdn_node_key));
         }
 
         // Charge the provider payments from the cluster.
         cluster.cdn_revenues.pay(Payable(distributed_revenue))?;
 
-        // Distribute revenues to nodes
-        for cdn_node_key in &cluster.cdn_nodes_keys {
-            let mut cdn_node = self.cdn_nodes.get(*cdn_node_key)?;
-
-            Self::send_cash(cdn_node.provider_id, Cash(cdn_node.undistributed_payment))?;
-            cdn_node.take_payment(cdn_node.undistributed_payment)?;
-            self.cdn_nodes.update(*cdn_node_key, &cdn_node)?;
+        // Credit the revenues to the earnings of the providers.
+        for payout in payouts {
+            self.accounts
+                .credit_earnings(payout.provider_id, Cash(payout.amount));
 
             Self::env().emit_event(ClusterDistributeCdnRevenues {
                 cluster_id,
-                provider_id: cdn_node.provider_id,
+                provider_id: payout.provider_id,
+                amount: payout.amount,
+                cdn_nodes_keys: payees
+                    .iter()
+                    .filter(|(provider_id, _)| *provider_id == payout.provider_id)
+                    .map(|(_, cdn_node_key)| *cdn_node_key)
+                    .collect(),
             });
         }
         self.clusters.update(cluster_id, &cluster)?;
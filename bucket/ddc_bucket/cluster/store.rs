@@ -1,7 +1,12 @@
 //! The store where to create and access Clusters by ID.
 
-use super::entity::{Cluster, ClusterId, ClusterParams};
-use crate::ddc_bucket::{AccountId, Error::*, Resource, Result};
+use super::entity::{
+    CdnReport, Cluster, ClusterId, ClusterParams, ClusterRole, ProviderPayout, ResourceUpdate,
+    RevenueDistribution,
+};
+use crate::ddc_bucket::chunked::{ChunkedLists, CHUNK_LEN};
+use crate::ddc_bucket::node::entity::NodeKey;
+use crate::ddc_bucket::{AccountId, Balance, Error::*, Resource, Result};
 use ink_prelude::vec::Vec;
 use ink_storage::traits::{SpreadAllocate, SpreadLayout};
 use ink_storage::Mapping;
@@ -12,12 +17,39 @@ pub struct ClusterStore {
     pub next_cluster_id: ClusterId,
     pub clusters: Mapping<ClusterId, Cluster>,
     pub clusters_ids: Vec<ClusterId>,
+    /// The storage nodes of clusters.
+    pub nodes_keys: ChunkedLists<ClusterId, NodeKey>,
+    /// The number of storage nodes with the ACTIVE status, by cluster.
+    pub active_nodes_len: Mapping<ClusterId, u32>,
+    /// The distributions of revenues in progress.
+    pub distributions: Mapping<ClusterId, RevenueDistribution>,
+    /// The payouts of the distributions in progress, by index of chunk.
+    pub payouts: Mapping<(ClusterId, u32), Vec<ProviderPayout>>,
+    /// The position of the payout of each provider in the distributions in progress.
+    pub payout_positions: Mapping<(ClusterId, AccountId), u32>,
+    /// The nodes covered by the payout of each provider in the distributions in progress.
+    pub payout_nodes: ChunkedLists<(ClusterId, AccountId), NodeKey>,
+    /// The changes of the resource per vnode in progress.
+    pub resource_updates: Mapping<ClusterId, ResourceUpdate>,
+    /// The proposed new manager of a cluster.
+    pub pending_managers: Mapping<ClusterId, AccountId>,
+    /// The roles of the operators of clusters.
+    pub operators: Mapping<(ClusterId, AccountId), Vec<ClusterRole>>,
+    /// The operators with at least one role, by cluster.
+    pub operators_ids: ChunkedLists<ClusterId, AccountId>,
+    /// The votes of the validators on the CDN usage of clusters, by era.
+    pub cdn_reports: Mapping<(ClusterId, u64), CdnReport>,
+    /// The last era whose CDN usage was charged, by cluster.
+    pub last_settled_cdn_eras: Mapping<ClusterId, u64>,
 }
 
 // https://use.ink/datastructures/storage-layout#packed-vs-non-packed-layout
 // There is a buffer with only limited capacity (around 16KB in the default configuration) available.
 pub const MAX_CLUSTERS_LEN_IN_VEC: usize = 3900;
 
+/// The operators of a cluster are revoked at once when the cluster changes hands or is removed.
+pub const MAX_CLUSTER_OPERATORS_LEN: u32 = 32;
+
 impl ClusterStore {
     pub fn create(
         &mut self,
@@ -54,8 +86,255 @@ impl ClusterStore {
 
     pub fn remove(&mut self, cluster_id: ClusterId) {
         self.clusters.remove(cluster_id);
+        self.distributions.remove(cluster_id);
+        self.active_nodes_len.remove(cluster_id);
+        self.resource_updates.remove(cluster_id);
+        self.pending_managers.remove(cluster_id);
+        self.last_settled_cdn_eras.remove(cluster_id);
+        // A cluster without a distribution in progress has no payouts left.
+        // The CDN reports of past eras are left unreachable, as cluster IDs are not reused.
+        // Removing them would take a write per era in a single call.
+        self.revoke_operators(cluster_id);
+        self.nodes_keys.clear(cluster_id);
         if let Some(pos) = self.clusters_ids.iter().position(|x| *x == cluster_id) {
             self.clusters_ids.remove(pos);
         };
     }
+
+    pub fn add_node(&mut self, cluster_id: ClusterId, node_key: NodeKey) {
+        self.nodes_keys.push(cluster_id, node_key);
+    }
+
+    pub fn remove_node(&mut self, cluster_id: ClusterId, node_key: NodeKey) {
+        self.nodes_keys.remove(cluster_id, node_key);
+    }
+
+    pub fn list_nodes_keys(
+        &self,
+        cluster_id: ClusterId,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<NodeKey>, u32) {
+        self.nodes_keys.get_page(cluster_id, offset, limit)
+    }
+
+    pub fn count_nodes(&self, cluster_id: ClusterId) -> u32 {
+        self.nodes_keys.len(cluster_id)
+    }
+
+    pub fn count_active_nodes(&self, cluster_id: ClusterId) -> u32 {
+        self.active_nodes_len.get(cluster_id).unwrap_or(0)
+    }
+
+    /// Follow a node of the cluster entering or leaving the ACTIVE status.
+    pub fn update_active_nodes(
+        &mut self,
+        cluster_id: ClusterId,
+        was_active: bool,
+        is_active: bool,
+    ) {
+        let active_nodes_len = self.count_active_nodes(cluster_id);
+        match (was_active, is_active) {
+            (false, true) => self
+                .active_nodes_len
+                .insert(cluster_id, &(active_nodes_len + 1)),
+            (true, false) => self
+                .active_nodes_len
+                .insert(cluster_id, &active_nodes_len.saturating_sub(1)),
+            _ => {}
+        }
+    }
+
+    pub fn only_without_nodes(&self, cluster_id: ClusterId, cluster: &Cluster) -> Result<()> {
+        if self.count_nodes(cluster_id) == 0
+            && cluster.cdn_nodes_keys.is_empty()
+            && !self.distributions.contains(cluster_id)
+        {
+            Ok(())
+        } else {
+            Err(ClusterIsNotEmpty)
+        }
+    }
+
+    /// The nodes of a cluster must not change while an operation goes through them over several calls.
+    pub fn only_unlocked_nodes(&self, cluster_id: ClusterId) -> Result<()> {
+        if self.resource_updates.contains(cluster_id) {
+            return Err(ClusterNodesAreLocked);
+        }
+        match self.distributions.get(cluster_id) {
+            Some(distribution) if distribution.is_over_nodes() => Err(ClusterNodesAreLocked),
+            _ => Ok(()),
+        }
+    }
+
+    /// Add the share of a node to the payout of its provider in the distribution in progress.
+    pub fn add_payout(
+        &mut self,
+        cluster_id: ClusterId,
+        distribution: &mut RevenueDistribution,
+        provider_id: AccountId,
+        node_key: NodeKey,
+        amount: Balance,
+    ) {
+        self.payout_nodes.push((cluster_id, provider_id), node_key);
+
+        let position = match self.payout_positions.get((cluster_id, provider_id)) {
+            Some(position) => position,
+            None => {
+                let position = distribution.payouts_len;
+                distribution.payouts_len += 1;
+                self.payout_positions
+                    .insert((cluster_id, provider_id), &position);
+                position
+            }
+        };
+
+        let key = (cluster_id, position / CHUNK_LEN);
+        let mut chunk = self.payouts.get(key).unwrap_or_default();
+        match chunk.get_mut((position % CHUNK_LEN) as usize) {
+            Some(payout) => payout.amount += amount,
+            None => chunk.push(ProviderPayout {
+                provider_id,
+                amount,
+            }),
+        }
+        self.payouts.insert(key, &chunk);
+    }
+
+    /// Take up to `limit` payouts of the distribution in progress, in order, with the nodes that they cover.
+    pub fn take_payouts(
+        &mut self,
+        cluster_id: ClusterId,
+        distribution: &mut RevenueDistribution,
+        limit: u32,
+    ) -> Vec<(ProviderPayout, Vec<NodeKey>)> {
+        let end = distribution
+            .next_index
+            .saturating_add(limit)
+            .min(distribution.payouts_len);
+
+        let mut payouts = Vec::new();
+        while distribution.next_index < end {
+            let chunk_index = distribution.next_index / CHUNK_LEN;
+            let chunk = self
+                .payouts
+                .get((cluster_id, chunk_index))
+                .unwrap_or_default();
+            let start = (distribution.next_index % CHUNK_LEN) as usize;
+            let stop = chunk
+                .len()
+                .min(start + (end - distribution.next_index) as usize);
+            if start >= stop {
+                break;
+            }
+            for payout in &chunk[start..stop] {
+                let key = (cluster_id, payout.provider_id);
+                self.payout_positions.remove(key);
+                let nodes_keys = self.payout_nodes.get_all(key);
+                self.payout_nodes.clear(key);
+                payouts.push((payout.clone(), nodes_keys));
+            }
+            distribution.next_index += (stop - start) as u32;
+
+            // A chunk is removed once all its payouts are taken.
+            if stop == chunk.len() {
+                self.payouts.remove((cluster_id, chunk_index));
+            }
+        }
+        payouts
+    }
+
+    pub fn propose_manager(&mut self, cluster_id: ClusterId, manager_id: AccountId) {
+        self.pending_managers.insert(cluster_id, &manager_id);
+    }
+
+    pub fn cancel_manager_proposal(&mut self, cluster_id: ClusterId) {
+        self.pending_managers.remove(cluster_id);
+    }
+
+    pub fn accept_manager(&mut self, cluster_id: ClusterId, manager_id: AccountId) -> Result<()> {
+        match self.pending_managers.get(cluster_id) {
+            Some(pending_manager_id) if pending_manager_id == manager_id => {
+                self.pending_managers.remove(cluster_id);
+                self.revoke_operators(cluster_id);
+                Ok(())
+            }
+            _ => Err(OnlyPendingClusterManager),
+        }
+    }
+
+    pub fn get_operator_roles(
+        &self,
+        cluster_id: ClusterId,
+        operator_id: AccountId,
+    ) -> Vec<ClusterRole> {
+        self.operators
+            .get((cluster_id, operator_id))
+            .unwrap_or_default()
+    }
+
+    pub fn has_operator_role(
+        &self,
+        cluster_id: ClusterId,
+        operator_id: AccountId,
+        role: ClusterRole,
+    ) -> bool {
+        self.get_operator_roles(cluster_id, operator_id)
+            .contains(&role)
+    }
+
+    pub fn list_operators(
+        &self,
+        cluster_id: ClusterId,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<AccountId>, u32) {
+        self.operators_ids.get_page(cluster_id, offset, limit)
+    }
+
+    /// Grant a role to an operator. Granting a role twice has no effect.
+    pub fn grant_operator_role(
+        &mut self,
+        cluster_id: ClusterId,
+        operator_id: AccountId,
+        role: ClusterRole,
+    ) -> Result<()> {
+        let mut roles = self.get_operator_roles(cluster_id, operator_id);
+        if roles.contains(&role) {
+            return Ok(());
+        }
+        if roles.is_empty() {
+            if self.operators_ids.len(cluster_id) >= MAX_CLUSTER_OPERATORS_LEN {
+                return Err(OperatorsSizeExceedsLimit);
+            }
+            self.operators_ids.push(cluster_id, operator_id);
+        }
+        roles.push(role);
+        self.operators.insert((cluster_id, operator_id), &roles);
+        Ok(())
+    }
+
+    pub fn revoke_operator_role(
+        &mut self,
+        cluster_id: ClusterId,
+        operator_id: AccountId,
+        role: ClusterRole,
+    ) {
+        let mut roles = self.get_operator_roles(cluster_id, operator_id);
+        roles.retain(|r| *r != role);
+        if roles.is_empty() {
+            self.operators.remove((cluster_id, operator_id));
+            self.operators_ids.remove(cluster_id, operator_id);
+        } else {
+            self.operators.insert((cluster_id, operator_id), &roles);
+        }
+    }
+
+    /// Revoke all roles from all operators of a cluster.
+    pub fn revoke_operators(&mut self, cluster_id: ClusterId) {
+        for operator_id in self.operators_ids.get_all(cluster_id) {
+            self.operators.remove((cluster_id, operator_id));
+        }
+        self.operators_ids.clear(cluster_id);
+    }
 }
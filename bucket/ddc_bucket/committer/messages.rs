@@ -11,7 +11,25 @@ impl DdcBucket {
         cdn_node_key: CdnNodeKey,
         commit: Commit,
     ) -> Result<()> {
+        let caller = Self::env().caller();
+
+        let cdn_node = self.cdn_nodes.get(cdn_node_key)?;
+        cdn_node.only_provider(caller)?;
+        if cdn_owner != caller {
+            return Err(OnlyCdnNodeProvider);
+        }
+        if cdn_node.cluster_id.is_none() {
+            return Err(CdnNodeIsNotAddedToAnyCluster);
+        }
+
+        let now_ms = Self::env().block_timestamp();
+        if !self.committer.is_commit_phase(now_ms) {
+            return Err(EraIsNotInCommitPhase);
+        }
+        let era = self.committer.get_era(now_ms).current_era;
+
         self.committer.set_commit(cdn_owner, cdn_node_key, commit);
+        self.committer.set_era_commit(cdn_node_key, era, commit);
         Ok(())
     }
 
@@ -19,6 +37,25 @@ impl DdcBucket {
         self.committer.get_commit(cdn_owner)
     }
 
+    pub fn message_get_commits_for_era(
+        &self,
+        era: u64,
+        cursor: u32,
+        limit: u32,
+    ) -> (Vec<(CdnNodeKey, Commit)>, u32) {
+        self.committer.get_commits_for_era(era, cursor, limit)
+    }
+
+    pub fn message_get_commit_history(
+        &self,
+        cdn_node_key: CdnNodeKey,
+        from_era: u64,
+        to_era: u64,
+    ) -> Vec<(u64, Commit)> {
+        self.committer
+            .get_commit_history(cdn_node_key, from_era, to_era)
+    }
+
     pub fn message_get_validated_commit(&self, cdn_node_key: CdnNodeKey) -> EraAndTimestamp {
         self.committer.get_validate_commit(cdn_node_key)
     }
@@ -1,3 +1,4 @@
+use crate::ddc_bucket::chunked::ChunkedLists;
 use crate::ddc_bucket::{AccountId, CdnNodeKey, Hash};
 
 use ink_prelude::vec::Vec;
@@ -23,22 +24,30 @@ pub enum Phase {
 #[derive(Debug, PartialEq, scale::Encode, scale::Decode)]
 #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
 pub struct EraStatus {
-    current_era: u64,
-    current_phase: Phase,
-    previous_era: u64,
-    prev_era_from_timestamp: u64,
-    prev_era_to_timestamp: u64,
+    pub current_era: u64,
+    pub current_phase: Phase,
+    pub previous_era: u64,
+    pub prev_era_from_timestamp: u64,
+    pub prev_era_to_timestamp: u64,
 }
 
 #[derive(
-    Copy, Clone, SpreadAllocate, PackedLayout, SpreadLayout, scale::Encode, scale::Decode, Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    SpreadAllocate,
+    PackedLayout,
+    SpreadLayout,
+    scale::Encode,
+    scale::Decode,
+    Debug,
 )]
 #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
 pub struct Commit {
-    hash: Hash,
-    total_logs: u128,
-    from_timestamp: u64,
-    to_timestamp: u64,
+    pub hash: Hash,
+    pub total_logs: u128,
+    pub from_timestamp: u64,
+    pub to_timestamp: u64,
 }
 
 #[derive(
@@ -49,10 +58,10 @@ pub struct Commit {
     derive(::scale_info::TypeInfo, ink_storage::traits::StorageLayout)
 )]
 pub struct EraConfig {
-    start: u64,
-    interval: u64,
-    commit_duration: u64,
-    validation_duration: u64,
+    pub start: u64,
+    pub interval: u64,
+    pub commit_duration: u64,
+    pub validation_duration: u64,
 }
 
 #[derive(Default, SpreadAllocate, SpreadLayout, Debug)]
@@ -62,8 +71,19 @@ pub struct CommitterStore {
     commits: Mapping<AccountId, Vec<(CdnNodeKey, Commit)>>,
     validated_commits: Mapping<CdnNodeKey, EraAndTimestamp>,
     era_settings: EraConfig,
+    // commits by CDN node and by era in which they were set
+    era_commits: Mapping<(CdnNodeKey, u64), Commit>,
+    // CDN nodes that set a commit in an era
+    era_nodes: ChunkedLists<u64, CdnNodeKey>,
+    // eras with commits that are not pruned yet, oldest first
+    commit_eras: Vec<u64>,
 }
 
+// The number of eras for which commits are kept.
+pub const COMMIT_HISTORY_ERAS: u64 = 30;
+// The number of old commits pruned on every new commit, so that pruning keeps up with new commits.
+pub const COMMITS_PRUNED_PER_CALL: u32 = 10;
+
 pub type Result<T> = core::result::Result<T, Error>;
 
 pub type EraAndTimestamp = (u64, u64);
@@ -74,7 +94,7 @@ impl CommitterStore {
     }
 
     /// The node can set the latest commit with this function
-    /// check the sender !!!!
+    /// The sender is checked by the caller of this function
     pub fn set_commit(&mut self, cdn_owner: AccountId, cdn_node_key: CdnNodeKey, commit: Commit) {
         if !self.commits.contains(&cdn_owner) {
             let empty_vec = Vec::<(CdnNodeKey, Commit)>::new();
@@ -93,6 +113,77 @@ impl CommitterStore {
         self.commits.insert(&cdn_owner, &account_commits);
     }
 
+    /// Keep the commit of a node for an era, replacing a previous commit of the node in the same era.
+    pub fn set_era_commit(&mut self, cdn_node_key: CdnNodeKey, era: u64, commit: Commit) {
+        if self.commit_eras.last() != Some(&era) {
+            self.commit_eras.push(era);
+        }
+        self.era_commits.insert((cdn_node_key, era), &commit);
+        self.era_nodes.push(era, cdn_node_key);
+
+        self.prune_commits(era, COMMITS_PRUNED_PER_CALL);
+    }
+
+    /// Remove up to `limit` commits of the eras that are out of the retention window.
+    pub fn prune_commits(&mut self, current_era: u64, limit: u32) {
+        let mut remaining = limit;
+        while remaining > 0 {
+            let era = match self.commit_eras.first() {
+                Some(era) if *era + COMMIT_HISTORY_ERAS <= current_era => *era,
+                _ => break,
+            };
+
+            let (cdn_nodes_keys, _) = self.era_nodes.get_page(era, 0, remaining);
+            for cdn_node_key in cdn_nodes_keys {
+                self.era_commits.remove((cdn_node_key, era));
+                self.era_nodes.remove(era, cdn_node_key);
+                remaining -= 1;
+            }
+
+            if self.era_nodes.len(era) == 0 {
+                self.era_nodes.clear(era);
+                self.commit_eras.remove(0);
+            }
+        }
+    }
+
+    /// Return a page of the commits set in an era, and the number of commits of the era.
+    pub fn get_commits_for_era(
+        &self,
+        era: u64,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<(CdnNodeKey, Commit)>, u32) {
+        let (cdn_nodes_keys, total) = self.era_nodes.get_page(era, offset, limit);
+        let commits = cdn_nodes_keys
+            .into_iter()
+            .filter_map(|cdn_node_key| {
+                self.era_commits
+                    .get((cdn_node_key, era))
+                    .map(|commit| (cdn_node_key, commit))
+            })
+            .collect();
+        (commits, total)
+    }
+
+    /// Return the commits of a node from `from_era` to `to_era` inclusive, oldest first.
+    pub fn get_commit_history(
+        &self,
+        cdn_node_key: CdnNodeKey,
+        from_era: u64,
+        to_era: u64,
+    ) -> Vec<(u64, Commit)> {
+        self.commit_eras
+            .iter()
+            .filter(|era| from_era <= **era && **era <= to_era)
+            .filter_map(|era| {
+                self.era_commits
+                    .get((cdn_node_key, *era))
+                    .map(|commit| (*era, commit))
+            })
+            .collect()
+    }
+
     pub fn get_commit(&self, cdn_owner: AccountId) -> Vec<(CdnNodeKey, Commit)> {
         self.commits
             .get(&cdn_owner)
@@ -103,12 +194,11 @@ impl CommitterStore {
     }
 
     // Set the last validated commit per CDN node
-    pub fn set_validated_commit(&mut self, cdn_node_key: CdnNodeKey, era: u64) -> Result<()> {
+    pub fn set_validated_commit(&mut self, cdn_node_key: CdnNodeKey, era: u64) {
         let prev_era_to_timestamp =
             self.era_settings.start + self.era_settings.interval * (era + 1);
         self.validated_commits
             .insert(&cdn_node_key, &(era, prev_era_to_timestamp));
-        Ok(())
     }
 
     // Get the last era & timestamp validated per CDN node
@@ -162,6 +252,19 @@ impl CommitterStore {
         }
     }
 
+    // Get the current era phase, if eras are configured and started
+    pub fn try_get_era(&self, timestamp: u64) -> Option<EraStatus> {
+        if self.era_settings.interval == 0 || timestamp < self.era_settings.start {
+            return None;
+        }
+        Some(self.get_era(timestamp))
+    }
+
+    // Whether eras are configured and the timestamp is in the commit phase of an era
+    pub fn is_commit_phase(&self, timestamp: u64) -> bool {
+        matches!(self.try_get_era(timestamp), Some(era) if era.current_phase == Phase::Commit)
+    }
+
     // Get the current era phase
     pub fn get_era_settings(&self) -> EraConfig {
         return self.era_settings;
@@ -98,8 +98,7 @@ impl Node {
     }
 
     pub fn only_with_cluster(&self, cluster_id: ClusterId) -> Result<()> {
-        self.cluster_id
-            .is_some()
+        (self.cluster_id == Some(cluster_id))
             .then(|| ())
             .ok_or(NodeIsNotAddedToCluster(cluster_id))
     }
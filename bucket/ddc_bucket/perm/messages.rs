@@ -32,12 +32,16 @@ impl DdcBucket {
             .ok_or(Unauthorized)
     }
 
-    pub fn only_trusted_cluster_manager(&self, provider_id: AccountId) -> Result<AccountId> {
-        let caller = Self::env().caller();
+    /// Check that the provider trusts the manager of a cluster, whoever the caller is among the manager and its operators.
+    pub fn only_trusted_cluster_manager(
+        &self,
+        manager_id: AccountId,
+        provider_id: AccountId,
+    ) -> Result<AccountId> {
         let perm = Permission::ClusterManagerTrustedBy(provider_id);
         self.perms
-            .has_permission(caller, perm)
-            .then(|| caller)
+            .has_permission(manager_id, perm)
+            .then(|| manager_id)
             .ok_or(OnlyTrustedClusterManager)
     }
 
@@ -15,4 +15,16 @@ impl DdcBucket {
     pub fn message_get_protocol_revenues(&self) -> Cash {
         self.protocol.get_revenues()
     }
+
+    pub fn message_get_bucket_grace_period_ms(&self) -> u64 {
+        self.protocol.get_bucket_grace_period_ms()
+    }
+
+    pub fn message_get_bucket_rerate_notice_ms(&self) -> u64 {
+        self.protocol.get_bucket_rerate_notice_ms()
+    }
+
+    pub fn message_get_cdn_report_quorum(&self) -> u32 {
+        self.protocol.get_cdn_report_quorum()
+    }
 }
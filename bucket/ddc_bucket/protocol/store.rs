@@ -46,6 +46,12 @@ pub struct ProtocolStore {
     pub revenues: Cash,
     pub curr_converter: CurrencyConverter,
     pub network_fee_config: NetworkFeeConfig,
+    /// How long a bucket is still served after the rent is not covered anymore.
+    pub bucket_grace_period_ms: u64,
+    /// How long before an increase of the rent of a cluster applies to its existing buckets.
+    pub bucket_rerate_notice_ms: u64,
+    /// How many validators must agree on the CDN usage of a cluster before it is charged.
+    pub cdn_report_quorum: u32,
 }
 
 impl ProtocolStore {
@@ -111,6 +117,30 @@ impl ProtocolStore {
     pub fn get_cluster_management_fee_bp(&self) -> BasisPoints {
         self.network_fee_config.cluster_management_fee_bp
     }
+
+    pub fn get_bucket_grace_period_ms(&self) -> u64 {
+        self.bucket_grace_period_ms
+    }
+
+    pub fn set_bucket_grace_period_ms(&mut self, grace_period_ms: u64) {
+        self.bucket_grace_period_ms = grace_period_ms;
+    }
+
+    pub fn get_bucket_rerate_notice_ms(&self) -> u64 {
+        self.bucket_rerate_notice_ms
+    }
+
+    pub fn set_bucket_rerate_notice_ms(&mut self, notice_ms: u64) {
+        self.bucket_rerate_notice_ms = notice_ms;
+    }
+
+    pub fn get_cdn_report_quorum(&self) -> u32 {
+        self.cdn_report_quorum
+    }
+
+    pub fn set_cdn_report_quorum(&mut self, quorum: u32) {
+        self.cdn_report_quorum = quorum;
+    }
 }
 
 impl DdcBucket {
@@ -123,9 +153,9 @@ impl DdcBucket {
         )
     }
 
-    /// Take a fee from the given revenues (in place) and send it to the destination.
+    /// Take a fee from the given revenues (in place) and credit it to the earnings of the destination.
     pub fn capture_fee(
-        &self,
+        &mut self,
         rate_bp: Balance,
         destination: AccountId,
         revenues: &mut Cash,
@@ -133,6 +163,7 @@ impl DdcBucket {
         let fee = revenues.peek() * rate_bp / BASIS_POINTS;
         let (payable, cash) = Cash::borrow_payable_cash(fee);
         revenues.pay(payable)?;
-        Self::send_cash(destination, cash)
+        self.accounts.credit_earnings(destination, cash);
+        Ok(())
     }
 }
@@ -23,6 +23,10 @@ impl Schedule {
         Schedule::new(0, 0)
     }
 
+    pub fn get_rate(&self) -> Balance {
+        self.rate
+    }
+
     pub fn value_at_time(&self, time_ms: u64) -> Balance {
         let absolute = self.rate * time_ms as Balance / MS_PER_MONTH;
         assert!(absolute >= self.offset);
@@ -44,6 +48,17 @@ impl Schedule {
         self.rate += to_add.rate;
     }
 
+    /// Decrease the rate from the given time on, keeping the value accrued until then.
+    ///
+    /// The accrued value should be taken beforehand. The rate never goes below zero,
+    /// and rounding never makes the value at `start_ms` negative.
+    pub fn remove_rate(&mut self, start_ms: u64, rate: Balance) {
+        let to_remove = Schedule::new(start_ms, rate.min(self.rate));
+        self.rate -= to_remove.rate;
+        let absolute = self.rate * start_ms as Balance / MS_PER_MONTH;
+        self.offset = self.offset.saturating_sub(to_remove.offset).min(absolute);
+    }
+
     pub fn take_value(&mut self, value: Balance) {
         self.offset += value;
     }
@@ -61,12 +61,15 @@ pub fn print_events(events: &[Event]) {
             Event::ClusterNodeReset(ev) => println!("EVENT {:?}", ev),
             Event::ClusterReserveResource(ev) => println!("EVENT {:?}", ev),
             Event::ClusterDistributeRevenues(ev) => println!("EVENT {:?}", ev),
+            Event::ClusterDistributionProgress(ev) => println!("EVENT {:?}", ev),
+            Event::ClusterDistributionCompleted(ev) => println!("EVENT {:?}", ev),
             Event::NodeCreated(ev) => println!("EVENT {:?}", ev),
             Event::BucketCreated(ev) => println!("EVENT {:?}", ev),
             Event::BucketAllocated(ev) => println!("EVENT {:?}", ev),
             Event::BucketSettlePayment(ev) => println!("EVENT {:?}", ev),
             Event::BucketAvailabilityUpdated(ev) => println!("EVENT {:?}", ev),
             Event::Deposit(ev) => println!("EVENT {:?}", ev),
+            Event::EarningsClaimed(ev) => println!("EVENT {:?}", ev),
             Event::PermissionGranted(ev) => println!("EVENT {:?}", ev),
             Event::PermissionRevoked(ev) => println!("EVENT {:?}", ev),
             Event::ClusterDistributeCdnRevenues(ev) => println!("EVENT {:?}", ev),
@@ -77,6 +80,14 @@ pub fn print_events(events: &[Event]) {
             Event::ClusterCdnNodeRemoved(ev) => println!("EVENT {:?}", ev),
             Event::ClusterParamsSet(ev) => println!("EVENT {:?}", ev),
             Event::ClusterRemoved(ev) => println!("EVENT {:?}", ev),
+            Event::ClusterManagerTransferProposed(ev) => println!("EVENT {:?}", ev),
+            Event::ClusterManagerTransferred(ev) => println!("EVENT {:?}", ev),
+            Event::ClusterOperatorRoleGranted(ev) => println!("EVENT {:?}", ev),
+            Event::CdnReportSubmitted(ev) => println!("EVENT {:?}", ev),
+            Event::CdnReportAgreed(ev) => println!("EVENT {:?}", ev),
+            Event::CdnReportApplied(ev) => println!("EVENT {:?}", ev),
+            Event::CdnRevenueSettled(ev) => println!("EVENT {:?}", ev),
+            Event::ClusterOperatorRoleRevoked(ev) => println!("EVENT {:?}", ev),
             Event::ClusterNodeStatusSet(ev) => println!("EVENT {:?}", ev),
             Event::ClusterCdnNodeStatusSet(ev) => println!("EVENT {:?}", ev),
             Event::CdnNodeRemoved(ev) => println!("EVENT {:?}", ev),
@@ -86,6 +97,17 @@ pub fn print_events(events: &[Event]) {
             Event::NodeOwnershipTransferred(ev) => println!("EVENT {:?}", ev),
             Event::CdnNodeOwnershipTransferred(ev) => println!("EVENT {:?}", ev),
             Event::BucketParamsSet(ev) => println!("EVENT {:?}", ev),
+            Event::BucketRemoved(ev) => println!("EVENT {:?}", ev),
+            Event::BucketStateChanged(ev) => println!("EVENT {:?}", ev),
+            Event::BucketRerateScheduled(ev) => println!("EVENT {:?}", ev),
+            Event::BucketRerated(ev) => println!("EVENT {:?}", ev),
+            Event::BucketRerateFailed(ev) => println!("EVENT {:?}", ev),
+            Event::BucketReleased(ev) => println!("EVENT {:?}", ev),
+            Event::BucketMigrated(ev) => println!("EVENT {:?}", ev),
+            Event::BucketOwnershipTransferProposed(ev) => println!("EVENT {:?}", ev),
+            Event::BucketOwnershipTransferred(ev) => println!("EVENT {:?}", ev),
+            Event::BucketPayerProposed(ev) => println!("EVENT {:?}", ev),
+            Event::BucketPayerSet(ev) => println!("EVENT {:?}", ev),
         }
     }
 }
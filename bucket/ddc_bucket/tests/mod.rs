@@ -6,5 +6,6 @@ mod test_admin;
 mod test_bucket;
 mod test_cdn_node;
 mod test_cluster;
+mod test_committer;
 mod test_currency;
 mod test_node;
@@ -265,6 +265,16 @@ pub fn setup_cluster() -> TestCluster {
     }
 }
 
+/// Make the storage nodes of the cluster active, so that they count for the replication of buckets.
+pub fn setup_active_nodes(ctx: &mut TestCluster) {
+    set_caller(ctx.manager_id);
+    for node_key in ctx.nodes_keys.clone() {
+        ctx.contract
+            .cluster_set_node_status(ctx.cluster_id, node_key, NodeStatusInCluster::ACTIVE)
+            .unwrap();
+    }
+}
+
 pub struct TestBucket {
     pub bucket_id: BucketId,
     pub owner_id: AccountId,
@@ -277,6 +287,7 @@ pub fn setup_bucket(ctx: &mut TestCluster) -> TestBucket {
         0x75, 0xcb, 0x39, 0xf8, 0x27, 0xbb, 0xc7, 0x46, 0x4b, 0x8d, 0x5d, 0x70, 0xd1, 0x02, 0xaa,
         0x71, 0x0a,
     ]);
+    setup_active_nodes(ctx);
     set_balance(owner_id, 1000 * TOKEN);
     set_caller_value(owner_id, CONTRACT_FEE_LIMIT);
 
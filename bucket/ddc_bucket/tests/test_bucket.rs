@@ -4,6 +4,7 @@ use super::env_utils::*;
 use super::setup_utils::*;
 use crate::ddc_bucket::flow::Flow;
 use crate::ddc_bucket::schedule::{Schedule, MS_PER_MONTH};
+use crate::ddc_bucket::Error::*;
 use crate::ddc_bucket::*;
 
 fn do_bucket_pays_cluster(
@@ -118,7 +119,7 @@ fn bucket_create_ok() {
         + ctx.rent_v_node_per_month1 * ctx.v_nodes1.len() as Balance
         + ctx.rent_v_node_per_month2 * ctx.v_nodes2.len() as Balance;
 
-    let bucket_params = BucketParams::from("");
+    let bucket_params = "{}".to_string();
     let expect_bucket = Bucket {
         owner_id: test_bucket.owner_id,
         cluster_id: ctx.cluster_id,
@@ -130,6 +131,7 @@ fn bucket_create_ok() {
         public_availability: false,
         resource_consumption_cap: 0,
         bucket_params: bucket_params,
+        state: BucketState::Active,
     };
 
     // Check the status of the bucket.
@@ -175,13 +177,145 @@ fn bucket_change_params_ok() {
     let test_bucket = &setup_bucket(ctx);
 
     // Change params.
+    let new_params =
+        "{\"replicationFactor\":3,\"region\":\"eu\",\"extension\":{\"new\":[\"params\"]}}"
+            .to_string();
     set_caller_value(test_bucket.owner_id, CONTRACT_FEE_LIMIT);
     ctx.contract
-        .bucket_change_params(test_bucket.bucket_id, "new params".to_string())?;
+        .bucket_change_params(test_bucket.bucket_id, new_params.clone())?;
 
     // Check the changed params.
     let status = ctx.contract.bucket_get(test_bucket.bucket_id)?;
-    assert_eq!(status.params, "new params");
+    assert_eq!(status.params, new_params);
+}
+
+#[ink::test]
+fn bucket_change_params_err_if_invalid_replication_factor() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+
+    set_caller_value(test_bucket.owner_id, CONTRACT_FEE_LIMIT);
+    for replication_factor in [0, 4] {
+        assert_eq!(
+            ctx.contract.bucket_change_params(
+                test_bucket.bucket_id,
+                format!("{{\"replicationFactor\":{}}}", replication_factor)
+            ),
+            Err(InvalidReplicationFactor)
+        );
+    }
+
+    // Nodes being deleted do not count.
+    set_caller(ctx.manager_id);
+    ctx.contract.cluster_set_node_status(
+        ctx.cluster_id,
+        ctx.node_key2,
+        NodeStatusInCluster::DELETING,
+    )?;
+
+    set_caller_value(test_bucket.owner_id, CONTRACT_FEE_LIMIT);
+    assert_eq!(
+        ctx.contract.bucket_create(
+            "{\"replicationFactor\":3}".to_string(),
+            ctx.cluster_id,
+            None
+        ),
+        Err(InvalidReplicationFactor)
+    );
+
+    // Nodes being added do not count either.
+    set_caller(ctx.manager_id);
+    ctx.contract.cluster_set_node_status(
+        ctx.cluster_id,
+        ctx.node_key2,
+        NodeStatusInCluster::ADDING,
+    )?;
+
+    set_caller_value(test_bucket.owner_id, CONTRACT_FEE_LIMIT);
+    assert_eq!(
+        ctx.contract.bucket_create(
+            "{\"replicationFactor\":3}".to_string(),
+            ctx.cluster_id,
+            None
+        ),
+        Err(InvalidReplicationFactor)
+    );
+    ctx.contract.bucket_create(
+        "{\"replicationFactor\":2}".to_string(),
+        ctx.cluster_id,
+        None,
+    )?;
+
+    // Setting an active node active again does not count it twice.
+    set_caller(ctx.manager_id);
+    ctx.contract.cluster_set_node_status(
+        ctx.cluster_id,
+        ctx.node_key0,
+        NodeStatusInCluster::ACTIVE,
+    )?;
+
+    set_caller_value(test_bucket.owner_id, CONTRACT_FEE_LIMIT);
+    assert_eq!(
+        ctx.contract.bucket_create(
+            "{\"replicationFactor\":3}".to_string(),
+            ctx.cluster_id,
+            None
+        ),
+        Err(InvalidReplicationFactor)
+    );
+
+    // Removed nodes do not count.
+    set_caller(ctx.manager_id);
+    ctx.contract
+        .cluster_remove_node(ctx.cluster_id, ctx.node_key1)?;
+
+    set_caller_value(test_bucket.owner_id, CONTRACT_FEE_LIMIT);
+    assert_eq!(
+        ctx.contract.bucket_create(
+            "{\"replicationFactor\":2}".to_string(),
+            ctx.cluster_id,
+            None
+        ),
+        Err(InvalidReplicationFactor)
+    );
+}
+
+#[ink::test]
+fn bucket_create_err_if_invalid_params() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+
+    set_caller_value(test_bucket.owner_id, CONTRACT_FEE_LIMIT);
+    for bucket_params in [
+        "new params",
+        "{\"replicationFactor\":\"2\"}",
+        "{\"replicationFactor\":-1}",
+        "{\"region\":1}",
+        "{\"replicationFactor\":2",
+        "{\"extension\":[1,2,} ",
+        "{} {}",
+    ] {
+        assert_eq!(
+            ctx.contract
+                .bucket_create(bucket_params.to_string(), ctx.cluster_id, None),
+            Err(InvalidBucketParams)
+        );
+    }
+
+    // The known settings are read among free-form fields.
+    let bucket_id = ctx.contract.bucket_create(
+        " { \"extension\": {\"a\": [true, null, -1.5e3, \"\\u00e9\\\"\"]}, \"tier\": null, \"replicationFactor\": 3 } "
+            .to_string(),
+        ctx.cluster_id,
+        None,
+    )?;
+    assert_eq!(
+        ctx.contract.bucket_change_params(
+            bucket_id,
+            "{\"extension\": {}, \"replicationFactor\": 4}".to_string()
+        ),
+        Err(InvalidReplicationFactor)
+    );
 }
 
 #[ink::test]
@@ -193,13 +327,607 @@ fn bucket_change_params_only_owner() {
     // Change params.
     set_caller_value(get_accounts().bob, CONTRACT_FEE_LIMIT);
     ctx.contract
-        .bucket_change_params(test_bucket.bucket_id, "new params".to_string())?;
+        .bucket_change_params(test_bucket.bucket_id, "{\"new\":\"params\"}".to_string())?;
     // Panic.
 }
 
+#[ink::test]
+fn bucket_remove_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+
+    let resource_used_before = ctx
+        .contract
+        .cluster_get(ctx.cluster_id)?
+        .cluster
+        .resource_used;
+
+    set_caller_value(test_bucket.owner_id, CONTRACT_FEE_LIMIT);
+    ctx.contract
+        .bucket_set_writer_perm(test_bucket.bucket_id, get_accounts().bob, None)?;
+
+    // Go to the future when some revenues are due.
+    advance_block::<DefaultEnvironment>();
+
+    set_caller(test_bucket.owner_id);
+    ctx.contract.bucket_remove(test_bucket.bucket_id)?;
+
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketRemoved(ev) if ev ==
+        BucketRemoved {
+            bucket_id: test_bucket.bucket_id,
+            cluster_id: ctx.cluster_id
+        })
+    );
+
+    assert_eq!(
+        ctx.contract.bucket_get(test_bucket.bucket_id),
+        Err(BucketDoesNotExist)
+    );
+    assert_eq!(
+        ctx.contract.get_bucket_writers(test_bucket.bucket_id),
+        vec![]
+    );
+    assert_eq!(ctx.contract.bucket_list(0, 100, None), (vec![], 1));
+
+    let cluster = ctx.contract.cluster_get(ctx.cluster_id)?.cluster;
+    assert_eq!(
+        cluster.resource_used,
+        resource_used_before - test_bucket.resource,
+        "resources must be released to the cluster"
+    );
+    assert!(
+        cluster.revenues.peek() > 0,
+        "the due costs must be settled to the cluster"
+    );
+
+    let account = ctx.contract.account_get(test_bucket.owner_id)?;
+    assert_eq!(
+        account.payable_schedule,
+        Schedule::empty(),
+        "the payer must not be charged anymore"
+    );
+}
+
+#[ink::test]
+fn bucket_remove_underfunded_ok() {
+    let ctx = &mut setup_cluster();
+    setup_active_nodes(ctx);
+    let owner_id = AccountId::from([0x5e; 32]);
+    set_balance(owner_id, 1000 * TOKEN);
+
+    // Cover the rent of the bucket for about one block.
+    set_caller_value(owner_id, CONTRACT_FEE_LIMIT);
+    let bucket_id = ctx
+        .contract
+        .bucket_create("{}".to_string(), ctx.cluster_id, None)?;
+    ctx.contract.bucket_alloc_into_cluster(bucket_id, 1)?;
+    set_caller_value(owner_id, 3000);
+    ctx.contract.account_deposit()?;
+    let deposit = ctx.contract.account_get(owner_id)?.deposit.peek();
+
+    advance_block::<DefaultEnvironment>();
+    advance_block::<DefaultEnvironment>();
+    advance_block::<DefaultEnvironment>();
+
+    set_caller(owner_id);
+    ctx.contract.bucket_remove(bucket_id)?;
+    assert_eq!(ctx.contract.bucket_get(bucket_id), Err(BucketDoesNotExist));
+
+    // The deposit pays what it can, and the payer owes the rest.
+    let account = ctx.contract.account_get(owner_id)?;
+    assert_eq!(account.deposit.peek(), 0);
+    assert!(account.negative.peek() > 0);
+    assert_eq!(account.payable_schedule, Schedule::empty());
+
+    let cluster = ctx.contract.cluster_get(ctx.cluster_id)?.cluster;
+    assert_eq!(cluster.revenues.peek(), deposit);
+}
+
+#[ink::test]
+fn bucket_remove_err_if_not_owner() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+
+    set_caller(ctx.manager_id);
+    assert_eq!(
+        ctx.contract.bucket_remove(test_bucket.bucket_id),
+        Err(OnlyOwner)
+    );
+}
+
+#[ink::test]
+fn bucket_grants_expire_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+    let accounts = get_accounts();
+    let expires_at_ms = block_timestamp::<DefaultEnvironment>() + 1;
+
+    set_caller(test_bucket.owner_id);
+    ctx.contract
+        .bucket_set_writer_perm(test_bucket.bucket_id, accounts.bob, None)?;
+    ctx.contract.bucket_set_writer_perm(
+        test_bucket.bucket_id,
+        accounts.charlie,
+        Some(expires_at_ms),
+    )?;
+    // Granting again only changes the expiry.
+    ctx.contract
+        .bucket_set_reader_perm(test_bucket.bucket_id, accounts.django, None)?;
+    ctx.contract.bucket_set_reader_perm(
+        test_bucket.bucket_id,
+        accounts.django,
+        Some(expires_at_ms),
+    )?;
+
+    assert_eq!(
+        ctx.contract.get_bucket_writers(test_bucket.bucket_id),
+        vec![accounts.bob, accounts.charlie]
+    );
+    assert_eq!(
+        ctx.contract.get_bucket_readers(test_bucket.bucket_id),
+        vec![accounts.django]
+    );
+
+    // Go past the expiry.
+    advance_block::<DefaultEnvironment>();
+
+    assert_eq!(
+        ctx.contract.get_bucket_writers(test_bucket.bucket_id),
+        vec![accounts.bob]
+    );
+    assert_eq!(
+        ctx.contract.get_bucket_readers(test_bucket.bucket_id),
+        vec![]
+    );
+    let status = ctx.contract.bucket_get(test_bucket.bucket_id)?;
+    assert_eq!(status.writer_ids, vec![accounts.bob, test_bucket.owner_id]);
+    assert_eq!(status.reader_ids, vec![]);
+
+    // Free the storage of the expired grants.
+    assert_eq!(
+        ctx.contract
+            .bucket_purge_expired_grants(test_bucket.bucket_id),
+        Ok(2)
+    );
+    assert_eq!(
+        ctx.contract
+            .bucket_purge_expired_grants(test_bucket.bucket_id),
+        Ok(0)
+    );
+    assert_eq!(
+        ctx.contract
+            .buckets
+            .writers
+            .get(test_bucket.bucket_id)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[ink::test]
+fn bucket_check_access_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+    let accounts = get_accounts();
+    let bucket_id = test_bucket.bucket_id;
+
+    set_caller(test_bucket.owner_id);
+    ctx.contract
+        .bucket_set_writer_perm(bucket_id, accounts.bob, None)?;
+    ctx.contract
+        .bucket_set_reader_perm(bucket_id, accounts.charlie, None)?;
+
+    let check = |ctx: &TestCluster, account_id: AccountId| {
+        [AccessKind::Read, AccessKind::Write, AccessKind::Admin].map(|access| {
+            ctx.contract
+                .bucket_check_access(bucket_id, account_id, access)
+                .unwrap()
+        })
+    };
+
+    assert_eq!(check(ctx, test_bucket.owner_id), [true; 3]);
+    assert_eq!(check(ctx, ctx.manager_id), [true; 3]);
+    assert_eq!(check(ctx, accounts.bob), [true, true, false]);
+    assert_eq!(check(ctx, accounts.charlie), [true, false, false]);
+    assert_eq!(check(ctx, accounts.django), [false; 3]);
+
+    // Anyone can read a public bucket.
+    ctx.contract.bucket_set_availability(bucket_id, true)?;
+    assert_eq!(check(ctx, accounts.django), [true, false, false]);
+
+    assert_eq!(
+        ctx.contract
+            .bucket_check_access(bucket_id + 1, accounts.bob, AccessKind::Read),
+        Err(BucketDoesNotExist)
+    );
+}
+
+#[ink::test]
+fn bucket_release_from_cluster_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+
+    // Grow the reservation to 4, then release half of it.
+    set_caller(test_bucket.owner_id);
+    ctx.contract
+        .bucket_alloc_into_cluster(test_bucket.bucket_id, 3)?;
+    let resource_used_before = ctx
+        .contract
+        .cluster_get(ctx.cluster_id)?
+        .cluster
+        .resource_used;
+    let rate_before = ctx
+        .contract
+        .buckets
+        .get(test_bucket.bucket_id)?
+        .flow
+        .schedule
+        .get_rate();
+
+    advance_block::<DefaultEnvironment>();
+    ctx.contract
+        .bucket_release_from_cluster(test_bucket.bucket_id, 2)?;
+
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketReleased(ev) if ev ==
+        BucketReleased {
+            bucket_id: test_bucket.bucket_id,
+            cluster_id: ctx.cluster_id,
+            resource: 2
+        })
+    );
+
+    let bucket = ctx.contract.buckets.get(test_bucket.bucket_id)?;
+    assert_eq!(bucket.resource_reserved, 2);
+    assert_eq!(
+        bucket.flow.schedule.get_rate(),
+        rate_before / 2,
+        "the rate must be reduced in proportion"
+    );
+
+    let cluster = ctx.contract.cluster_get(ctx.cluster_id)?.cluster;
+    assert_eq!(cluster.resource_used, resource_used_before - 2);
+    assert!(
+        cluster.revenues.peek() > 0,
+        "the due costs must be settled to the cluster"
+    );
+
+    let account = ctx.contract.account_get(test_bucket.owner_id)?;
+    assert_eq!(account.payable_schedule.get_rate(), rate_before / 2);
+
+    // The payer is charged at the reduced rate from now on.
+    let deposit_before = account.deposit.peek();
+    let timestamp_before = block_timestamp::<DefaultEnvironment>();
+    advance_block::<DefaultEnvironment>();
+    ctx.contract.bucket_settle_payment(test_bucket.bucket_id)?;
+    let timespan = block_timestamp::<DefaultEnvironment>() - timestamp_before;
+
+    let spent = deposit_before
+        - ctx
+            .contract
+            .account_get(test_bucket.owner_id)?
+            .deposit
+            .peek();
+    assert_eq!(spent, rate_before / 2 * timespan as Balance / MS_PER_MONTH);
+}
+
+#[ink::test]
+fn bucket_release_from_cluster_err_if_more_than_reserved() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+
+    set_caller(test_bucket.owner_id);
+    assert_eq!(
+        ctx.contract
+            .bucket_release_from_cluster(test_bucket.bucket_id, test_bucket.resource + 1),
+        Err(InsufficientBucketResources)
+    );
+
+    // A bucket without reserved resources.
+    set_caller_value(test_bucket.owner_id, CONTRACT_FEE_LIMIT);
+    let bucket_id = ctx
+        .contract
+        .bucket_create("{}".to_string(), ctx.cluster_id, None)?;
+    assert_eq!(
+        ctx.contract.bucket_release_from_cluster(bucket_id, 1),
+        Err(InsufficientBucketResources)
+    );
+}
+
+#[ink::test]
+fn bucket_migrate_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+
+    // Create a target cluster with a single node.
+    let node_key = AccountId::from([0x1a; 32]);
+    let rent_v_node_per_month = 5 * TOKEN;
+    set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+    ctx.contract.node_create(
+        node_key,
+        NodeParams::from("{\"url\":\"https://ddc-3.cere.network/storage/3\"}"),
+        100,
+        rent_v_node_per_month,
+    )?;
+
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    let target_cluster_id = ctx
+        .contract
+        .cluster_create(ClusterParams::from("{}"), ctx.resource_per_v_node)?;
+    let target_v_nodes = vec![1, 2];
+    ctx.contract
+        .cluster_add_node(target_cluster_id, node_key, target_v_nodes.clone())?;
+    ctx.contract.cluster_set_node_status(
+        target_cluster_id,
+        node_key,
+        NodeStatusInCluster::ACTIVE,
+    )?;
+
+    // The source cluster announces an increase of the rate.
+    let node_key3 = AccountId::from([0x1c; 32]);
+    set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+    ctx.contract.node_create(
+        node_key3,
+        NodeParams::from("{\"url\":\"https://ddc-3.cere.network/storage/3\"}"),
+        100,
+        10 * TOKEN,
+    )?;
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    ctx.contract
+        .cluster_add_node(ctx.cluster_id, node_key3, vec![10, 11, 12])?;
+    ctx.contract.bucket_rerate(test_bucket.bucket_id)?;
+    assert!(ctx
+        .contract
+        .bucket_get_pending_rate(test_bucket.bucket_id)
+        .is_some());
+
+    advance_block::<DefaultEnvironment>();
+    set_caller(test_bucket.owner_id);
+    ctx.contract
+        .bucket_migrate(test_bucket.bucket_id, target_cluster_id)?;
+
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketMigrated(ev) if ev ==
+        BucketMigrated {
+            bucket_id: test_bucket.bucket_id,
+            source_cluster_id: ctx.cluster_id,
+            target_cluster_id,
+            resource: test_bucket.resource
+        })
+    );
+
+    let source_cluster = ctx.contract.cluster_get(ctx.cluster_id)?.cluster;
+    assert_eq!(source_cluster.resource_used, 0);
+    assert!(
+        source_cluster.revenues.peek() > 0,
+        "the due costs must be settled to the source cluster"
+    );
+
+    let target_cluster = ctx.contract.cluster_get(target_cluster_id)?.cluster;
+    assert_eq!(target_cluster.resource_used, test_bucket.resource);
+
+    let expected_rate =
+        rent_v_node_per_month * target_v_nodes.len() as Balance * test_bucket.resource as Balance;
+    let bucket = ctx.contract.buckets.get(test_bucket.bucket_id)?;
+    assert_eq!(bucket.cluster_id, target_cluster_id);
+    assert_eq!(bucket.flow.schedule.get_rate(), expected_rate);
+    assert_eq!(
+        ctx.contract.bucket_get_pending_rate(test_bucket.bucket_id),
+        None,
+        "the announced rate of the source cluster must not apply"
+    );
+    assert_eq!(
+        ctx.contract
+            .buckets
+            .cluster_buckets
+            .get_all(target_cluster_id),
+        vec![test_bucket.bucket_id]
+    );
+    assert_eq!(ctx.contract.buckets.cluster_buckets.len(ctx.cluster_id), 0);
+
+    let account = ctx.contract.account_get(test_bucket.owner_id)?;
+    assert_eq!(
+        account.payable_schedule.get_rate(),
+        expected_rate,
+        "the payer must only pay the target cluster"
+    );
+}
+
+#[ink::test]
+fn bucket_migrate_err_if_insufficient_cluster_resources() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    let empty_cluster_id = ctx
+        .contract
+        .cluster_create(ClusterParams::from("{}"), ctx.resource_per_v_node)?;
+
+    set_caller(test_bucket.owner_id);
+    assert_eq!(
+        ctx.contract
+            .bucket_migrate(test_bucket.bucket_id, empty_cluster_id),
+        Err(InsufficientClusterResources)
+    );
+    assert_eq!(
+        ctx.contract
+            .bucket_migrate(test_bucket.bucket_id, ctx.cluster_id),
+        Err(BucketIsAlreadyInCluster)
+    );
+}
+
+#[ink::test]
+fn bucket_set_payer_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+    let rate = ctx
+        .contract
+        .buckets
+        .get(test_bucket.bucket_id)?
+        .flow
+        .schedule
+        .get_rate();
+
+    let sponsor_id = AccountId::from([0x5b; 32]);
+    set_balance(sponsor_id, 1000 * TOKEN);
+    set_caller_value(sponsor_id, 10 * TOKEN);
+    ctx.contract.account_deposit()?;
+
+    // The owner proposes a sponsor, the flow is not moved yet.
+    set_caller(test_bucket.owner_id);
+    ctx.contract
+        .bucket_set_payer(test_bucket.bucket_id, sponsor_id)?;
+
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketPayerProposed(ev) if ev ==
+        BucketPayerProposed { bucket_id: test_bucket.bucket_id, payer_id: sponsor_id })
+    );
+    let bucket = ctx.contract.buckets.get(test_bucket.bucket_id)?;
+    assert_eq!(bucket.flow.from, test_bucket.owner_id);
+
+    // The sponsor accepts and pays from now on.
+    advance_block::<DefaultEnvironment>();
+    set_caller(sponsor_id);
+    ctx.contract.bucket_accept_payer(test_bucket.bucket_id)?;
+
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketPayerSet(ev) if ev ==
+        BucketPayerSet { bucket_id: test_bucket.bucket_id, payer_id: sponsor_id })
+    );
+
+    let cluster = ctx.contract.cluster_get(ctx.cluster_id)?.cluster;
+    assert!(
+        cluster.revenues.peek() > 0,
+        "the due costs of the previous payer must be settled"
+    );
+
+    let status = ctx.contract.bucket_get(test_bucket.bucket_id)?;
+    assert_eq!(status.bucket.owner_id, test_bucket.owner_id);
+    assert_eq!(status.bucket.payer_id, sponsor_id);
+
+    let owner_account = ctx.contract.account_get(test_bucket.owner_id)?;
+    assert_eq!(owner_account.payable_schedule.get_rate(), 0);
+    let sponsor_account = ctx.contract.account_get(sponsor_id)?;
+    assert_eq!(sponsor_account.payable_schedule.get_rate(), rate);
+
+    // The sponsor can give the payment back to the owner.
+    ctx.contract
+        .bucket_set_payer(test_bucket.bucket_id, test_bucket.owner_id)?;
+
+    let sponsor_account = ctx.contract.account_get(sponsor_id)?;
+    assert_eq!(sponsor_account.payable_schedule.get_rate(), 0);
+    let owner_account = ctx.contract.account_get(test_bucket.owner_id)?;
+    assert_eq!(owner_account.payable_schedule.get_rate(), rate);
+}
+
+#[ink::test]
+fn bucket_set_payer_err_if_not_proposed() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+    let sponsor_id = AccountId::from([0x5b; 32]);
+
+    set_caller(sponsor_id);
+    assert_eq!(
+        ctx.contract
+            .bucket_set_payer(test_bucket.bucket_id, sponsor_id),
+        Err(OnlyOwner)
+    );
+    assert_eq!(
+        ctx.contract.bucket_accept_payer(test_bucket.bucket_id),
+        Err(OnlyPendingPayer)
+    );
+    assert_eq!(
+        ctx.contract
+            .bucket_set_payer(test_bucket.bucket_id, test_bucket.owner_id),
+        Err(OnlyOwnerOrPayer)
+    );
+}
+
+#[ink::test]
+fn bucket_transfer_ownership_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+    let new_owner_id = AccountId::from([0x5c; 32]);
+
+    // Propose a new owner, the current owner keeps paying.
+    set_caller(test_bucket.owner_id);
+    ctx.contract
+        .bucket_transfer_ownership(test_bucket.bucket_id, new_owner_id, true)?;
+
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketOwnershipTransferProposed(ev) if ev ==
+        BucketOwnershipTransferProposed { bucket_id: test_bucket.bucket_id, owner_id: new_owner_id })
+    );
+    let status = ctx.contract.bucket_get(test_bucket.bucket_id)?;
+    assert_eq!(status.bucket.owner_id, test_bucket.owner_id);
+
+    // The new owner accepts.
+    set_caller(new_owner_id);
+    ctx.contract
+        .bucket_accept_ownership(test_bucket.bucket_id)?;
+
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketOwnershipTransferred(ev) if ev ==
+        BucketOwnershipTransferred { bucket_id: test_bucket.bucket_id, owner_id: new_owner_id })
+    );
+    let status = ctx.contract.bucket_get(test_bucket.bucket_id)?;
+    assert_eq!(status.bucket.owner_id, new_owner_id);
+    assert_eq!(status.bucket.payer_id, test_bucket.owner_id);
+    ctx.contract.account_get(new_owner_id)?;
+
+    // Hand the payment over to the next owner.
+    let next_owner_id = AccountId::from([0x5d; 32]);
+    ctx.contract
+        .bucket_transfer_ownership(test_bucket.bucket_id, next_owner_id, false)?;
+    set_caller(next_owner_id);
+    ctx.contract
+        .bucket_accept_ownership(test_bucket.bucket_id)?;
+
+    let status = ctx.contract.bucket_get(test_bucket.bucket_id)?;
+    assert_eq!(status.bucket.owner_id, next_owner_id);
+    assert_eq!(status.bucket.payer_id, next_owner_id);
+    let account = ctx.contract.account_get(test_bucket.owner_id)?;
+    assert_eq!(account.payable_schedule.get_rate(), 0);
+}
+
+#[ink::test]
+fn bucket_transfer_ownership_err_if_not_proposed() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+    let new_owner_id = AccountId::from([0x5c; 32]);
+
+    set_caller(new_owner_id);
+    assert_eq!(
+        ctx.contract
+            .bucket_transfer_ownership(test_bucket.bucket_id, new_owner_id, true),
+        Err(OnlyOwner)
+    );
+    assert_eq!(
+        ctx.contract.bucket_accept_ownership(test_bucket.bucket_id),
+        Err(OnlyPendingOwner)
+    );
+
+    // A cancelled proposal cannot be accepted.
+    set_caller(test_bucket.owner_id);
+    ctx.contract
+        .bucket_transfer_ownership(test_bucket.bucket_id, new_owner_id, true)?;
+    ctx.contract
+        .bucket_cancel_ownership_transfer(test_bucket.bucket_id)?;
+
+    set_caller(new_owner_id);
+    assert_eq!(
+        ctx.contract.bucket_accept_ownership(test_bucket.bucket_id),
+        Err(OnlyPendingOwner)
+    );
+}
+
 #[ink::test]
 fn bucket_list_ok() {
-    let mut ddc_bucket = setup_contract();
+    let mut ctx = setup_cluster();
+    setup_active_nodes(&mut ctx);
+    let mut ddc_bucket = ctx.contract;
 
     let owner_id1 = AccountId::from([
         0xd8, 0x69, 0x19, 0x54, 0xea, 0xdc, 0x9a, 0xc0, 0x3d, 0x37, 0x56, 0x9f, 0x2a, 0xe8, 0xdf,
@@ -220,7 +948,7 @@ fn bucket_list_ok() {
     ]);
     set_balance(owner_id3, 1000 * TOKEN);
 
-    let cluster_id = 0;
+    let cluster_id = ctx.cluster_id;
 
     set_caller_value(owner_id1, CONTRACT_FEE_LIMIT);
     let bucket_id1 = ddc_bucket.bucket_create("".to_string(), cluster_id, None)?;
@@ -270,3 +998,310 @@ fn bucket_list_ok() {
         (vec![], count)
     );
 }
+
+#[ink::test]
+fn bucket_list_for_account_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+    let owner_id = test_bucket.owner_id;
+    let new_owner_id = AccountId::from([0x5c; 32]);
+
+    set_caller_value(owner_id, CONTRACT_FEE_LIMIT);
+    let bucket_id2 = ctx
+        .contract
+        .bucket_create("{}".to_string(), ctx.cluster_id, None)?;
+    set_caller_value(owner_id, CONTRACT_FEE_LIMIT);
+    let bucket_id3 = ctx
+        .contract
+        .bucket_create("{}".to_string(), ctx.cluster_id, None)?;
+
+    let status1 = ctx.contract.bucket_get(test_bucket.bucket_id)?;
+    let status2 = ctx.contract.bucket_get(bucket_id2)?;
+    let status3 = ctx.contract.bucket_get(bucket_id3)?;
+
+    assert_eq!(
+        ctx.contract.bucket_list_for_account(owner_id, 0, 2),
+        (vec![status1.clone(), status2], 3)
+    );
+    assert_eq!(
+        ctx.contract.bucket_list_for_account(owner_id, 2, 2),
+        (vec![status3], 3)
+    );
+    assert_eq!(
+        ctx.contract.bucket_list_for_account(new_owner_id, 0, 10),
+        (vec![], 0)
+    );
+
+    // The index follows ownership transfers and removals.
+    set_caller(owner_id);
+    ctx.contract
+        .bucket_transfer_ownership(bucket_id2, new_owner_id, true)?;
+    set_caller(new_owner_id);
+    ctx.contract.bucket_accept_ownership(bucket_id2)?;
+
+    set_caller(owner_id);
+    ctx.contract.bucket_remove(test_bucket.bucket_id)?;
+
+    let list_ids = |ctx: &TestCluster, owner_id: AccountId| {
+        let (statuses, total) = ctx.contract.bucket_list_for_account(owner_id, 0, 10);
+        let bucket_ids: Vec<BucketId> = statuses.iter().map(|s| s.bucket_id).collect();
+        (bucket_ids, total)
+    };
+    assert_eq!(list_ids(ctx, owner_id), (vec![bucket_id3], 1));
+    assert_eq!(list_ids(ctx, new_owner_id), (vec![bucket_id2], 1));
+}
+
+#[ink::test]
+fn bucket_check_suspension_ok() {
+    let ctx = &mut setup_cluster();
+    setup_active_nodes(ctx);
+    let owner_id = AccountId::from([0x5e; 32]);
+    set_balance(owner_id, 1000 * TOKEN);
+
+    // Cover the rent of the bucket for about one block, with a grace period of two blocks.
+    set_caller(admin_id());
+    ctx.contract.admin_set_bucket_grace_period_ms(12)?;
+
+    set_caller_value(owner_id, CONTRACT_FEE_LIMIT);
+    let bucket_id = ctx
+        .contract
+        .bucket_create("{}".to_string(), ctx.cluster_id, None)?;
+    ctx.contract.bucket_alloc_into_cluster(bucket_id, 1)?;
+    let rate = ctx
+        .contract
+        .buckets
+        .get(bucket_id)?
+        .flow
+        .schedule
+        .get_rate();
+    set_caller_value(owner_id, 3000);
+    ctx.contract.account_deposit()?;
+
+    assert_eq!(
+        ctx.contract.bucket_check_suspension(bucket_id),
+        Ok(BucketState::Active)
+    );
+
+    advance_block::<DefaultEnvironment>();
+    advance_block::<DefaultEnvironment>();
+    assert_eq!(
+        ctx.contract.bucket_check_suspension(bucket_id),
+        Ok(BucketState::GracePeriod)
+    );
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketStateChanged(ev) if ev ==
+        BucketStateChanged { bucket_id, state: BucketState::GracePeriod })
+    );
+    assert!(ctx
+        .contract
+        .bucket_check_access(bucket_id, owner_id, AccessKind::Write)?);
+
+    advance_block::<DefaultEnvironment>();
+    advance_block::<DefaultEnvironment>();
+    assert_eq!(
+        ctx.contract.bucket_check_suspension(bucket_id),
+        Ok(BucketState::Suspended)
+    );
+    assert!(!ctx
+        .contract
+        .bucket_check_access(bucket_id, owner_id, AccessKind::Read)?);
+
+    // The payment flow is paused while the bucket is suspended.
+    assert_eq!(
+        ctx.contract
+            .buckets
+            .get(bucket_id)?
+            .flow
+            .schedule
+            .get_rate(),
+        0
+    );
+    assert_eq!(ctx.contract.buckets.paused_rates.get(bucket_id), Some(rate));
+    set_caller(owner_id);
+    assert_eq!(
+        ctx.contract.bucket_alloc_into_cluster(bucket_id, 1),
+        Err(BucketIsSuspended)
+    );
+
+    // Checking again does not emit an event.
+    let events_len = get_events::<Event>().len();
+    advance_block::<DefaultEnvironment>();
+    ctx.contract.bucket_check_suspension(bucket_id)?;
+    assert_eq!(get_events::<Event>().len(), events_len);
+
+    // A deposit reactivates the bucket, and its payment flow resumes from now.
+    advance_block::<DefaultEnvironment>();
+    set_caller_value(owner_id, 10 * TOKEN);
+    ctx.contract.account_deposit()?;
+    assert_eq!(
+        ctx.contract
+            .buckets
+            .get(bucket_id)?
+            .flow
+            .schedule
+            .get_rate(),
+        rate
+    );
+    assert_eq!(ctx.contract.buckets.paused_rates.get(bucket_id), None);
+    let account = ctx.contract.account_get(owner_id)?;
+    assert_eq!(
+        account
+            .payable_schedule
+            .value_at_time(block_timestamp::<DefaultEnvironment>()),
+        0,
+        "the suspended time must not be charged"
+    );
+
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketStateChanged(ev) if ev ==
+        BucketStateChanged { bucket_id, state: BucketState::Active })
+    );
+    assert_eq!(
+        ctx.contract.bucket_get(bucket_id)?.bucket.state,
+        BucketState::Active
+    );
+    assert_eq!(ctx.contract.buckets.get_unpaid_buckets(owner_id), vec![]);
+}
+
+#[ink::test]
+fn bucket_rerate_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+    let bucket_id = test_bucket.bucket_id;
+    let get_rate = |ctx: &TestCluster| {
+        ctx.contract
+            .buckets
+            .get(bucket_id)
+            .unwrap()
+            .flow
+            .schedule
+            .get_rate()
+    };
+    let old_rate = get_rate(ctx);
+
+    set_caller(admin_id());
+    ctx.contract.admin_set_bucket_rerate_notice_ms(12)?;
+
+    // The rate does not change while the rent is the same.
+    ctx.contract.bucket_rerate(bucket_id)?;
+    assert_eq!(get_rate(ctx), old_rate);
+    assert_eq!(ctx.contract.bucket_get_pending_rate(bucket_id), None);
+
+    // The rent of the cluster increases with a new node.
+    let node_key3 = AccountId::from([0x1c; 32]);
+    set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+    ctx.contract.node_create(
+        node_key3,
+        NodeParams::from("{\"url\":\"https://ddc-3.cere.network/storage/3\"}"),
+        100,
+        10 * TOKEN,
+    )?;
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    ctx.contract
+        .cluster_add_node(ctx.cluster_id, node_key3, vec![10, 11, 12])?;
+    let new_rate = ctx.contract.cluster_get(ctx.cluster_id)?.cluster.total_rent
+        * test_bucket.resource as Balance;
+    assert!(new_rate > old_rate);
+
+    // The increase is announced first.
+    ctx.contract.bucket_rerate(bucket_id)?;
+    let effective_at_ms = block_timestamp::<DefaultEnvironment>() + 12;
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketRerateScheduled(ev) if ev ==
+        BucketRerateScheduled { bucket_id, rate: new_rate, effective_at_ms })
+    );
+    assert_eq!(
+        ctx.contract.bucket_get_pending_rate(bucket_id),
+        Some(PendingRate {
+            rate: new_rate,
+            effective_at_ms
+        })
+    );
+    assert_eq!(get_rate(ctx), old_rate);
+
+    // Not yet effective.
+    advance_block::<DefaultEnvironment>();
+    ctx.contract.bucket_rerate(bucket_id)?;
+    assert_eq!(get_rate(ctx), old_rate);
+
+    // The increase applies after the notice period.
+    advance_block::<DefaultEnvironment>();
+    let revenues_before = ctx.contract.cluster_get(ctx.cluster_id)?.cluster.revenues;
+    ctx.contract.bucket_rerate(bucket_id)?;
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketRerated(ev) if ev ==
+        BucketRerated { bucket_id, rate: new_rate })
+    );
+    assert_eq!(get_rate(ctx), new_rate);
+    assert_eq!(ctx.contract.bucket_get_pending_rate(bucket_id), None);
+    let revenues_after = ctx.contract.cluster_get(ctx.cluster_id)?.cluster.revenues;
+    assert!(
+        revenues_after.peek() > revenues_before.peek(),
+        "the old flow must be settled"
+    );
+
+    // A decrease applies at once.
+    ctx.contract
+        .cluster_remove_node(ctx.cluster_id, node_key3)?;
+    ctx.contract.bucket_rerate(bucket_id)?;
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::BucketRerated(ev) if ev ==
+        BucketRerated { bucket_id, rate: old_rate })
+    );
+    assert_eq!(get_rate(ctx), old_rate);
+}
+
+#[ink::test]
+fn bucket_deposit_refreshes_bounded_buckets_ok() {
+    let ctx = &mut setup_cluster();
+    setup_active_nodes(ctx);
+    let owner_id = AccountId::from([0x5e; 32]);
+    set_balance(owner_id, 1000 * TOKEN);
+
+    set_caller(admin_id());
+    ctx.contract.admin_set_bucket_grace_period_ms(0)?;
+
+    // The buckets are suspended at once without a deposit.
+    set_caller_value(owner_id, CONTRACT_FEE_LIMIT);
+    let mut bucket_ids = Vec::new();
+    for _ in 0..MAX_UNPAID_BUCKETS_REFRESHED_PER_CALL + 2 {
+        let bucket_id = ctx
+            .contract
+            .bucket_create("{}".to_string(), ctx.cluster_id, None)?;
+        ctx.contract.bucket_alloc_into_cluster(bucket_id, 1)?;
+        bucket_ids.push(bucket_id);
+    }
+    advance_block::<DefaultEnvironment>();
+    for bucket_id in &bucket_ids {
+        assert_eq!(
+            ctx.contract.bucket_check_suspension(*bucket_id),
+            Ok(BucketState::Suspended)
+        );
+    }
+    let get_states = |ctx: &TestCluster| -> Vec<BucketState> {
+        bucket_ids
+            .iter()
+            .map(|bucket_id| ctx.contract.buckets.get(*bucket_id).unwrap().state)
+            .collect()
+    };
+
+    // A deposit reactivates a bounded number of buckets.
+    set_caller_value(owner_id, 10 * TOKEN);
+    ctx.contract.account_deposit()?;
+    let states = get_states(ctx);
+    assert_eq!(
+        states
+            .iter()
+            .filter(|state| **state == BucketState::Active)
+            .count(),
+        MAX_UNPAID_BUCKETS_REFRESHED_PER_CALL as usize
+    );
+
+    // The next deposit continues with the other buckets.
+    set_caller_value(owner_id, 10 * TOKEN);
+    ctx.contract.account_deposit()?;
+    assert!(get_states(ctx)
+        .iter()
+        .all(|state| *state == BucketState::Active));
+    assert_eq!(ctx.contract.buckets.get_unpaid_buckets(owner_id), vec![]);
+}
@@ -1,3 +1,4 @@
+use ink_env::{block_timestamp, test::advance_block, DefaultEnvironment};
 use ink_lang as ink;
 
 use super::env_utils::*;
@@ -5,6 +6,7 @@ use super::setup_utils::*;
 use crate::ddc_bucket::Error::*;
 use crate::ddc_bucket::*;
 use cdn_node::entity::*;
+use cluster::store::MAX_CLUSTER_OPERATORS_LEN;
 
 #[ink::test]
 fn cluster_create_ok() {
@@ -190,7 +192,6 @@ fn cluster_create_ok() {
             cluster_id: ctx.cluster_id,
             cluster: Cluster {
                 manager_id: ctx.manager_id,
-                nodes_keys: ctx.nodes_keys,
                 resource_per_v_node: ctx.resource_per_v_node,
                 resource_used: 0,
                 cluster_params: ctx.cluster_params.clone(),
@@ -200,9 +201,14 @@ fn cluster_create_ok() {
                 cdn_usd_per_gb: CDN_USD_PER_GB,
                 cdn_revenues: Cash(0),
             },
-            cluster_v_nodes
+            nodes_len: 3,
         }
     );
+    assert_eq!(
+        ctx.contract
+            .cluster_list_nodes_v_nodes(ctx.cluster_id, 0, 10),
+        (cluster_v_nodes, 3)
+    );
 
     // Check emitted events
     let mut events = get_events();
@@ -502,16 +508,12 @@ fn cluster_add_node_ok() {
 
     let _nodes_keys = vec![ctx.node_key0, ctx.node_key1, ctx.node_key2, new_node_key];
 
-    let _cluster_v_nodes = vec![
-        ctx.v_nodes0,
-        ctx.v_nodes1,
-        ctx.v_nodes2,
-        new_v_nodes.clone(),
-    ];
-
     let cluster_info = ctx.contract.cluster_get(ctx.cluster_id)?;
-    assert!(matches!(cluster_info.cluster.nodes_keys, _nodes_keys));
-    assert!(matches!(cluster_info.cluster_v_nodes, _cluster_v_nodes));
+    assert_eq!(
+        ctx.contract.cluster_list_nodes(ctx.cluster_id, 0, 10).0,
+        _nodes_keys
+    );
+    assert_eq!(cluster_info.nodes_len, 4);
 
     let node_info = ctx.contract.node_get(new_node_key)?;
     let _expected_node_info = NodeInfo {
@@ -595,11 +597,12 @@ fn cluster_remove_node_ok_if_node_provider() {
 
     let _nodes_keys = vec![ctx.node_key0, ctx.node_key2];
 
-    let _cluster_v_nodes = vec![ctx.v_nodes0.clone(), ctx.v_nodes2];
-
     let cluster_info = ctx.contract.cluster_get(ctx.cluster_id)?;
-    assert!(matches!(cluster_info.cluster.nodes_keys, _nodes_keys));
-    assert!(matches!(cluster_info.cluster_v_nodes, _cluster_v_nodes));
+    assert_eq!(
+        ctx.contract.cluster_list_nodes(ctx.cluster_id, 0, 10).0,
+        _nodes_keys
+    );
+    assert_eq!(cluster_info.nodes_len, 2);
 
     let node_info = ctx.contract.node_get(ctx.node_key1)?;
     let v_nodes0_len: u32 = ctx.v_nodes0.len().try_into().unwrap();
@@ -636,11 +639,12 @@ fn cluster_remove_node_ok_if_cluster_manager() {
 
     let _nodes_keys = vec![ctx.node_key0, ctx.node_key1];
 
-    let _cluster_v_nodes = vec![ctx.v_nodes0, ctx.v_nodes1];
-
     let cluster_info = ctx.contract.cluster_get(ctx.cluster_id)?;
-    assert!(matches!(cluster_info.cluster.nodes_keys, _nodes_keys));
-    assert!(matches!(cluster_info.cluster_v_nodes, _cluster_v_nodes));
+    assert_eq!(
+        ctx.contract.cluster_list_nodes(ctx.cluster_id, 0, 10).0,
+        _nodes_keys
+    );
+    assert_eq!(cluster_info.nodes_len, 2);
 
     let node_info = ctx.contract.node_get(ctx.node_key2)?;
     let v_nodes2_len: u32 = ctx.v_nodes2.len().try_into().unwrap();
@@ -1163,9 +1167,11 @@ fn cluster_replace_node_ok() {
     node_v_nodes_2.v_nodes.extend(v_nodes_to_reasign.clone());
     cluster_v_nodes.push(node_v_nodes_2);
 
-    let cluster_info = ctx.contract.cluster_get(ctx.cluster_id)?;
     assert_eq!(
-        &cluster_info.cluster_v_nodes, &cluster_v_nodes,
+        &ctx.contract
+            .cluster_list_nodes_v_nodes(ctx.cluster_id, 0, 10)
+            .0,
+        &cluster_v_nodes,
         "a v_node must be replaced"
     );
 
@@ -1365,9 +1371,11 @@ fn cluster_reset_node_ok() {
     };
     cluster_v_nodes.push(node_v_nodes_2);
 
-    let cluster_info = ctx.contract.cluster_get(ctx.cluster_id)?;
     assert_eq!(
-        &cluster_info.cluster_v_nodes, &cluster_v_nodes,
+        &ctx.contract
+            .cluster_list_nodes_v_nodes(ctx.cluster_id, 0, 10)
+            .0,
+        &cluster_v_nodes,
         "a v_node must be replaced"
     );
 
@@ -1412,8 +1420,11 @@ fn cluster_reserve_resource_ok() {
     let new_resource_per_v_node = 15;
 
     // Reserve more resources.
-    ctx.contract
-        .cluster_set_resource_per_v_node(ctx.cluster_id, new_resource_per_v_node)?;
+    assert!(ctx.contract.cluster_set_resource_per_v_node(
+        ctx.cluster_id,
+        new_resource_per_v_node,
+        10
+    )?);
 
     // Check the last event.
     let ev = get_events().pop().unwrap();
@@ -1456,6 +1467,117 @@ fn cluster_reserve_resource_ok() {
     }
 }
 
+#[ink::test]
+fn cluster_reserve_resource_paged_ok() {
+    let mut ctx = setup_cluster();
+    set_caller(ctx.manager_id);
+
+    let new_resource_per_v_node = 15;
+    let free_resource_before = ctx.node_capacity0 - 3 * ctx.resource_per_v_node;
+    let free_resource_after = ctx.node_capacity0 - 3 * new_resource_per_v_node;
+
+    // Check the first 2 nodes, the cluster keeps the old value.
+    assert!(!ctx.contract.cluster_set_resource_per_v_node(
+        ctx.cluster_id,
+        new_resource_per_v_node,
+        2
+    )?);
+    let cluster = ctx.contract.cluster_get(ctx.cluster_id)?.cluster;
+    assert_eq!(cluster.resource_per_v_node, ctx.resource_per_v_node);
+    assert_eq!(
+        ctx.contract.node_get(ctx.node_key0)?.node.free_resource,
+        free_resource_before
+    );
+
+    // The nodes cannot change during the update.
+    assert_eq!(
+        ctx.contract
+            .cluster_remove_node(ctx.cluster_id, ctx.node_key2),
+        Err(ClusterNodesAreLocked)
+    );
+
+    // Check the last node, switch the cluster to the new value, and update the first node.
+    assert!(!ctx.contract.cluster_set_resource_per_v_node(
+        ctx.cluster_id,
+        new_resource_per_v_node,
+        2
+    )?);
+    let cluster = ctx.contract.cluster_get(ctx.cluster_id)?.cluster;
+    assert_eq!(cluster.resource_per_v_node, new_resource_per_v_node);
+    assert_eq!(
+        ctx.contract.node_get(ctx.node_key0)?.node.free_resource,
+        free_resource_after
+    );
+    assert_eq!(
+        ctx.contract.node_get(ctx.node_key1)?.node.free_resource,
+        free_resource_before
+    );
+
+    // Another value cannot be set once the update is being applied.
+    assert_eq!(
+        ctx.contract
+            .cluster_set_resource_per_v_node(ctx.cluster_id, 20, 10),
+        Err(ClusterNodesAreLocked)
+    );
+
+    // Update the other nodes.
+    assert!(ctx.contract.cluster_set_resource_per_v_node(
+        ctx.cluster_id,
+        new_resource_per_v_node,
+        2
+    )?);
+    let ev = get_events().pop().unwrap();
+    assert!(matches!(ev, Event::ClusterReserveResource(ev) if ev ==
+        ClusterReserveResource {
+            cluster_id: ctx.cluster_id,
+            resource: new_resource_per_v_node
+        }
+    ));
+    for node_key in [ctx.node_key1, ctx.node_key2] {
+        assert_eq!(
+            ctx.contract.node_get(node_key)?.node.free_resource,
+            free_resource_after
+        );
+    }
+
+    // The nodes are unlocked.
+    ctx.contract
+        .cluster_remove_node(ctx.cluster_id, ctx.node_key2)?;
+}
+
+#[ink::test]
+fn cluster_reserve_resource_err_if_insufficient_node_resources() {
+    let mut ctx = setup_cluster();
+    set_caller(ctx.manager_id);
+
+    // Start an update that the nodes can take.
+    assert!(!ctx
+        .contract
+        .cluster_set_resource_per_v_node(ctx.cluster_id, 20, 1)?);
+
+    // Restart with a value that the nodes cannot take.
+    assert_eq!(
+        ctx.contract
+            .cluster_set_resource_per_v_node(ctx.cluster_id, 40, 1),
+        Err(InsufficientNodeResources)
+    );
+
+    // Cancel the update with the current value.
+    assert!(ctx.contract.cluster_set_resource_per_v_node(
+        ctx.cluster_id,
+        ctx.resource_per_v_node,
+        10
+    )?);
+    let cluster = ctx.contract.cluster_get(ctx.cluster_id)?.cluster;
+    assert_eq!(cluster.resource_per_v_node, ctx.resource_per_v_node);
+    assert_eq!(
+        ctx.contract.node_get(ctx.node_key0)?.node.free_resource,
+        ctx.node_capacity0 - 3 * ctx.resource_per_v_node
+    );
+    ctx.contract
+        .cluster_remove_node(ctx.cluster_id, ctx.node_key2)?;
+}
+
 #[ink::test]
 fn cluster_distribute_revenue_ok() {
     let ctx = &mut setup_cluster();
@@ -1474,10 +1596,10 @@ fn cluster_distribute_revenue_ok() {
         .revenues
         .peek();
 
-    let before0 = balance_of(ctx.provider_id0);
-    let before1 = balance_of(ctx.provider_id1);
-    let before2 = balance_of(ctx.provider_id2);
-    let before_mgmt = balance_of(ctx.manager_id);
+    let before0 = ctx.contract.account_get_earnings(ctx.provider_id0);
+    let before1 = ctx.contract.account_get_earnings(ctx.provider_id1);
+    let before2 = ctx.contract.account_get_earnings(ctx.provider_id2);
+    let before_mgmt = ctx.contract.account_get_earnings(ctx.manager_id);
 
     let skip_events = get_events::<Event>().len();
 
@@ -1497,24 +1619,46 @@ fn cluster_distribute_revenue_ok() {
     let provider_fee: u128 = (to_distribute - burned_fee - manager_fee) / 3;
 
     // Distribute the revenues of the cluster to providers.
-    ctx.contract.cluster_distribute_revenues(ctx.cluster_id)?;
+    assert_eq!(
+        ctx.contract.cluster_distribute_revenues(ctx.cluster_id, 10),
+        Ok(true)
+    );
 
     // Check the last events.
     let mut events = get_events();
     events.reverse(); // Work with pop().
     events.truncate(events.len() - skip_events);
-    let expected_recipients = vec![ctx.provider_id0, ctx.provider_id1, ctx.provider_id2];
+    let expected_recipients = vec![
+        (ctx.provider_id0, ctx.node_key0),
+        (ctx.provider_id1, ctx.node_key1),
+        (ctx.provider_id2, ctx.node_key2),
+    ];
 
-    for provider_id in expected_recipients {
+    for (provider_id, node_key) in expected_recipients {
         assert!(
             matches!(events.pop().unwrap(), Event::ClusterDistributeRevenues(ev) if ev ==
             ClusterDistributeRevenues {
                 cluster_id: ctx.cluster_id,
-                provider_id
+                provider_id,
+                amount: provider_fee,
+                nodes_keys: vec![node_key],
             })
         );
     }
 
+    assert!(
+        matches!(events.pop().unwrap(), Event::ClusterDistributionProgress(ev) if ev ==
+        ClusterDistributionProgress {
+            cluster_id: ctx.cluster_id,
+            paid_providers: 3,
+            total_providers: 3
+        })
+    );
+    assert!(
+        matches!(events.pop().unwrap(), Event::ClusterDistributionCompleted(ev) if ev ==
+        ClusterDistributionCompleted { cluster_id: ctx.cluster_id })
+    );
+
     assert_eq!(events.len(), 0, "all events must be checked");
 
     // Get state after the distribution.
@@ -1525,10 +1669,10 @@ fn cluster_distribute_revenue_ok() {
         .revenues
         .peek();
 
-    let earned0 = balance_of(ctx.provider_id0) - before0;
-    let earned1 = balance_of(ctx.provider_id1) - before1;
-    let earned2 = balance_of(ctx.provider_id2) - before2;
-    let earned_mgmt = balance_of(ctx.manager_id) - before_mgmt;
+    let earned0 = ctx.contract.account_get_earnings(ctx.provider_id0) - before0;
+    let earned1 = ctx.contract.account_get_earnings(ctx.provider_id1) - before1;
+    let earned2 = ctx.contract.account_get_earnings(ctx.provider_id2) - before2;
+    let earned_mgmt = ctx.contract.account_get_earnings(ctx.manager_id) - before_mgmt;
 
     assert!(provider_fee > 0, "provider must earn something");
     assert_eq!(
@@ -1561,6 +1705,381 @@ fn cluster_distribute_revenue_ok() {
         to_distribute,
         "all revenues must go to providers"
     );
+
+    // The provider withdraws its earnings.
+    let balance_before = balance_of(ctx.provider_id0);
+    set_caller(ctx.provider_id0);
+    assert_eq!(ctx.contract.claim_earnings(), Ok(earned0));
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::EarningsClaimed(ev) if ev ==
+        EarningsClaimed { account_id: ctx.provider_id0, value: earned0 })
+    );
+    assert_eq!(balance_of(ctx.provider_id0) - balance_before, earned0);
+    assert_eq!(ctx.contract.account_get_earnings(ctx.provider_id0), 0);
+    assert_eq!(ctx.contract.claim_earnings(), Err(InsufficientBalance));
+}
+
+#[ink::test]
+fn cluster_distribute_revenue_paged_ok() {
+    let ctx = &mut setup_cluster();
+    let test_bucket = &setup_bucket(ctx);
+    advance_block::<DefaultEnvironment>();
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    ctx.contract.bucket_settle_payment(test_bucket.bucket_id)?;
+
+    let before0 = ctx.contract.account_get_earnings(ctx.provider_id0);
+    let before2 = ctx.contract.account_get_earnings(ctx.provider_id2);
+
+    // The first page weighs two nodes.
+    assert_eq!(
+        ctx.contract.cluster_distribute_revenues(ctx.cluster_id, 2),
+        Ok(false)
+    );
+    assert_eq!(
+        ctx.contract.clusters.distributions.get(ctx.cluster_id),
+        Some(RevenueDistribution {
+            phase: DistributionPhase::Weighing,
+            next_node_index: 2,
+            ..ctx
+                .contract
+                .clusters
+                .distributions
+                .get(ctx.cluster_id)
+                .unwrap()
+        })
+    );
+
+    // The nodes cannot change until they are all shared.
+    set_caller(ctx.manager_id);
+    assert_eq!(
+        ctx.contract
+            .cluster_remove_node(ctx.cluster_id, ctx.node_key2),
+        Err(ClusterNodesAreLocked)
+    );
+
+    // The next pages weigh the last node and share all the nodes.
+    ctx.contract
+        .cluster_distribute_revenues(ctx.cluster_id, 2)?;
+    ctx.contract
+        .cluster_distribute_revenues(ctx.cluster_id, 2)?;
+    assert_eq!(
+        ctx.contract
+            .clusters
+            .distributions
+            .get(ctx.cluster_id)
+            .map(|distribution| distribution.phase),
+        Some(DistributionPhase::Paying)
+    );
+    assert_eq!(ctx.contract.account_get_earnings(ctx.provider_id0), before0);
+
+    // The next page pays two providers.
+    assert_eq!(
+        ctx.contract.cluster_distribute_revenues(ctx.cluster_id, 2),
+        Ok(false)
+    );
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::ClusterDistributionProgress(ev) if ev ==
+        ClusterDistributionProgress {
+            cluster_id: ctx.cluster_id,
+            paid_providers: 2,
+            total_providers: 3
+        })
+    );
+    let earned0 = ctx.contract.account_get_earnings(ctx.provider_id0) - before0;
+    assert!(earned0 > 0);
+    assert_eq!(ctx.contract.account_get_earnings(ctx.provider_id2), before2);
+
+    // New revenues do not change the share of the current round.
+    advance_block::<DefaultEnvironment>();
+    ctx.contract.bucket_settle_payment(test_bucket.bucket_id)?;
+
+    assert_eq!(
+        ctx.contract.cluster_distribute_revenues(ctx.cluster_id, 2),
+        Ok(true)
+    );
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::ClusterDistributionCompleted(ev) if ev ==
+        ClusterDistributionCompleted { cluster_id: ctx.cluster_id })
+    );
+    assert_eq!(
+        ctx.contract.account_get_earnings(ctx.provider_id2) - before2,
+        earned0
+    );
+    assert!(ctx
+        .contract
+        .clusters
+        .payouts
+        .get((ctx.cluster_id, 0))
+        .is_none());
+
+    // The nodes can change again, and the new revenues are distributed in the next round.
+    let revenues = ctx.contract.cluster_get(ctx.cluster_id)?.cluster.revenues;
+    assert!(revenues.peek() > 0);
+    set_caller(ctx.manager_id);
+    ctx.contract
+        .cluster_remove_node(ctx.cluster_id, ctx.node_key2)?;
+    assert_eq!(
+        ctx.contract.cluster_distribute_revenues(ctx.cluster_id, 10),
+        Ok(true)
+    );
+    assert!(ctx.contract.account_get_earnings(ctx.provider_id0) - before0 > earned0);
+}
+
+#[ink::test]
+fn cluster_distribute_revenue_weighted_by_v_nodes_ok() {
+    let ctx = &mut setup_cluster();
+
+    // Create a cluster where the second node contributes 4 times more rent than the first.
+    let node_key_a = AccountId::from([0x1a; 32]);
+    let node_key_b = AccountId::from([0x1b; 32]);
+    set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+    ctx.contract.node_create(
+        node_key_a,
+        NodeParams::from("{\"url\":\"https://ddc-3.cere.network/storage/3\"}"),
+        100,
+        5 * TOKEN,
+    )?;
+    set_caller_value(ctx.provider_id1, CONTRACT_FEE_LIMIT);
+    ctx.contract.node_create(
+        node_key_b,
+        NodeParams::from("{\"url\":\"https://ddc-4.cere.network/storage/4\"}"),
+        100,
+        10 * TOKEN,
+    )?;
+
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    let cluster_id = ctx
+        .contract
+        .cluster_create(ClusterParams::from("{}"), ctx.resource_per_v_node)?;
+    ctx.contract
+        .cluster_add_node(cluster_id, node_key_a, vec![1, 2])?;
+    ctx.contract
+        .cluster_add_node(cluster_id, node_key_b, vec![3, 4, 5, 6])?;
+    ctx.contract
+        .cluster_set_node_status(cluster_id, node_key_a, NodeStatusInCluster::ACTIVE)?;
+
+    // Collect some revenues without fees.
+    set_caller(admin_id());
+    ctx.contract
+        .admin_set_network_fee_config(NetworkFeeConfig::default())?;
+
+    let owner_id = AccountId::from([0x5e; 32]);
+    set_balance(owner_id, 1000 * TOKEN);
+    set_caller_value(owner_id, CONTRACT_FEE_LIMIT);
+    let bucket_id = ctx
+        .contract
+        .bucket_create("{}".to_string(), cluster_id, None)?;
+    ctx.contract.bucket_alloc_into_cluster(bucket_id, 1)?;
+    set_caller_value(owner_id, 10 * TOKEN);
+    ctx.contract.account_deposit()?;
+    advance_block::<DefaultEnvironment>();
+    ctx.contract.bucket_settle_payment(bucket_id)?;
+
+    let revenues = ctx
+        .contract
+        .cluster_get(cluster_id)?
+        .cluster
+        .revenues
+        .peek();
+    let before_a = ctx.contract.account_get_earnings(ctx.provider_id0);
+    let before_b = ctx.contract.account_get_earnings(ctx.provider_id1);
+
+    ctx.contract.cluster_distribute_revenues(cluster_id, 10)?;
+
+    let earned_a = ctx.contract.account_get_earnings(ctx.provider_id0) - before_a;
+    let earned_b = ctx.contract.account_get_earnings(ctx.provider_id1) - before_b;
+    assert_eq!(earned_a, revenues * 10 / 50);
+    assert_eq!(earned_b, revenues * 40 / 50);
+
+    let remainder = ctx
+        .contract
+        .cluster_get(cluster_id)?
+        .cluster
+        .revenues
+        .peek();
+    assert_eq!(
+        earned_a + earned_b + remainder,
+        revenues,
+        "the remainder must be kept for the next round"
+    );
+}
+
+#[ink::test]
+fn cluster_distribute_revenue_aggregated_by_provider_ok() {
+    let ctx = &mut setup_cluster();
+
+    // The first provider runs a second node in the cluster.
+    let node_key3 = AccountId::from([0x1c; 32]);
+    set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+    ctx.contract.node_create(
+        node_key3,
+        NodeParams::from("{\"url\":\"https://ddc-3.cere.network/storage/3\"}"),
+        100,
+        10 * TOKEN,
+    )?;
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    ctx.contract
+        .cluster_add_node(ctx.cluster_id, node_key3, vec![10, 11, 12])?;
+
+    let test_bucket = &setup_bucket(ctx);
+    advance_block::<DefaultEnvironment>();
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    ctx.contract.bucket_settle_payment(test_bucket.bucket_id)?;
+
+    let before0 = ctx.contract.account_get_earnings(ctx.provider_id0);
+    let before1 = ctx.contract.account_get_earnings(ctx.provider_id1);
+
+    assert_eq!(
+        ctx.contract.cluster_distribute_revenues(ctx.cluster_id, 20),
+        Ok(true)
+    );
+
+    // The first provider is paid once for both of its nodes, which are reported with the payout.
+    let events: Vec<ClusterDistributeRevenues> = get_events()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::ClusterDistributeRevenues(ev) => Some(ev),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(events.len(), 3);
+
+    let earned0 = ctx.contract.account_get_earnings(ctx.provider_id0) - before0;
+    let earned1 = ctx.contract.account_get_earnings(ctx.provider_id1) - before1;
+    assert_eq!(
+        events[0],
+        ClusterDistributeRevenues {
+            cluster_id: ctx.cluster_id,
+            provider_id: ctx.provider_id0,
+            amount: earned0,
+            nodes_keys: vec![ctx.node_key0, node_key3],
+        }
+    );
+    assert_eq!(events[1].nodes_keys, vec![ctx.node_key1]);
+    assert_eq!(events[1].provider_id, ctx.provider_id1);
+    assert_eq!(events[1].amount, earned1);
+    assert_eq!(earned0, 2 * earned1);
+
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::ClusterDistributionCompleted(ev) if ev ==
+        ClusterDistributionCompleted { cluster_id: ctx.cluster_id })
+    );
+    assert!(
+        matches!(get_events::<Event>().into_iter().rev().nth(1).unwrap(), Event::ClusterDistributionProgress(ev) if ev ==
+        ClusterDistributionProgress {
+            cluster_id: ctx.cluster_id,
+            paid_providers: 3,
+            total_providers: 3
+        })
+    );
+}
+
+#[ink::test]
+fn cluster_rerate_buckets_ok() {
+    let ctx = &mut setup_cluster();
+    let bucket_id0 = setup_bucket(ctx).bucket_id;
+    let bucket_id1 = setup_bucket(ctx).bucket_id;
+
+    set_caller(admin_id());
+    ctx.contract.admin_set_bucket_rerate_notice_ms(0)?;
+
+    // The rent of the cluster increases with a new node.
+    let node_key3 = AccountId::from([0x1c; 32]);
+    set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+    ctx.contract.node_create(
+        node_key3,
+        NodeParams::from("{\"url\":\"https://ddc-3.cere.network/storage/3\"}"),
+        100,
+        10 * TOKEN,
+    )?;
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    ctx.contract
+        .cluster_add_node(ctx.cluster_id, node_key3, vec![10, 11, 12])?;
+    let new_rate = ctx.contract.cluster_get(ctx.cluster_id)?.cluster.total_rent;
+
+    // Announce the increase for the first page, then for the second page.
+    assert_eq!(
+        ctx.contract.cluster_rerate_buckets(ctx.cluster_id, 0, 1),
+        Ok(2)
+    );
+    assert!(ctx.contract.bucket_get_pending_rate(bucket_id0).is_some());
+    assert_eq!(ctx.contract.bucket_get_pending_rate(bucket_id1), None);
+    ctx.contract.cluster_rerate_buckets(ctx.cluster_id, 1, 1)?;
+
+    // Apply it to all buckets.
+    ctx.contract.cluster_rerate_buckets(ctx.cluster_id, 0, 2)?;
+    let rerated: Vec<BucketRerated> = get_events()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::BucketRerated(ev) => Some(ev),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        rerated,
+        vec![
+            BucketRerated {
+                bucket_id: bucket_id0,
+                rate: new_rate
+            },
+            BucketRerated {
+                bucket_id: bucket_id1,
+                rate: new_rate
+            },
+        ]
+    );
+    assert_eq!(ctx.contract.bucket_get_pending_rate(bucket_id0), None);
+    assert_eq!(ctx.contract.bucket_get_pending_rate(bucket_id1), None);
+}
+
+#[ink::test]
+fn cluster_rerate_buckets_reports_failures_ok() {
+    let ctx = &mut setup_cluster();
+    let bucket_id0 = setup_bucket(ctx).bucket_id;
+
+    // The payer of this bucket has no deposit.
+    let owner_id = AccountId::from([0x5e; 32]);
+    set_balance(owner_id, 1000 * TOKEN);
+    set_caller_value(owner_id, CONTRACT_FEE_LIMIT);
+    let bucket_id1 = ctx
+        .contract
+        .bucket_create("{}".to_string(), ctx.cluster_id, None)?;
+    ctx.contract.bucket_alloc_into_cluster(bucket_id1, 1)?;
+    let bucket_id2 = setup_bucket(ctx).bucket_id;
+    advance_block::<DefaultEnvironment>();
+
+    // The rent of the cluster decreases when a node leaves.
+    set_caller(ctx.manager_id);
+    ctx.contract
+        .cluster_remove_node(ctx.cluster_id, ctx.node_key2)?;
+
+    assert_eq!(
+        ctx.contract.cluster_rerate_buckets(ctx.cluster_id, 0, 10),
+        Ok(3)
+    );
+    let events = get_events::<Event>();
+    let rerated: Vec<BucketId> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::BucketRerated(ev) => Some(ev.bucket_id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(rerated, vec![bucket_id0, bucket_id2]);
+    let failed: Vec<BucketRerateFailed> = events
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::BucketRerateFailed(ev) => Some(ev),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        failed,
+        vec![BucketRerateFailed {
+            bucket_id: bucket_id1,
+            error: InsufficientBalance
+        }]
+    );
 }
 
 #[ink::test]
@@ -1672,19 +2191,38 @@ fn cluster_set_node_status_ok() {
 }
 
 #[ink::test]
-fn cluster_set_cdn_node_status_err_if_not_cluster_manager() {
+fn cluster_set_node_status_err_if_node_is_in_another_cluster() {
     let mut ctx = setup_cluster();
 
-    let not_manager_id = AccountId::from([
-        0xee, 0x0a, 0xc9, 0x58, 0xa2, 0x0d, 0xe8, 0xda, 0x73, 0xb2, 0x05, 0xe9, 0xc6, 0x34, 0xa6,
-        0xb2, 0x23, 0xcc, 0x54, 0x30, 0x24, 0x5d, 0x89, 0xb6, 0x4d, 0x83, 0x9b, 0x6d, 0xca, 0xc4,
-        0xf8, 0x6d,
-    ]);
-    set_balance(not_manager_id, 1000 * TOKEN);
+    set_caller(ctx.manager_id);
+    let cluster_id2 = ctx
+        .contract
+        .cluster_create(ClusterParams::from("{}"), ctx.resource_per_v_node)?;
 
-    set_caller(not_manager_id);
     assert_eq!(
-        ctx.contract.cluster_set_cdn_node_status(
+        ctx.contract.cluster_set_node_status(
+            cluster_id2,
+            ctx.node_key0,
+            NodeStatusInCluster::ACTIVE,
+        ),
+        Err(NodeIsNotAddedToCluster(cluster_id2))
+    );
+}
+
+#[ink::test]
+fn cluster_set_cdn_node_status_err_if_not_cluster_manager() {
+    let mut ctx = setup_cluster();
+
+    let not_manager_id = AccountId::from([
+        0xee, 0x0a, 0xc9, 0x58, 0xa2, 0x0d, 0xe8, 0xda, 0x73, 0xb2, 0x05, 0xe9, 0xc6, 0x34, 0xa6,
+        0xb2, 0x23, 0xcc, 0x54, 0x30, 0x24, 0x5d, 0x89, 0xb6, 0x4d, 0x83, 0x9b, 0x6d, 0xca, 0xc4,
+        0xf8, 0x6d,
+    ]);
+    set_balance(not_manager_id, 1000 * TOKEN);
+
+    set_caller(not_manager_id);
+    assert_eq!(
+        ctx.contract.cluster_set_cdn_node_status(
             ctx.cluster_id,
             ctx.cdn_node_key0,
             NodeStatusInCluster::ACTIVE
@@ -1756,7 +2294,6 @@ fn cluster_get_ok() {
                 cluster_id: ctx.cluster_id,
                 cluster: Cluster {
                     manager_id: ctx.manager_id,
-                    nodes_keys: ctx.nodes_keys,
                     resource_per_v_node: ctx.resource_per_v_node,
                     resource_used: 0,
                     cluster_params: ctx.cluster_params.clone(),
@@ -1766,35 +2303,28 @@ fn cluster_get_ok() {
                     cdn_usd_per_gb: CDN_USD_PER_GB,
                     cdn_revenues: Cash(0),
                 },
-                cluster_v_nodes,
+                nodes_len: 3,
             }
         })
     );
+
+    // The Storage nodes are listed with their vnodes in pages.
+    assert_eq!(
+        ctx.contract
+            .cluster_list_nodes_v_nodes(ctx.cluster_id, 0, 2),
+        (cluster_v_nodes[..2].to_vec(), 3)
+    );
+    assert_eq!(
+        ctx.contract
+            .cluster_list_nodes_v_nodes(ctx.cluster_id, 2, 2),
+        (cluster_v_nodes[2..].to_vec(), 3)
+    );
 }
 
 #[ink::test]
 fn cluster_list_ok() {
     let mut ctx = setup_cluster();
 
-    let mut cluster_v_nodes: Vec<NodeVNodesInfo> = Vec::new();
-    let node_v_nodes_0 = NodeVNodesInfo {
-        node_key: ctx.node_key0,
-        v_nodes: ctx.v_nodes0.clone(),
-    };
-    cluster_v_nodes.push(node_v_nodes_0);
-
-    let node_v_nodes_1 = NodeVNodesInfo {
-        node_key: ctx.node_key1,
-        v_nodes: ctx.v_nodes1.clone(),
-    };
-    cluster_v_nodes.push(node_v_nodes_1);
-
-    let node_v_nodes_2 = NodeVNodesInfo {
-        node_key: ctx.node_key2,
-        v_nodes: ctx.v_nodes2.clone(),
-    };
-    cluster_v_nodes.push(node_v_nodes_2);
-
     let total_rent = ctx.rent_v_node_per_month0 * ctx.v_nodes0.len() as Balance
         + ctx.rent_v_node_per_month1 * ctx.v_nodes1.len() as Balance
         + ctx.rent_v_node_per_month2 * ctx.v_nodes2.len() as Balance;
@@ -1803,7 +2333,6 @@ fn cluster_list_ok() {
         cluster_id: ctx.cluster_id,
         cluster: Cluster {
             manager_id: ctx.manager_id,
-            nodes_keys: ctx.nodes_keys,
             resource_per_v_node: ctx.resource_per_v_node,
             resource_used: 0,
             cluster_params: ctx.cluster_params.clone(),
@@ -1813,7 +2342,7 @@ fn cluster_list_ok() {
             cdn_usd_per_gb: CDN_USD_PER_GB,
             cdn_revenues: Cash(0),
         },
-        cluster_v_nodes,
+        nodes_len: 3,
     };
 
     let cluster_params2 = ClusterParams::from("{}");
@@ -1835,7 +2364,6 @@ fn cluster_list_ok() {
         cluster_id: cluster_id2,
         cluster: Cluster {
             manager_id: manager_id2,
-            nodes_keys: Vec::new(),
             resource_per_v_node: resource_per_v_node2,
             resource_used: 0,
             cluster_params: cluster_params2,
@@ -1845,7 +2373,7 @@ fn cluster_list_ok() {
             cdn_usd_per_gb: CDN_USD_PER_GB,
             cdn_revenues: Cash(0),
         },
-        cluster_v_nodes: Vec::new(),
+        nodes_len: 0,
     };
 
     let count = 2;
@@ -1987,6 +2515,7 @@ fn cluster_distribute_cdn_revenue_ok() {
     set_caller(admin_id());
     ctx.contract
         .admin_grant_permission(admin_id(), permission)?;
+    setup_validation_era(&mut ctx, 5);
 
     ctx.contract.cluster_put_cdn_revenue(
         ctx.cluster_id,
@@ -2036,3 +2565,945 @@ fn cluster_distribute_cdn_revenue_ok() {
     let account0_after_distributing = ctx.contract.accounts.get(&ctx.provider_id0).unwrap();
     println!("{:?}", account0_after_distributing);
 }
+
+#[ink::test]
+fn cluster_transfer_manager_ok() {
+    let ctx = &mut setup_cluster();
+    let new_manager_id = get_accounts().charlie;
+    let operator_id = get_accounts().django;
+
+    set_caller(ctx.manager_id);
+    ctx.contract.cluster_grant_operator_role(
+        ctx.cluster_id,
+        operator_id,
+        ClusterRole::SetNodeStatus,
+    )?;
+    ctx.contract
+        .cluster_transfer_manager(ctx.cluster_id, new_manager_id)?;
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::ClusterManagerTransferProposed(ev) if ev ==
+        ClusterManagerTransferProposed { cluster_id: ctx.cluster_id, manager_id: new_manager_id })
+    );
+
+    // The manager does not change until the new manager accepts.
+    assert_eq!(
+        ctx.contract.cluster_get(ctx.cluster_id)?.cluster.manager_id,
+        ctx.manager_id
+    );
+
+    set_caller(new_manager_id);
+    ctx.contract.cluster_accept_manager(ctx.cluster_id)?;
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::ClusterManagerTransferred(ev) if ev ==
+        ClusterManagerTransferred { cluster_id: ctx.cluster_id, manager_id: new_manager_id })
+    );
+    assert_eq!(
+        ctx.contract.cluster_get(ctx.cluster_id)?.cluster.manager_id,
+        new_manager_id
+    );
+
+    // The previous manager and its operators have no rights anymore.
+    for account_id in [ctx.manager_id, operator_id] {
+        set_caller(account_id);
+        assert_eq!(
+            ctx.contract.cluster_set_node_status(
+                ctx.cluster_id,
+                ctx.node_key0,
+                NodeStatusInCluster::ACTIVE
+            ),
+            Err(OnlyClusterManager)
+        );
+    }
+    assert_eq!(
+        ctx.contract
+            .cluster_get_operator_roles(ctx.cluster_id, operator_id),
+        vec![]
+    );
+    assert_eq!(
+        ctx.contract.cluster_list_operators(ctx.cluster_id, 0, 10),
+        (vec![], 0)
+    );
+}
+
+#[ink::test]
+fn cluster_transfer_manager_err_if_not_proposed() {
+    let ctx = &mut setup_cluster();
+    let new_manager_id = get_accounts().charlie;
+    let other_id = get_accounts().django;
+
+    set_caller(ctx.manager_id);
+    ctx.contract
+        .cluster_transfer_manager(ctx.cluster_id, new_manager_id)?;
+
+    set_caller(other_id);
+    assert_eq!(
+        ctx.contract.cluster_accept_manager(ctx.cluster_id),
+        Err(OnlyPendingClusterManager)
+    );
+    assert_eq!(
+        ctx.contract
+            .cluster_transfer_manager(ctx.cluster_id, other_id),
+        Err(OnlyClusterManager)
+    );
+
+    // A cancelled proposal cannot be accepted.
+    set_caller(ctx.manager_id);
+    ctx.contract
+        .cluster_cancel_manager_transfer(ctx.cluster_id)?;
+    set_caller(new_manager_id);
+    assert_eq!(
+        ctx.contract.cluster_accept_manager(ctx.cluster_id),
+        Err(OnlyPendingClusterManager)
+    );
+}
+
+#[ink::test]
+fn cluster_operator_roles_ok() {
+    let ctx = &mut setup_cluster();
+    let operator_id = get_accounts().charlie;
+
+    set_caller(ctx.manager_id);
+    ctx.contract.cluster_grant_operator_role(
+        ctx.cluster_id,
+        operator_id,
+        ClusterRole::SetNodeStatus,
+    )?;
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::ClusterOperatorRoleGranted(ev) if ev ==
+        ClusterOperatorRoleGranted {
+            cluster_id: ctx.cluster_id,
+            operator_id,
+            role: ClusterRole::SetNodeStatus
+        })
+    );
+    // Granting twice has no effect.
+    ctx.contract.cluster_grant_operator_role(
+        ctx.cluster_id,
+        operator_id,
+        ClusterRole::SetNodeStatus,
+    )?;
+    assert_eq!(
+        ctx.contract
+            .cluster_get_operator_roles(ctx.cluster_id, operator_id),
+        vec![ClusterRole::SetNodeStatus]
+    );
+    assert_eq!(
+        ctx.contract.cluster_list_operators(ctx.cluster_id, 0, 10),
+        (vec![operator_id], 1)
+    );
+
+    // The operator acts within the scope of its role only.
+    set_caller(operator_id);
+    ctx.contract.cluster_set_node_status(
+        ctx.cluster_id,
+        ctx.node_key0,
+        NodeStatusInCluster::ACTIVE,
+    )?;
+    ctx.contract.cluster_set_cdn_node_status(
+        ctx.cluster_id,
+        ctx.cdn_node_key0,
+        NodeStatusInCluster::ACTIVE,
+    )?;
+    assert_eq!(
+        ctx.contract.cdn_set_rate(ctx.cluster_id, 1),
+        Err(OnlyClusterManager)
+    );
+    assert_eq!(
+        ctx.contract.cluster_remove(ctx.cluster_id),
+        Err(OnlyClusterManager)
+    );
+    assert_eq!(
+        ctx.contract.cluster_grant_operator_role(
+            ctx.cluster_id,
+            operator_id,
+            ClusterRole::SetCdnRate
+        ),
+        Err(OnlyClusterManager)
+    );
+
+    // Revoked roles are not effective anymore.
+    set_caller(ctx.manager_id);
+    ctx.contract.cluster_revoke_operator_role(
+        ctx.cluster_id,
+        operator_id,
+        ClusterRole::SetNodeStatus,
+    )?;
+    assert_eq!(
+        ctx.contract
+            .cluster_get_operator_roles(ctx.cluster_id, operator_id),
+        vec![]
+    );
+    set_caller(operator_id);
+    assert_eq!(
+        ctx.contract.cluster_set_node_status(
+            ctx.cluster_id,
+            ctx.node_key0,
+            NodeStatusInCluster::OFFLINE
+        ),
+        Err(OnlyClusterManager)
+    );
+    assert_eq!(
+        ctx.contract.cluster_list_operators(ctx.cluster_id, 0, 10),
+        (vec![], 0)
+    );
+}
+
+#[ink::test]
+fn cluster_grant_operator_role_err_if_too_many_operators() {
+    let ctx = &mut setup_cluster();
+
+    set_caller(ctx.manager_id);
+    for i in 0..MAX_CLUSTER_OPERATORS_LEN {
+        ctx.contract.cluster_grant_operator_role(
+            ctx.cluster_id,
+            AccountId::from([i as u8 + 1; 32]),
+            ClusterRole::SetNodeStatus,
+        )?;
+    }
+    assert_eq!(
+        ctx.contract.cluster_grant_operator_role(
+            ctx.cluster_id,
+            AccountId::from([0xff; 32]),
+            ClusterRole::SetNodeStatus,
+        ),
+        Err(OperatorsSizeExceedsLimit)
+    );
+
+    // An existing operator can still be granted more roles.
+    ctx.contract.cluster_grant_operator_role(
+        ctx.cluster_id,
+        AccountId::from([1; 32]),
+        ClusterRole::SetCdnRate,
+    )?;
+}
+
+#[ink::test]
+fn cluster_add_node_auto_ok() {
+    let ctx = &mut setup_cluster();
+
+    let node_key3 = AccountId::from([0x1c; 32]);
+    set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+    ctx.contract.node_create(
+        node_key3,
+        NodeParams::from("{\"url\":\"https://ddc-3.cere.network/storage/3\"}"),
+        100,
+        10 * TOKEN,
+    )?;
+
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    let v_nodes = ctx
+        .contract
+        .cluster_add_node_auto(ctx.cluster_id, node_key3, 4)?;
+
+    assert_eq!(v_nodes.len(), 4);
+    assert!(
+        matches!(get_events().pop().unwrap(), Event::ClusterNodeAdded(ev) if ev ==
+        ClusterNodeAdded {
+            cluster_id: ctx.cluster_id,
+            node_key: node_key3,
+            v_nodes: v_nodes.clone()
+        })
+    );
+    assert_eq!(
+        ctx.contract
+            .get_v_nodes_by_node(ctx.cluster_id, node_key3)?,
+        v_nodes
+    );
+
+    // The tokens are deterministic.
+    assert_eq!(
+        ctx.contract
+            .cluster_reset_node_auto(ctx.cluster_id, node_key3, 4)?,
+        v_nodes
+    );
+
+    // A lower count keeps the first tokens.
+    let v_nodes_2 = ctx
+        .contract
+        .cluster_reset_node_auto(ctx.cluster_id, node_key3, 2)?;
+    assert_eq!(v_nodes_2, v_nodes[..2].to_vec());
+    let v_nodes = v_nodes_2;
+    assert_eq!(
+        ctx.contract
+            .get_v_nodes_by_node(ctx.cluster_id, node_key3)?,
+        v_nodes
+    );
+
+    assert_eq!(
+        ctx.contract
+            .cluster_reset_node_auto(ctx.cluster_id, node_key3, 0),
+        Err(AtLeastOneVNodeHasToBeAssigned(ctx.cluster_id, node_key3))
+    );
+}
+
+#[ink::test]
+fn cluster_add_node_auto_skips_taken_tokens_ok() {
+    let ctx = &mut setup_cluster();
+
+    let node_key3 = AccountId::from([0x1c; 32]);
+    let node_key4 = AccountId::from([0x1d; 32]);
+    set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+    for node_key in [node_key3, node_key4] {
+        ctx.contract.node_create(
+            node_key,
+            NodeParams::from("{\"url\":\"https://ddc-3.cere.network/storage/3\"}"),
+            100,
+            10 * TOKEN,
+        )?;
+    }
+
+    // Another node takes the first token that would be generated.
+    let expected = ctx
+        .contract
+        .topology
+        .generate_v_nodes(ctx.cluster_id, node_key4, 2)?;
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    ctx.contract
+        .cluster_add_node(ctx.cluster_id, node_key3, vec![expected[0]])?;
+
+    let v_nodes = ctx
+        .contract
+        .cluster_add_node_auto(ctx.cluster_id, node_key4, 2)?;
+    assert_eq!(v_nodes, vec![expected[0].wrapping_add(1), expected[1]]);
+}
+
+#[ink::test]
+fn cluster_add_node_auto_balanced_ok() {
+    let ctx = &mut setup_cluster();
+    let v_nodes_count = 64;
+
+    set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+    let cluster_id = ctx.contract.cluster_create(ClusterParams::from("{}"), 1)?;
+
+    let nodes_keys: Vec<NodeKey> = (0..4).map(|i| AccountId::from([0x30 + i; 32])).collect();
+    for node_key in &nodes_keys {
+        set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+        ctx.contract.node_create(
+            *node_key,
+            NodeParams::from("{\"url\":\"https://ddc-3.cere.network/storage/3\"}"),
+            v_nodes_count as Resource,
+            10 * TOKEN,
+        )?;
+        set_caller_value(ctx.manager_id, CONTRACT_FEE_LIMIT);
+        ctx.contract
+            .cluster_add_node_auto(cluster_id, *node_key, v_nodes_count)?;
+    }
+
+    // A token owns the keys from the previous token of the ring, excluded, up to itself.
+    let mut ring = ctx.contract.topology.get_v_nodes_by_cluster(cluster_id);
+    ring.sort_unstable();
+    assert_eq!(ring.len(), nodes_keys.len() * v_nodes_count as usize);
+    let mut shares = vec![0u128; nodes_keys.len()];
+    for (i, v_node) in ring.iter().enumerate() {
+        let previous = ring[(i + ring.len() - 1) % ring.len()];
+        let node_key = ctx
+            .contract
+            .topology
+            .get_node_by_v_node(cluster_id, *v_node)?;
+        let node_index = nodes_keys.iter().position(|key| *key == node_key).unwrap();
+        shares[node_index] += v_node.wrapping_sub(previous) as u128;
+    }
+
+    // Each node owns about an even share of the ring.
+    let even_share = (1u128 << 64) / nodes_keys.len() as u128;
+    for share in shares {
+        assert!(
+            share > even_share * 3 / 4 && share < even_share * 5 / 4,
+            "unbalanced share {} of {}",
+            share,
+            even_share
+        );
+    }
+}
+
+#[ink::test]
+fn cluster_resolve_key_ok() {
+    let ctx = &mut setup_cluster();
+
+    // Nodes that are not active are not selected.
+    assert_eq!(
+        ctx.contract.cluster_resolve_key(ctx.cluster_id, 5, 2)?,
+        vec![]
+    );
+
+    set_caller(ctx.manager_id);
+    for node_key in [ctx.node_key0, ctx.node_key1, ctx.node_key2] {
+        ctx.contract.cluster_set_node_status(
+            ctx.cluster_id,
+            node_key,
+            NodeStatusInCluster::ACTIVE,
+        )?;
+    }
+
+    // Walk the ring from the key, skipping other vnodes of selected nodes.
+    assert_eq!(
+        ctx.contract.cluster_resolve_key(ctx.cluster_id, 5, 2)?,
+        vec![(5, ctx.node_key1), (7, ctx.node_key2)]
+    );
+    // Wrap around the ring.
+    assert_eq!(
+        ctx.contract.cluster_resolve_key(ctx.cluster_id, 8, 3)?,
+        vec![(8, ctx.node_key2), (1, ctx.node_key0), (4, ctx.node_key1)]
+    );
+    assert_eq!(
+        ctx.contract.cluster_resolve_key(ctx.cluster_id, 10, 1)?,
+        vec![(1, ctx.node_key0)]
+    );
+
+    // Skip nodes that are not active.
+    ctx.contract.cluster_set_node_status(
+        ctx.cluster_id,
+        ctx.node_key1,
+        NodeStatusInCluster::OFFLINE,
+    )?;
+    assert_eq!(
+        ctx.contract.cluster_resolve_key(ctx.cluster_id, 5, 2)?,
+        vec![(7, ctx.node_key2), (1, ctx.node_key0)]
+    );
+
+    // There are not enough active nodes.
+    assert_eq!(
+        ctx.contract.cluster_resolve_key(ctx.cluster_id, 5, 5)?,
+        vec![(7, ctx.node_key2), (1, ctx.node_key0)]
+    );
+
+    assert_eq!(
+        ctx.contract.cluster_resolve_key(ctx.cluster_id, 5, 0),
+        Err(InvalidReplicationFactor)
+    );
+}
+
+#[ink::test]
+fn cluster_topology_beyond_vec_limit_ok() {
+    let mut ctx = setup_cluster();
+
+    let provider_id = AccountId::from([
+        0x3c, 0x1f, 0x8e, 0x5a, 0x42, 0x9b, 0x07, 0xd4, 0x6e, 0x21, 0xa3, 0x58, 0xf0, 0x7c, 0x14,
+        0xb9, 0x2d, 0x85, 0x61, 0xce, 0x03, 0x9f, 0x4a, 0xe7, 0x36, 0xbb, 0x12, 0x70, 0xd8, 0x5c,
+        0x29, 0x94,
+    ]);
+    let node_key_a = AccountId::from([
+        0x51, 0xa8, 0x0c, 0x7d, 0xe3, 0x26, 0x94, 0x1b, 0xf5, 0x38, 0x6a, 0xc2, 0x0e, 0x87, 0x4d,
+        0xb1, 0x79, 0x13, 0xec, 0x5f, 0x22, 0x96, 0x0a, 0xd7, 0x64, 0x3e, 0xb8, 0x01, 0xcf, 0x45,
+        0x9a, 0x6d,
+    ]);
+    let node_key_b = AccountId::from([
+        0x8d, 0x34, 0xf1, 0x62, 0x0b, 0xa7, 0x5e, 0xc9, 0x17, 0x83, 0x2c, 0xd0, 0x6f, 0x45, 0xba,
+        0x08, 0xe4, 0x3a, 0x91, 0x76, 0x1d, 0xcb, 0x58, 0x2f, 0xa0, 0x6b, 0x04, 0xde, 0x39, 0x87,
+        0x12, 0xf6,
+    ]);
+    let v_nodes_a: Vec<VNodeToken> = (1000..2000).collect();
+    let v_nodes_b: Vec<VNodeToken> = (5000..6000).collect();
+
+    set_caller_value(provider_id, CONTRACT_FEE_LIMIT);
+    for node_key in [node_key_a, node_key_b] {
+        ctx.contract.node_create(
+            node_key,
+            NodeParams::from("large_node"),
+            ctx.resource_per_v_node * 1000,
+            1,
+        )?;
+    }
+    ctx.contract
+        .grant_trusted_manager_permission(ctx.manager_id)?;
+
+    set_caller(ctx.manager_id);
+    ctx.contract
+        .cluster_add_node(ctx.cluster_id, node_key_a, v_nodes_a.clone())?;
+    ctx.contract
+        .cluster_add_node(ctx.cluster_id, node_key_b, v_nodes_b.clone())?;
+
+    // The ring holds more virtual nodes than a single packed vector could.
+    let mut expected_v_nodes = [
+        ctx.v_nodes0.clone(),
+        ctx.v_nodes1.clone(),
+        ctx.v_nodes2.clone(),
+        v_nodes_a.clone(),
+        v_nodes_b.clone(),
+    ]
+    .concat();
+    let total = expected_v_nodes.len() as u32;
+
+    let mut v_nodes = Vec::new();
+    let mut offset = 0;
+    loop {
+        let (page, page_total) = ctx
+            .contract
+            .list_v_nodes_by_cluster(ctx.cluster_id, offset, 700);
+        assert_eq!(page_total, total);
+        if page.is_empty() {
+            break;
+        }
+        offset += page.len() as u32;
+        v_nodes.extend(page);
+    }
+    assert_eq!(v_nodes, expected_v_nodes);
+    assert_eq!(
+        ctx.contract.get_v_nodes_by_cluster(ctx.cluster_id),
+        expected_v_nodes
+    );
+    assert_eq!(
+        ctx.contract.cluster_list_nodes(ctx.cluster_id, 0, 10),
+        (
+            [ctx.nodes_keys.clone(), vec![node_key_a, node_key_b]].concat(),
+            5
+        )
+    );
+
+    // Remove a node from the middle of the ring, the others keep their virtual nodes.
+    ctx.contract
+        .cluster_remove_node(ctx.cluster_id, node_key_a)?;
+
+    expected_v_nodes = [
+        ctx.v_nodes0.clone(),
+        ctx.v_nodes1.clone(),
+        ctx.v_nodes2.clone(),
+        v_nodes_b.clone(),
+    ]
+    .concat();
+    let mut v_nodes = ctx.contract.get_v_nodes_by_cluster(ctx.cluster_id);
+    v_nodes.sort_unstable();
+    assert_eq!(v_nodes, expected_v_nodes);
+    assert_eq!(
+        ctx.contract
+            .list_v_nodes_by_cluster(ctx.cluster_id, 0, 10)
+            .1,
+        expected_v_nodes.len() as u32
+    );
+    assert_eq!(
+        ctx.contract.cluster_list_nodes(ctx.cluster_id, 0, 10),
+        ([ctx.nodes_keys.clone(), vec![node_key_b]].concat(), 4)
+    );
+    assert_eq!(
+        ctx.contract.get_node_by_v_node(ctx.cluster_id, 1000),
+        Err(VNodeIsNotAssignedToNode(ctx.cluster_id, 1000))
+    );
+    assert_eq!(
+        ctx.contract.get_node_by_v_node(ctx.cluster_id, 5000),
+        Ok(node_key_b)
+    );
+
+    // The node can join again after being removed.
+    ctx.contract
+        .cluster_add_node(ctx.cluster_id, node_key_a, v_nodes_a.clone())?;
+    assert_eq!(
+        ctx.contract
+            .list_v_nodes_by_cluster(ctx.cluster_id, 0, 10)
+            .1,
+        total
+    );
+}
+
+#[ink::test]
+fn cluster_topology_changes_ok() {
+    let mut ctx = setup_cluster();
+
+    // Each node added by the setup is a version.
+    assert_eq!(ctx.contract.get_topology_version(ctx.cluster_id), Ok(3));
+    assert_eq!(
+        ctx.contract.get_topology_changes(ctx.cluster_id, 0, 0, 100),
+        Ok(TopologyChanges {
+            version: 3,
+            v_nodes: vec![1, 2, 3, 4, 5, 6, 7, 8, 9],
+            total: 9,
+        })
+    );
+    assert_eq!(
+        ctx.contract.get_topology_changes(ctx.cluster_id, 3, 0, 100),
+        Ok(TopologyChanges {
+            version: 3,
+            v_nodes: vec![],
+            total: 0,
+        })
+    );
+
+    set_caller(ctx.manager_id);
+    ctx.contract
+        .cluster_replace_node(ctx.cluster_id, vec![1, 2], ctx.node_key1)?;
+    // Only the vnodes that left or joined the node changed.
+    ctx.contract
+        .cluster_reset_node(ctx.cluster_id, ctx.node_key2, vec![7, 10])?;
+
+    assert_eq!(ctx.contract.get_topology_version(ctx.cluster_id), Ok(5));
+    assert_eq!(
+        ctx.contract.get_topology_changes(ctx.cluster_id, 3, 0, 100),
+        Ok(TopologyChanges {
+            version: 5,
+            v_nodes: vec![1, 2, 8, 9, 10],
+            total: 5,
+        })
+    );
+    assert_eq!(
+        ctx.contract.get_topology_changes(ctx.cluster_id, 3, 1, 2),
+        Ok(TopologyChanges {
+            version: 5,
+            v_nodes: vec![2, 8],
+            total: 5,
+        })
+    );
+    assert_eq!(
+        ctx.contract.get_topology_changes(ctx.cluster_id, 4, 0, 100),
+        Ok(TopologyChanges {
+            version: 5,
+            v_nodes: vec![8, 9, 10],
+            total: 3,
+        })
+    );
+
+    ctx.contract
+        .cluster_remove_node(ctx.cluster_id, ctx.node_key0)?;
+    assert_eq!(
+        ctx.contract.get_topology_changes(ctx.cluster_id, 5, 0, 100),
+        Ok(TopologyChanges {
+            version: 6,
+            v_nodes: vec![3],
+            total: 1,
+        })
+    );
+
+    assert_eq!(
+        ctx.contract.get_topology_version(ctx.cluster_id + 1),
+        Err(ClusterDoesNotExist)
+    );
+}
+
+#[ink::test]
+fn cluster_topology_changes_too_old_err() {
+    let mut ctx = setup_cluster();
+
+    let node_key = AccountId::from([
+        0x6a, 0x0f, 0xd2, 0x39, 0x84, 0x5b, 0xe1, 0x17, 0xc6, 0x4e, 0x93, 0x28, 0xfb, 0x70, 0x0d,
+        0xa5, 0x3c, 0x81, 0x5e, 0xb4, 0x26, 0x9d, 0x07, 0xe8, 0x52, 0x1a, 0xcf, 0x63, 0x95, 0x3b,
+        0xd0, 0x4c,
+    ]);
+    let v_nodes: Vec<VNodeToken> = (1000..2000).collect();
+
+    set_caller_value(ctx.provider_id0, CONTRACT_FEE_LIMIT);
+    ctx.contract.node_create(
+        node_key,
+        NodeParams::from("large_node"),
+        ctx.resource_per_v_node * 1000,
+        1,
+    )?;
+
+    // Add and remove the node until the oldest changes are dropped.
+    set_caller(ctx.manager_id);
+    for _ in 0..4 {
+        ctx.contract
+            .cluster_add_node(ctx.cluster_id, node_key, v_nodes.clone())?;
+        ctx.contract.cluster_remove_node(ctx.cluster_id, node_key)?;
+    }
+    assert_eq!(ctx.contract.get_topology_version(ctx.cluster_id), Ok(11));
+
+    assert_eq!(
+        ctx.contract.get_topology_changes(ctx.cluster_id, 0, 0, 100),
+        Err(TopologyVersionIsTooOld)
+    );
+    assert_eq!(
+        ctx.contract.get_topology_changes(ctx.cluster_id, 3, 0, 100),
+        Err(TopologyVersionIsTooOld)
+    );
+
+    let changes = ctx
+        .contract
+        .get_topology_changes(ctx.cluster_id, 4, 1000, 1000)?;
+    assert_eq!(changes.version, 11);
+    assert_eq!(changes.total, 7000);
+    assert_eq!(changes.v_nodes, v_nodes);
+}
+
+#[ink::test]
+fn cluster_put_cdn_revenue_quorum_ok() {
+    let mut ctx = setup_cluster();
+    let validator0 = get_accounts().bob;
+    let validator1 = get_accounts().charlie;
+    let validator2 = get_accounts().django;
+
+    set_caller_value(ctx.provider_id0, 10 * TOKEN);
+    ctx.contract.account_deposit()?;
+    set_caller_value(ctx.provider_id0, 10 * TOKEN);
+    ctx.contract.account_bond(5 * TOKEN)?;
+
+    set_caller(admin_id());
+    ctx.contract.admin_set_cdn_report_quorum(2)?;
+    for validator_id in [validator0, validator1, validator2] {
+        ctx.contract
+            .admin_grant_permission(validator_id, Permission::Validator)?;
+    }
+
+    let era = 1;
+    setup_validation_era(&mut ctx, era);
+    let aggregates_accounts = vec![(ctx.provider_id0, 1000)];
+    let aggregates_nodes = vec![(ctx.cdn_node_key0, 1000)];
+    let report_hash = CdnReport::hash_aggregates(
+        ctx.cluster_id,
+        era,
+        &aggregates_accounts,
+        &aggregates_nodes,
+        &[],
+    );
+    let undistributed_payment = |ctx: &TestCluster| {
+        ctx.contract
+            .cdn_nodes
+            .get(ctx.cdn_node_key0)
+            .unwrap()
+            .undistributed_payment
+    };
+
+    // A single validator is not enough.
+    set_caller(validator0);
+    ctx.contract.cluster_put_cdn_revenue(
+        ctx.cluster_id,
+        aggregates_accounts.clone(),
+        aggregates_nodes.clone(),
+        vec![],
+        era,
+    )?;
+    assert_eq!(undistributed_payment(&ctx), 0);
+
+    // Another validator disagrees.
+    let other_hash = Hash::from([0x01; 32]);
+    set_caller(validator1);
+    ctx.contract
+        .cluster_submit_cdn_report(ctx.cluster_id, era, other_hash)?;
+
+    // The quorum agrees, the usage is charged with the aggregates.
+    set_caller(validator2);
+    ctx.contract
+        .cluster_submit_cdn_report(ctx.cluster_id, era, report_hash)?;
+    assert!(matches!(
+        get_events().pop().unwrap(), Event::CdnReportAgreed(ev) if ev ==
+        CdnReportAgreed { cluster_id: ctx.cluster_id, era, report_hash }
+    ));
+    assert_eq!(undistributed_payment(&ctx), 0);
+
+    ctx.contract.cluster_put_cdn_revenue(
+        ctx.cluster_id,
+        aggregates_accounts.clone(),
+        aggregates_nodes.clone(),
+        vec![],
+        era,
+    )?;
+    assert!(matches!(
+        get_events().pop().unwrap(), Event::CdnReportApplied(ev) if ev ==
+        CdnReportApplied { cluster_id: ctx.cluster_id, era, report_hash }
+    ));
+    let payment = undistributed_payment(&ctx);
+    assert!(payment > 0);
+
+    assert_eq!(
+        ctx.contract.cluster_get_cdn_report(ctx.cluster_id, era),
+        Some(CdnReport {
+            votes: vec![
+                (validator0, report_hash),
+                (validator1, other_hash),
+                (validator2, report_hash)
+            ],
+            agreed_hash: Some(report_hash),
+            applied: true,
+            dissenters: vec![validator1],
+        })
+    );
+
+    // The agreed usage is charged only once.
+    set_caller(validator0);
+    ctx.contract.cluster_put_cdn_revenue(
+        ctx.cluster_id,
+        aggregates_accounts,
+        aggregates_nodes,
+        vec![],
+        era,
+    )?;
+    assert_eq!(undistributed_payment(&ctx), payment);
+
+    // A late dissenting vote is listed for review.
+    set_caller(validator2);
+    ctx.contract
+        .cluster_submit_cdn_report(ctx.cluster_id, era, other_hash)?;
+    assert_eq!(undistributed_payment(&ctx), payment);
+    assert_eq!(
+        ctx.contract
+            .cluster_get_cdn_report(ctx.cluster_id, era)
+            .unwrap()
+            .dissenters,
+        vec![validator1, validator2]
+    );
+}
+
+// Set eras that are always in their validation phase, and wait for the given era.
+fn setup_validation_era(ctx: &mut TestCluster, era: u64) {
+    set_caller(admin_id());
+    ctx.contract
+        .set_era(EraConfig {
+            start: block_timestamp::<DefaultEnvironment>(),
+            interval: 10,
+            commit_duration: 0,
+            validation_duration: 10,
+        })
+        .unwrap();
+    while ctx.contract.get_era().current_era < era {
+        advance_block::<DefaultEnvironment>();
+    }
+}
+
+#[ink::test]
+fn cluster_put_cdn_revenue_err_if_era_invalid() {
+    let mut ctx = setup_cluster();
+    let validator_id = get_accounts().bob;
+    set_caller(admin_id());
+    ctx.contract
+        .admin_grant_permission(validator_id, Permission::Validator)?;
+
+    // Eras are not set.
+    set_caller(validator_id);
+    assert_eq!(
+        ctx.contract
+            .cluster_put_cdn_revenue(ctx.cluster_id, vec![], vec![], vec![], 0),
+        Err(EraIsNotInValidationOrPayoutPhase)
+    );
+
+    // The era is in its commit phase.
+    set_caller(admin_id());
+    ctx.contract.set_era(EraConfig {
+        start: block_timestamp::<DefaultEnvironment>(),
+        interval: 10,
+        commit_duration: 10,
+        validation_duration: 0,
+    })?;
+    set_caller(validator_id);
+    assert_eq!(
+        ctx.contract
+            .cluster_put_cdn_revenue(ctx.cluster_id, vec![], vec![], vec![], 0),
+        Err(EraIsNotInValidationOrPayoutPhase)
+    );
+
+    setup_validation_era(&mut ctx, 2);
+    set_caller(validator_id);
+    assert_eq!(
+        ctx.contract
+            .cluster_put_cdn_revenue(ctx.cluster_id, vec![], vec![], vec![], 3),
+        Err(EraIsInTheFuture)
+    );
+    assert_eq!(
+        ctx.contract
+            .cluster_submit_cdn_report(ctx.cluster_id, 3, Hash::from([0x01; 32])),
+        Err(EraIsInTheFuture)
+    );
+
+    ctx.contract
+        .cluster_put_cdn_revenue(ctx.cluster_id, vec![], vec![], vec![], 2)?;
+    assert_eq!(
+        ctx.contract
+            .cluster_put_cdn_revenue(ctx.cluster_id, vec![], vec![], vec![], 1),
+        Err(EraIsOlderThanLastSettled)
+    );
+
+    // Votes on the settled era are recorded, but not on the skipped eras.
+    ctx.contract
+        .cluster_submit_cdn_report(ctx.cluster_id, 2, Hash::from([0x01; 32]))?;
+    assert_eq!(
+        ctx.contract
+            .cluster_submit_cdn_report(ctx.cluster_id, 1, Hash::from([0x01; 32])),
+        Err(EraIsOlderThanLastSettled)
+    );
+}
+
+#[ink::test]
+fn cluster_submit_cdn_report_err_if_not_validator() {
+    let mut ctx = setup_cluster();
+
+    set_caller(ctx.provider_id0);
+    assert_eq!(
+        ctx.contract
+            .cluster_submit_cdn_report(ctx.cluster_id, 1, Hash::from([0x01; 32])),
+        Err(OnlyValidator)
+    );
+
+    set_caller(admin_id());
+    assert_eq!(
+        ctx.contract.admin_set_cdn_report_quorum(0),
+        Err(InvalidCdnReportQuorum)
+    );
+}
+
+#[ink::test]
+fn cluster_put_cdn_revenue_settlement_ok() {
+    let mut ctx = setup_cluster();
+    let validator_id = get_accounts().bob;
+
+    set_caller_value(ctx.provider_id0, 10 * TOKEN);
+    ctx.contract.account_deposit()?;
+    set_caller_value(ctx.provider_id0, 10 * TOKEN);
+    ctx.contract.account_bond(5 * TOKEN)?;
+    let account_before = ctx.contract.accounts.get(&ctx.provider_id0)?;
+
+    set_caller(admin_id());
+    ctx.contract.admin_set_protocol_fee_bp(1_000)?;
+    ctx.contract
+        .admin_grant_permission(validator_id, Permission::Validator)?;
+    setup_validation_era(&mut ctx, 1);
+
+    // The nodes would be paid more than the accounts are charged.
+    set_caller(validator_id);
+    assert_eq!(
+        ctx.contract.cluster_put_cdn_revenue(
+            ctx.cluster_id,
+            vec![(ctx.provider_id0, 1000)],
+            vec![(ctx.cdn_node_key0, 1000), (ctx.cdn_node_key1, 1000)],
+            vec![],
+            1,
+        ),
+        Err(UnbalancedCdnReport)
+    );
+
+    // The buckets do not break down the usage of the accounts.
+    assert_eq!(
+        ctx.contract.cluster_put_cdn_revenue(
+            ctx.cluster_id,
+            vec![(ctx.provider_id0, 1000)],
+            vec![(ctx.cdn_node_key0, 1000)],
+            vec![(1, 500)],
+            1,
+        ),
+        Err(UnbalancedCdnReport)
+    );
+    assert_eq!(
+        ctx.contract.accounts.get(&ctx.provider_id0)?,
+        account_before
+    );
+
+    ctx.contract.cluster_put_cdn_revenue(
+        ctx.cluster_id,
+        vec![(ctx.provider_id0, 400), (ctx.provider_id0, 600)],
+        vec![(ctx.cdn_node_key0, 1000)],
+        vec![],
+        1,
+    )?;
+
+    let rate = ctx.contract.cdn_get_rate(ctx.cluster_id)?;
+    let conv = &ctx.contract.protocol.curr_converter;
+    let accounts_payment =
+        conv.to_cere(400 * rate / KB_PER_GB) + conv.to_cere(600 * rate / KB_PER_GB);
+    let payment = conv.to_cere(1000 * rate / KB_PER_GB);
+    let nodes_payment = payment * 9_000 / 10_000;
+
+    let mut events = get_events::<Event>();
+    events.pop(); // CdnReportApplied
+    assert!(matches!(
+        events.pop().unwrap(), Event::CdnRevenueSettled(ev) if ev ==
+        CdnRevenueSettled {
+            cluster_id: ctx.cluster_id,
+            era: 1,
+            accounts_payment,
+            nodes_payment,
+            protocol_fee: payment - nodes_payment,
+        }
+    ));
+    assert_eq!(
+        ctx.contract
+            .cdn_nodes
+            .get(ctx.cdn_node_key0)?
+            .undistributed_payment,
+        nodes_payment
+    );
+}
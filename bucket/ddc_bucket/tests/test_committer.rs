@@ -0,0 +1,216 @@
+use ink_env::{block_timestamp, test::advance_block, DefaultEnvironment};
+use ink_lang as ink;
+
+use crate::ddc_bucket::Error::*;
+use crate::ddc_bucket::*;
+
+use super::env_utils::*;
+use super::setup_utils::*;
+
+const COMMIT_DURATION_MS: u64 = 60;
+
+fn setup_era(ctx: &mut TestCluster) -> EraConfig {
+    let era_config = EraConfig {
+        start: block_timestamp::<DefaultEnvironment>(),
+        interval: 1000,
+        commit_duration: COMMIT_DURATION_MS,
+        validation_duration: 100,
+    };
+    set_caller(admin_id());
+    ctx.contract.set_era(era_config).unwrap();
+    era_config
+}
+
+fn new_commit() -> Commit {
+    Commit {
+        hash: Hash::from([0x11; 32]),
+        total_logs: 100,
+        from_timestamp: 0,
+        to_timestamp: 10,
+    }
+}
+
+#[ink::test]
+fn set_commit_ok() {
+    let mut ctx = setup_cluster();
+    setup_era(&mut ctx);
+
+    set_caller(ctx.provider_id0);
+    ctx.contract
+        .set_commit(ctx.provider_id0, ctx.cdn_node_key0, new_commit())?;
+
+    assert_eq!(
+        ctx.contract.get_commit(ctx.provider_id0),
+        vec![(ctx.cdn_node_key0, new_commit())]
+    );
+}
+
+#[ink::test]
+fn set_commit_err_if_not_provider() {
+    let mut ctx = setup_cluster();
+    setup_era(&mut ctx);
+
+    // The caller does not provide the CDN node.
+    set_caller(ctx.provider_id1);
+    assert_eq!(
+        ctx.contract
+            .set_commit(ctx.provider_id0, ctx.cdn_node_key0, new_commit()),
+        Err(OnlyCdnNodeProvider)
+    );
+    assert_eq!(
+        ctx.contract
+            .set_commit(ctx.provider_id1, ctx.cdn_node_key0, new_commit()),
+        Err(OnlyCdnNodeProvider)
+    );
+
+    // The commit would be stored for another account.
+    set_caller(ctx.provider_id0);
+    assert_eq!(
+        ctx.contract
+            .set_commit(ctx.provider_id1, ctx.cdn_node_key0, new_commit()),
+        Err(OnlyCdnNodeProvider)
+    );
+
+    assert_eq!(ctx.contract.get_commit(ctx.provider_id1), vec![]);
+}
+
+#[ink::test]
+fn set_commit_err_if_node_not_in_cluster() {
+    let mut ctx = setup_cluster();
+    setup_era(&mut ctx);
+
+    let cdn_node_key = AccountId::from([0x4d; 32]);
+    set_caller(ctx.provider_id0);
+    ctx.contract
+        .cdn_node_create(cdn_node_key, CdnNodeParams::from("{}"))?;
+
+    assert_eq!(
+        ctx.contract
+            .set_commit(ctx.provider_id0, cdn_node_key, new_commit()),
+        Err(CdnNodeIsNotAddedToAnyCluster)
+    );
+    assert_eq!(
+        ctx.contract
+            .set_commit(ctx.provider_id0, AccountId::from([0x4e; 32]), new_commit()),
+        Err(CdnNodeDoesNotExist)
+    );
+}
+
+#[ink::test]
+fn set_commit_err_if_not_commit_phase() {
+    let mut ctx = setup_cluster();
+
+    // Eras are not set.
+    set_caller(ctx.provider_id0);
+    assert_eq!(
+        ctx.contract
+            .set_commit(ctx.provider_id0, ctx.cdn_node_key0, new_commit()),
+        Err(EraIsNotInCommitPhase)
+    );
+
+    let era_config = setup_era(&mut ctx);
+    while block_timestamp::<DefaultEnvironment>() < era_config.start + COMMIT_DURATION_MS {
+        advance_block::<DefaultEnvironment>();
+    }
+
+    // The era is in its validation phase.
+    set_caller(ctx.provider_id0);
+    assert_eq!(
+        ctx.contract
+            .set_commit(ctx.provider_id0, ctx.cdn_node_key0, new_commit()),
+        Err(EraIsNotInCommitPhase)
+    );
+}
+
+fn advance_era(ctx: &mut TestCluster) {
+    let era = ctx.contract.get_era().current_era;
+    while ctx.contract.get_era().current_era == era {
+        advance_block::<DefaultEnvironment>();
+    }
+}
+
+#[ink::test]
+fn commit_history_ok() {
+    let mut ctx = setup_cluster();
+
+    // Every era is in its commit phase.
+    set_caller(admin_id());
+    ctx.contract.set_era(EraConfig {
+        start: block_timestamp::<DefaultEnvironment>(),
+        interval: 12,
+        commit_duration: 12,
+        validation_duration: 0,
+    })?;
+
+    let commit0 = new_commit();
+    let commit1 = Commit {
+        total_logs: 200,
+        ..new_commit()
+    };
+    let commit2 = Commit {
+        total_logs: 300,
+        ..new_commit()
+    };
+
+    set_caller(ctx.provider_id0);
+    ctx.contract
+        .set_commit(ctx.provider_id0, ctx.cdn_node_key0, commit0)?;
+    set_caller(ctx.provider_id1);
+    ctx.contract
+        .set_commit(ctx.provider_id1, ctx.cdn_node_key1, commit1)?;
+
+    advance_era(&mut ctx);
+    set_caller(ctx.provider_id0);
+    ctx.contract
+        .set_commit(ctx.provider_id0, ctx.cdn_node_key0, commit2)?;
+
+    assert_eq!(
+        ctx.contract.get_commits_for_era(0, 0, 10),
+        (
+            vec![(ctx.cdn_node_key0, commit0), (ctx.cdn_node_key1, commit1)],
+            2
+        )
+    );
+    assert_eq!(
+        ctx.contract.get_commits_for_era(0, 1, 10),
+        (vec![(ctx.cdn_node_key1, commit1)], 2)
+    );
+    assert_eq!(
+        ctx.contract.get_commits_for_era(1, 0, 10),
+        (vec![(ctx.cdn_node_key0, commit2)], 1)
+    );
+    assert_eq!(
+        ctx.contract.get_commit_history(ctx.cdn_node_key0, 0, 10),
+        vec![(0, commit0), (1, commit2)]
+    );
+    assert_eq!(
+        ctx.contract.get_commit_history(ctx.cdn_node_key0, 1, 1),
+        vec![(1, commit2)]
+    );
+    // The latest commit of the owner is still available.
+    assert_eq!(
+        ctx.contract.get_commit(ctx.provider_id0),
+        vec![(ctx.cdn_node_key0, commit2)]
+    );
+
+    // The next commit out of the retention window prunes the old eras.
+    while ctx.contract.get_era().current_era < COMMIT_HISTORY_ERAS + 1 {
+        advance_era(&mut ctx);
+    }
+    set_caller(ctx.provider_id1);
+    ctx.contract
+        .set_commit(ctx.provider_id1, ctx.cdn_node_key1, commit1)?;
+
+    assert_eq!(ctx.contract.get_commits_for_era(0, 0, 10), (vec![], 0));
+    assert_eq!(ctx.contract.get_commits_for_era(1, 0, 10), (vec![], 0));
+    assert_eq!(
+        ctx.contract
+            .get_commit_history(ctx.cdn_node_key0, 0, COMMIT_HISTORY_ERAS + 1),
+        vec![]
+    );
+    assert_eq!(
+        ctx.contract
+            .get_commit_history(ctx.cdn_node_key1, 0, COMMIT_HISTORY_ERAS + 1),
+        vec![(COMMIT_HISTORY_ERAS + 1, commit1)]
+    );
+}
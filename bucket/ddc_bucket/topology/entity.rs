@@ -0,0 +1,19 @@
+//! The data structures of the cluster topology.
+use ink_prelude::vec::Vec;
+use scale::{Decode, Encode};
+
+use super::store::VNodeToken;
+
+pub type TopologyVersion = u64;
+
+#[derive(Clone, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+pub struct TopologyChanges {
+    /// The current version of the topology.
+    pub version: TopologyVersion,
+    /// A page of the virtual nodes assigned, reassigned or unassigned since the requested version, oldest first.
+    /// A virtual node appears once per version that changed it.
+    pub v_nodes: Vec<VNodeToken>,
+    /// The number of changes since the requested version.
+    pub total: u32,
+}
@@ -1,6 +1,10 @@
 //! The public interface to manage Nodes.
 
-use crate::ddc_bucket::{ClusterId, DdcBucket, Error::*, NodeKey, Result, VNodeToken};
+use crate::ddc_bucket::chunked::CHUNK_LEN;
+use crate::ddc_bucket::{
+    ClusterId, DdcBucket, Error::*, NodeKey, NodeStatusInCluster, Result, TopologyChanges,
+    TopologyVersion, VNodeToken,
+};
 use ink_prelude::vec::Vec;
 
 impl DdcBucket {
@@ -8,6 +12,16 @@ impl DdcBucket {
         self.topology.get_v_nodes_by_cluster(cluster_id)
     }
 
+    pub fn message_list_v_nodes_by_cluster(
+        &self,
+        cluster_id: ClusterId,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<VNodeToken>, u32) {
+        self.topology
+            .list_v_nodes_by_cluster(cluster_id, offset, limit)
+    }
+
     pub fn message_get_v_nodes_by_node(
         &self,
         cluster_id: ClusterId,
@@ -29,4 +43,72 @@ impl DdcBucket {
     ) -> Result<NodeKey> {
         self.topology.get_node_by_v_node(cluster_id, v_node)
     }
+
+    pub fn message_get_topology_version(&self, cluster_id: ClusterId) -> Result<TopologyVersion> {
+        self.clusters.get(cluster_id)?;
+        Ok(self.topology.get_version(cluster_id))
+    }
+
+    pub fn message_get_topology_changes(
+        &self,
+        cluster_id: ClusterId,
+        since_version: TopologyVersion,
+        offset: u32,
+        limit: u32,
+    ) -> Result<TopologyChanges> {
+        self.clusters.get(cluster_id)?;
+        self.topology
+            .get_changes(cluster_id, since_version, offset, limit)
+    }
+
+    pub fn message_cluster_resolve_key(
+        &self,
+        cluster_id: ClusterId,
+        key_hash: VNodeToken,
+        replication_factor: u32,
+    ) -> Result<Vec<(VNodeToken, NodeKey)>> {
+        self.clusters.get(cluster_id)?;
+        if replication_factor == 0 {
+            return Err(InvalidReplicationFactor);
+        }
+
+        // The first token of each node clockwise from the key is the one the walk of the ring would reach.
+        // The nodes are read page by page, keeping only the closest ones, instead of loading the whole ring.
+        let mut replicas: Vec<(VNodeToken, NodeKey)> = Vec::new();
+        let mut offset = 0;
+        loop {
+            let (nodes_keys, total) = self.clusters.list_nodes_keys(cluster_id, offset, CHUNK_LEN);
+            for node_key in nodes_keys {
+                let is_active = self
+                    .nodes
+                    .get(node_key)
+                    .map(|node| node.status_in_cluster == Some(NodeStatusInCluster::ACTIVE))
+                    .unwrap_or(false);
+                if !is_active {
+                    continue;
+                }
+
+                let closest = self
+                    .topology
+                    .get_v_nodes_by_node(node_key)
+                    .into_iter()
+                    .min_by_key(|v_node| v_node.wrapping_sub(key_hash));
+                if let Some(v_node) = closest {
+                    let distance = v_node.wrapping_sub(key_hash);
+                    let pos = replicas
+                        .partition_point(|(other, _)| other.wrapping_sub(key_hash) <= distance);
+                    if pos < replication_factor as usize {
+                        replicas.insert(pos, (v_node, node_key));
+                        replicas.truncate(replication_factor as usize);
+                    }
+                }
+            }
+
+            offset += CHUNK_LEN;
+            if offset >= total {
+                break;
+            }
+        }
+        Ok(replicas)
+    }
 }
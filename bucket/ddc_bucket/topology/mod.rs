@@ -1,4 +1,5 @@
 //! cluster topology management.
 
+pub mod entity;
 pub mod messages;
 pub mod store;
@@ -1,8 +1,11 @@
 //! The store where to create and access Nodes.
+use ink_env::hash::{Blake2x256, HashOutput};
 use ink_prelude::vec::Vec;
 use ink_storage::traits::{SpreadAllocate, SpreadLayout};
 use ink_storage::Mapping;
 
+use super::entity::{TopologyChanges, TopologyVersion};
+use crate::ddc_bucket::chunked::ChunkedLists;
 use crate::ddc_bucket::cluster::entity::ClusterId;
 use crate::ddc_bucket::node::entity::NodeKey;
 use crate::ddc_bucket::{Error::*, Result};
@@ -12,8 +15,12 @@ pub type ClusterVNode = (ClusterId, VNodeToken);
 
 // https://use.ink/datastructures/storage-layout#packed-vs-non-packed-layout
 // There is a buffer with only limited capacity (around 16KB in the default configuration) available.
+// This limits the virtual nodes of a physical node, the virtual nodes of a cluster are stored in chunks.
 pub const MAX_V_NODES_LEN_IN_VEC: usize = 1800;
 
+// The number of changes kept per cluster, enough for a few resets of the largest node.
+pub const MAX_TOPOLOGY_CHANGES_LEN: u64 = 4 * MAX_V_NODES_LEN_IN_VEC as u64;
+
 #[derive(SpreadAllocate, SpreadLayout, Default)]
 #[cfg_attr(feature = "std", derive(ink_storage::traits::StorageLayout, Debug))]
 pub struct TopologyStore {
@@ -22,14 +29,106 @@ pub struct TopologyStore {
     // physical node assigned to a virtual node
     nodes_map: Mapping<ClusterVNode, NodeKey>,
     // virtual nodes within a cluster assigned to all its physical nodes
-    cluster_v_nodes_map: Mapping<ClusterId, Vec<VNodeToken>>,
+    cluster_v_nodes: ChunkedLists<ClusterId, VNodeToken>,
+    // current version of the topology of a cluster
+    versions: Mapping<ClusterId, TopologyVersion>,
+    // number of changes ever recorded in a cluster
+    changes_len: Mapping<ClusterId, u64>,
+    // virtual nodes that changed, by sequence number of the change; only the latest changes are kept
+    changes: Mapping<(ClusterId, u64), VNodeToken>,
+    // number of changes recorded when a version was reached
+    version_ends: Mapping<(ClusterId, TopologyVersion), u64>,
 }
 
 impl TopologyStore {
     pub fn get_v_nodes_by_cluster(&self, cluster_id: ClusterId) -> Vec<VNodeToken> {
-        self.cluster_v_nodes_map
-            .get(cluster_id)
-            .unwrap_or(Vec::new())
+        self.cluster_v_nodes.get_all(cluster_id)
+    }
+
+    pub fn list_v_nodes_by_cluster(
+        &self,
+        cluster_id: ClusterId,
+        offset: u32,
+        limit: u32,
+    ) -> (Vec<VNodeToken>, u32) {
+        self.cluster_v_nodes.get_page(cluster_id, offset, limit)
+    }
+
+    pub fn count_v_nodes_by_cluster(&self, cluster_id: ClusterId) -> u32 {
+        self.cluster_v_nodes.len(cluster_id)
+    }
+
+    pub fn get_version(&self, cluster_id: ClusterId) -> TopologyVersion {
+        self.versions.get(cluster_id).unwrap_or(0)
+    }
+
+    /// Return a page of the virtual nodes changed after `since_version`.
+    ///
+    /// The pages of a version stay the same when later versions add changes, which are appended.
+    pub fn get_changes(
+        &self,
+        cluster_id: ClusterId,
+        since_version: TopologyVersion,
+        offset: u32,
+        limit: u32,
+    ) -> Result<TopologyChanges> {
+        let version = self.get_version(cluster_id);
+        let changes_len = self.changes_len.get(cluster_id).unwrap_or(0);
+
+        let start = if since_version >= version {
+            changes_len
+        } else if since_version == 0 {
+            0
+        } else {
+            self.version_ends
+                .get((cluster_id, since_version))
+                .ok_or(TopologyVersionIsTooOld)?
+        };
+        if start + MAX_TOPOLOGY_CHANGES_LEN < changes_len {
+            return Err(TopologyVersionIsTooOld);
+        }
+
+        let total = (changes_len - start) as u32;
+        let mut v_nodes = Vec::new();
+        for seq in start + offset as u64..changes_len.min(start + offset as u64 + limit as u64) {
+            if let Some(v_node) = self.changes.get((cluster_id, seq)) {
+                v_nodes.push(v_node);
+            }
+        }
+
+        Ok(TopologyChanges {
+            version,
+            v_nodes,
+            total,
+        })
+    }
+
+    /// Bump the version of the topology and record the virtual nodes that changed.
+    fn record_changes(&mut self, cluster_id: ClusterId, v_nodes: &[VNodeToken]) {
+        if v_nodes.is_empty() {
+            return;
+        }
+        let version = self.get_version(cluster_id) + 1;
+        let mut changes_len = self.changes_len.get(cluster_id).unwrap_or(0);
+
+        for v_node in v_nodes {
+            self.changes.insert((cluster_id, changes_len), v_node);
+            if changes_len >= MAX_TOPOLOGY_CHANGES_LEN {
+                self.changes
+                    .remove((cluster_id, changes_len - MAX_TOPOLOGY_CHANGES_LEN));
+            }
+            changes_len += 1;
+        }
+
+        // Every version changes at least one virtual node, so older versions have no changes left.
+        if version > MAX_TOPOLOGY_CHANGES_LEN {
+            self.version_ends
+                .remove((cluster_id, version - MAX_TOPOLOGY_CHANGES_LEN - 1));
+        }
+        self.version_ends
+            .insert((cluster_id, version), &changes_len);
+        self.changes_len.insert(cluster_id, &changes_len);
+        self.versions.insert(cluster_id, &version);
     }
 
     pub fn get_v_nodes_by_node(&self, node_key: NodeKey) -> Vec<VNodeToken> {
@@ -46,13 +145,46 @@ impl TopologyStore {
         self.nodes_map.contains((cluster_id, v_node))
     }
 
+    /// Generate `count` tokens for a node, each derived independently from the cluster, the node and its index.
+    ///
+    /// The tokens of all nodes are spread uniformly over the ring, so the share of each node follows its number
+    /// of tokens. A token that is taken is moved to the next free position.
+    pub fn generate_v_nodes(
+        &self,
+        cluster_id: ClusterId,
+        node_key: NodeKey,
+        count: u32,
+    ) -> Result<Vec<VNodeToken>> {
+        if count == 0 {
+            return Err(AtLeastOneVNodeHasToBeAssigned(cluster_id, node_key));
+        }
+        if count as usize > MAX_V_NODES_LEN_IN_VEC {
+            return Err(VNodesSizeExceedsLimit);
+        }
+
+        let mut v_nodes = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let mut hash = <Blake2x256 as HashOutput>::Type::default();
+            ink_env::hash_encoded::<Blake2x256, _>(&(cluster_id, node_key, i), &mut hash);
+            let mut token_bytes = [0u8; 8];
+            token_bytes.copy_from_slice(&hash[..8]);
+
+            let mut v_node = VNodeToken::from_le_bytes(token_bytes);
+            while v_nodes.contains(&v_node)
+                || matches!(self.nodes_map.get((cluster_id, v_node)), Some(other) if other != node_key)
+            {
+                v_node = v_node.wrapping_add(1);
+            }
+            v_nodes.push(v_node);
+        }
+        Ok(v_nodes)
+    }
+
     pub fn create_topology(&mut self, cluster_id: ClusterId) -> Result<()> {
-        if self.cluster_v_nodes_map.contains(&cluster_id) {
+        if self.cluster_v_nodes.exists(cluster_id) {
             Err(TopologyAlreadyExists)
         } else {
-            let cluster_v_nodes: Vec<VNodeToken> = Vec::new();
-            self.cluster_v_nodes_map
-                .insert(cluster_id, &cluster_v_nodes);
+            self.cluster_v_nodes.create(cluster_id);
             Ok(())
         }
     }
@@ -62,6 +194,23 @@ impl TopologyStore {
         cluster_id: ClusterId,
         node_key: NodeKey,
         v_nodes: Vec<VNodeToken>,
+    ) -> Result<()> {
+        self.assign_node(cluster_id, node_key, v_nodes.clone())?;
+        self.record_changes(cluster_id, &v_nodes);
+        Ok(())
+    }
+
+    pub fn remove_node(&mut self, cluster_id: ClusterId, node_key: NodeKey) -> Result<()> {
+        let v_nodes = self.unassign_node(cluster_id, node_key)?;
+        self.record_changes(cluster_id, &v_nodes);
+        Ok(())
+    }
+
+    fn assign_node(
+        &mut self,
+        cluster_id: ClusterId,
+        node_key: NodeKey,
+        v_nodes: Vec<VNodeToken>,
     ) -> Result<()> {
         if v_nodes.is_empty() {
             return Err(AtLeastOneVNodeHasToBeAssigned(cluster_id, node_key));
@@ -71,12 +220,6 @@ impl TopologyStore {
             return Err(VNodesSizeExceedsLimit);
         }
 
-        let mut cluster_v_nodes = self.get_v_nodes_by_cluster(cluster_id);
-
-        if cluster_v_nodes.len() + v_nodes.len() > MAX_V_NODES_LEN_IN_VEC {
-            return Err(VNodesSizeExceedsLimit);
-        }
-
         for v_node in &v_nodes {
             // vnode that is being added should not exist in the cluster topology
             if self.v_node_has_node(cluster_id, *v_node) {
@@ -87,20 +230,20 @@ impl TopologyStore {
             self.nodes_map.insert((cluster_id, v_node), &node_key);
 
             // vnode that is being added should be added to the cluster topology
-            cluster_v_nodes.push(*v_node);
+            self.cluster_v_nodes.push(cluster_id, *v_node);
         }
 
-        self.cluster_v_nodes_map
-            .insert(cluster_id, &cluster_v_nodes);
-
         // vnodes that are being added should be assigned to the physical node
         self.v_nodes_map.insert(node_key, &v_nodes);
 
         Ok(())
     }
 
-    pub fn remove_node(&mut self, cluster_id: ClusterId, node_key: NodeKey) -> Result<()> {
-        let mut cluster_v_nodes = self.get_v_nodes_by_cluster(cluster_id);
+    fn unassign_node(
+        &mut self,
+        cluster_id: ClusterId,
+        node_key: NodeKey,
+    ) -> Result<Vec<VNodeToken>> {
         let v_nodes = self.get_v_nodes_by_node(node_key);
 
         for v_node in &v_nodes {
@@ -113,18 +256,13 @@ impl TopologyStore {
             self.nodes_map.remove((cluster_id, v_node));
 
             // vnode that is being removed should be removed from the cluster topology
-            if let Some(pos) = cluster_v_nodes.iter().position(|x| *x == *v_node) {
-                cluster_v_nodes.remove(pos);
-            };
+            self.cluster_v_nodes.remove(cluster_id, *v_node);
         }
 
-        self.cluster_v_nodes_map
-            .insert(cluster_id, &cluster_v_nodes);
-
         // vnodes that are being removed should be unusigned from the physical node
         self.v_nodes_map.remove(node_key);
 
-        Ok(())
+        Ok(v_nodes)
     }
 
     pub fn replace_node(
@@ -141,11 +279,9 @@ impl TopologyStore {
             return Err(VNodesSizeExceedsLimit);
         }
 
-        let cluster_v_nodes = self.get_v_nodes_by_cluster(cluster_id);
-
         for v_node in &v_nodes_to_reasign {
             // vnode that is being reasigned should be in the cluster topology
-            if None == cluster_v_nodes.iter().position(|x| *x == *v_node) {
+            if !self.cluster_v_nodes.contains(cluster_id, *v_node) {
                 return Err(VNodeDoesNotExistsInCluster(cluster_id));
             };
 
@@ -177,6 +313,8 @@ impl TopologyStore {
             return Err(VNodesSizeExceedsLimit);
         }
 
+        self.record_changes(cluster_id, &v_nodes_to_reasign);
+
         new_node_v_nodes.extend(v_nodes_to_reasign);
         self.v_nodes_map.insert(new_node_key, &new_node_v_nodes);
 
@@ -189,8 +327,24 @@ impl TopologyStore {
         node_key: NodeKey,
         new_v_nodes: Vec<VNodeToken>,
     ) -> Result<()> {
-        self.remove_node(cluster_id, node_key)?;
-        self.add_node(cluster_id, node_key, new_v_nodes)
+        let mut v_nodes = self.unassign_node(cluster_id, node_key)?;
+        self.assign_node(cluster_id, node_key, new_v_nodes.clone())?;
+
+        // A vnode that the node keeps appears in both lists, and did not change.
+        v_nodes.extend(new_v_nodes);
+        v_nodes.sort_unstable();
+        let mut changed_v_nodes = Vec::with_capacity(v_nodes.len());
+        let mut i = 0;
+        while i < v_nodes.len() {
+            if i + 1 < v_nodes.len() && v_nodes[i] == v_nodes[i + 1] {
+                i += 2;
+            } else {
+                changed_v_nodes.push(v_nodes[i]);
+                i += 1;
+            }
+        }
+        self.record_changes(cluster_id, &changed_v_nodes);
+        Ok(())
     }
 
     pub fn remove_topology(&mut self, cluster_id: ClusterId) -> Result<()> {
@@ -202,7 +356,12 @@ impl TopologyStore {
             self.v_nodes_map.remove(node_key);
         }
 
-        self.cluster_v_nodes_map.remove(cluster_id);
+        self.cluster_v_nodes.clear(cluster_id);
+
+        // The changes of a removed cluster are left unreachable, as cluster IDs are not reused.
+        // Removing them would take up to `MAX_TOPOLOGY_CHANGES_LEN` writes in a single call.
+        self.versions.remove(cluster_id);
+        self.changes_len.remove(cluster_id);
 
         Ok(())
     }
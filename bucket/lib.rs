@@ -19,7 +19,7 @@ pub mod ddc_bucket {
     use ink_storage::traits::SpreadAllocate;
     use node::{entity::*, store::*};
     use perm::store::*;
-    use topology::store::*;
+    use topology::{entity::*, store::*};
 
     use crate::ddc_bucket::cdn_node::store::CdnNodeStore;
     use crate::ddc_bucket::perm::entity::Permission;
@@ -34,6 +34,7 @@ pub mod ddc_bucket {
     pub mod bucket;
     pub mod cash;
     pub mod cdn_node;
+    pub mod chunked;
     pub mod cluster;
     pub mod committer;
     pub mod currency;
@@ -79,6 +80,15 @@ pub mod ddc_bucket {
                     admin,
                     DEFAULT_CLUSTER_FEE_BP,
                 );
+                contract
+                    .protocol
+                    .set_bucket_grace_period_ms(DEFAULT_BUCKET_GRACE_PERIOD_MS);
+                contract
+                    .protocol
+                    .set_bucket_rerate_notice_ms(DEFAULT_BUCKET_RERATE_NOTICE_MS);
+                contract
+                    .protocol
+                    .set_cdn_report_quorum(DEFAULT_CDN_REPORT_QUORUM);
             })
         }
     }
@@ -107,6 +117,70 @@ pub mod ddc_bucket {
         resource: Resource,
     }
 
+    /// Some amount of resources of a bucket were released back to its cluster.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketReleased {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        resource: Resource,
+    }
+
+    /// A bucket was moved to another cluster. Nodes of the source cluster should hand its data over to the target cluster.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketMigrated {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        #[ink(topic)]
+        source_cluster_id: ClusterId,
+        #[ink(topic)]
+        target_cluster_id: ClusterId,
+        resource: Resource,
+    }
+
+    /// An account was proposed as the new owner of a bucket. It becomes the owner once it accepts.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketOwnershipTransferProposed {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        #[ink(topic)]
+        owner_id: AccountId,
+    }
+
+    /// The ownership of a bucket was transferred to a new owner.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketOwnershipTransferred {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        #[ink(topic)]
+        owner_id: AccountId,
+    }
+
+    /// An account was proposed to pay for a bucket. It becomes the payer once it accepts.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketPayerProposed {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        #[ink(topic)]
+        payer_id: AccountId,
+    }
+
+    /// The account paying for a bucket has changed.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketPayerSet {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        #[ink(topic)]
+        payer_id: AccountId,
+    }
+
     /// The due costs of a bucket was settled from the bucket payer to the cluster.
     #[ink(event)]
     #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
@@ -135,12 +209,62 @@ pub mod ddc_bucket {
         bucket_params: BucketParams,
     }
 
+    /// The state of a bucket changed because its rent became covered or uncovered.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketStateChanged {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        state: BucketState,
+    }
+
+    /// An increase of the rate of a bucket was announced, following an increase of the rent of its cluster.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketRerateScheduled {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        rate: Balance,
+        effective_at_ms: u64,
+    }
+
+    /// The payment flow of a bucket was settled and restarted at a new rate.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketRerated {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        rate: Balance,
+    }
+
+    /// The rate of a bucket could not be brought to the rent of its cluster, because its payment flow could not be settled.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketRerateFailed {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        error: Error,
+    }
+
+    /// A bucket was removed. Its payment flow was settled and its resources were released to the cluster.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct BucketRemoved {
+        #[ink(topic)]
+        bucket_id: BucketId,
+        #[ink(topic)]
+        cluster_id: ClusterId,
+    }
+
     impl DdcBucket {
         /// Create a new bucket and return its `bucket_id`.
         ///
         /// The caller will be its first owner and payer of resources.
         ///
-        /// `bucket_params` is configuration used by clients and nodes. See the [data structure of BucketParams](https://docs.cere.network/ddc/protocols/contract-params-schema)
+        /// `bucket_params` is configuration used by clients and nodes, as a JSON object. See the [data structure of BucketParams](https://docs.cere.network/ddc/protocols/contract-params-schema)
+        /// Its `replicationFactor`, 1 if not given, must be between 1 and the number of active nodes of the cluster,
+        /// not counting nodes being added, deleted or offline. Otherwise `InvalidReplicationFactor` is returned.
+        /// Params that are neither empty nor a JSON object give `InvalidBucketParams`.
         ///
         /// The bucket can be connected to a single cluster (currently). Allocate cluster resources with the function `bucket_alloc_into_cluster`
         #[ink(message, payable)]
@@ -153,21 +277,145 @@ pub mod ddc_bucket {
             self.message_bucket_create(bucket_params, cluster_id, owner_id)
         }
 
-        /// Change owner of the bucket
+        /// Propose a new owner for a bucket.
+        ///
+        /// The ownership is transferred once the new owner accepts it with `bucket_accept_ownership`.
+        /// A previous proposal is replaced.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the bucket.
+        /// * `owner_id` - Account of the new owner.
+        /// * `keep_payer` - Whether the current payer keeps paying for the bucket, otherwise the new owner becomes the payer.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `BucketOwnershipTransferProposed` event on successful proposal.
         ///
-        /// Provide the account of new owner
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `OnlyOwner` error if the caller is not the bucket owner.
         #[ink(message, payable)]
-        pub fn bucket_change_owner(
+        pub fn bucket_transfer_ownership(
             &mut self,
             bucket_id: BucketId,
             owner_id: AccountId,
+            keep_payer: bool,
         ) -> Result<()> {
-            self.message_bucket_change_owner(bucket_id, owner_id)
+            self.message_bucket_transfer_ownership(bucket_id, owner_id, keep_payer)
+        }
+
+        /// Cancel a pending ownership transfer of a bucket.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the bucket.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `OnlyOwner` error if the caller is not the bucket owner.
+        #[ink(message)]
+        pub fn bucket_cancel_ownership_transfer(&mut self, bucket_id: BucketId) -> Result<()> {
+            self.message_bucket_cancel_ownership_transfer(bucket_id)
+        }
+
+        /// Accept the ownership of a bucket.
+        ///
+        /// An account is created for the new owner if needed.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the bucket.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `BucketOwnershipTransferred` event on successful transfer.
+        /// * `BucketPayerSet` event if the new owner becomes the payer.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `OnlyPendingOwner` error if the caller was not proposed as the owner of the bucket.
+        /// * `InsufficientBalance` error if the previous payer account cannot cover the due costs.
+        #[ink(message)]
+        pub fn bucket_accept_ownership(&mut self, bucket_id: BucketId) -> Result<()> {
+            self.message_bucket_accept_ownership(bucket_id)
+        }
+
+        /// Set the account that pays for a bucket.
+        ///
+        /// The owner pays for its bucket by default. The owner or the current payer can make the owner the payer again at any time.
+        /// Any other account is only proposed as the payer, and has to accept with `bucket_accept_payer`.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the bucket.
+        /// * `payer_id` - Account that should pay for the bucket.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `BucketPayerProposed` event if the payer has to accept the bucket.
+        /// * `BucketPayerSet` event if the owner becomes the payer.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `OnlyOwner` error if the caller is not the bucket owner.
+        /// * `OnlyOwnerOrPayer` error if the owner is made the payer by a caller that is neither the bucket owner nor its payer.
+        /// * `InsufficientBalance` error if the previous payer account cannot cover the due costs.
+        #[ink(message)]
+        pub fn bucket_set_payer(&mut self, bucket_id: BucketId, payer_id: AccountId) -> Result<()> {
+            self.message_bucket_set_payer(bucket_id, payer_id)
+        }
+
+        /// Accept to pay for a bucket.
+        ///
+        /// The due costs are settled from the previous payer, then the payment flow of the bucket is moved to the caller.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the bucket.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `BucketPayerSet` event on successful payer change.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `OnlyPendingPayer` error if the caller was not proposed as the payer of the bucket.
+        /// * `InsufficientBalance` error if the previous payer account cannot cover the due costs.
+        #[ink(message)]
+        pub fn bucket_accept_payer(&mut self, bucket_id: BucketId) -> Result<()> {
+            self.message_bucket_accept_payer(bucket_id)
         }
 
         /// Allocate some resources of a cluster to a bucket.
         ///
         /// The amount of resources is given per vnode (total resources will be `resource` times the number of vnodes).
+        /// A suspended bucket cannot allocate resources until its payer covers it again.
         #[ink(message)]
         pub fn bucket_alloc_into_cluster(
             &mut self,
@@ -177,15 +425,116 @@ pub mod ddc_bucket {
             self.message_bucket_alloc_into_cluster(bucket_id, resource)
         }
 
+        /// Release some resources of a bucket back to its cluster.
+        ///
+        /// The due costs are settled up to now, and the rate of the payment flow is reduced
+        /// in proportion to the released part of the reservation.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the targeting bucket.
+        /// * `resource` - Amount of resources to release, given per vnode like in `bucket_alloc_into_cluster`.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `BucketReleased` event on successful resource release.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `ClusterDoesNotExist` error if the cluster of the bucket does not exist.
+        /// * `OnlyOwner` error if the caller is neither the bucket owner nor the cluster manager.
+        /// * `InsufficientBucketResources` error if the bucket has less resources reserved than requested.
+        /// * `BucketIsSuspended` error if the bucket is suspended.
+        /// * `InsufficientBalance` error if the payer account cannot cover the due costs.
+        #[ink(message)]
+        pub fn bucket_release_from_cluster(
+            &mut self,
+            bucket_id: BucketId,
+            resource: Resource,
+        ) -> Result<()> {
+            self.message_bucket_release_from_cluster(bucket_id, resource)
+        }
+
+        /// Migrates a bucket to another cluster.
+        ///
+        /// The due costs are settled to the source cluster and its resources are released there.
+        /// The same amount of resources is reserved in the target cluster, and the payment flow restarts at the rent of the target cluster.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the migrating bucket.
+        /// * `target_cluster_id` - ID of the cluster to move the bucket into.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `BucketMigrated` event on successful bucket migration.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `OnlyOwner` error if the caller is not the bucket owner.
+        /// * `BucketIsAlreadyInCluster` error if the bucket is already in the target cluster.
+        /// * `BucketIsSuspended` error if the bucket is suspended.
+        /// * `ClusterDoesNotExist` error if the source or target cluster does not exist.
+        /// * `InsufficientClusterResources` error if there is not enough resources in the target cluster.
+        /// * `InvalidReplicationFactor` error if the target cluster has fewer active nodes than the replication factor of the bucket.
+        /// * `InvalidBucketParams` error if the params of the bucket are neither empty nor a JSON object.
+        /// * `InsufficientBalance` error if the payer account cannot cover the due costs.
+        #[ink(message)]
+        pub fn bucket_migrate(
+            &mut self,
+            bucket_id: BucketId,
+            target_cluster_id: ClusterId,
+        ) -> Result<()> {
+            self.message_bucket_migrate(bucket_id, target_cluster_id)
+        }
+
         /// Settle the due costs of a bucket from its payer account to the cluster account.
         #[ink(message)]
         pub fn bucket_settle_payment(&mut self, bucket_id: BucketId) -> Result<()> {
             self.message_bucket_settle_payment(bucket_id)
         }
 
+        /// Removes a bucket.
+        ///
+        /// This endpoint settles the due costs of the bucket to its cluster, stops its payment flow,
+        /// releases the resources reserved in the cluster and removes the bucket with its writers and readers.
+        /// The due costs that the deposit of the payer does not cover are recorded in the `negative` balance of the payer.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the removing bucket.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `BucketRemoved` event on successful bucket removal.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `OnlyOwner` error if the caller is not the bucket owner.
+        /// * `ClusterDoesNotExist` error if the cluster of the bucket does not exist.
+        #[ink(message)]
+        pub fn bucket_remove(&mut self, bucket_id: BucketId) -> Result<()> {
+            self.message_bucket_remove(bucket_id)
+        }
+
         /// Change the `bucket_params`, which is configuration used by clients and nodes.
         ///
-        /// See the [data structure of BucketParams](https://docs.cere.network/ddc/protocols/contract-params-schema)
+        /// The params are validated against the cluster of the bucket, as in `bucket_create`.
         #[ink(message, payable)]
         pub fn bucket_change_params(
             &mut self,
@@ -197,8 +546,102 @@ pub mod ddc_bucket {
 
         /// Get the current status of a bucket.
         #[ink(message)]
-        pub fn bucket_get(&self, bucket_id: BucketId) -> Result<BucketStatus> {
-            self.message_bucket_get(bucket_id)
+        pub fn bucket_get(&self, bucket_id: BucketId) -> Result<BucketStatus> {
+            self.message_bucket_get(bucket_id)
+        }
+
+        /// Check whether an account has some kind of access to a bucket.
+        ///
+        /// This is the reference for storage and CDN nodes to authorize requests.
+        ///
+        /// * `Admin` is granted to the bucket owner, the cluster manager and the operators with the `ManageBuckets` role.
+        /// * `Write` is granted to admins and to writers with an active permission.
+        /// * `Read` is granted to writers, to readers with an active permission, and to anyone if the bucket is public.
+        /// * `Read` and `Write` are denied to everyone if the bucket is suspended.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the bucket.
+        /// * `account_id` - Account requesting access.
+        /// * `access` - Kind of access requested.
+        ///
+        /// # Output
+        ///
+        /// Returns whether the access is granted.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `ClusterDoesNotExist` error if the cluster of the bucket does not exist.
+        #[ink(message)]
+        pub fn bucket_check_access(
+            &self,
+            bucket_id: BucketId,
+            account_id: AccountId,
+            access: AccessKind,
+        ) -> Result<bool> {
+            self.message_bucket_check_access(bucket_id, account_id, access)
+        }
+
+        /// Update the state of a bucket from the rent coverage of its payer. Anyone can call it.
+        ///
+        /// A bucket is `Active` while its rent is covered, then in `GracePeriod`, then `Suspended` once the grace period is over.
+        /// Nodes should stop serving suspended buckets. A deposit by the payer reactivates its buckets if they are covered again.
+        ///
+        /// The payment flow of a suspended bucket is settled and paused, so the payer is not charged while the bucket is not served.
+        /// The due costs that the payer cannot cover are recorded in its `negative` balance. The flow resumes at the same rate
+        /// once the payer covers it again.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the bucket.
+        ///
+        /// # Output
+        ///
+        /// Returns the state of the bucket.
+        ///
+        /// # Events
+        ///
+        /// * `BucketStateChanged` event if the state of the bucket changed.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        #[ink(message)]
+        pub fn bucket_check_suspension(&mut self, bucket_id: BucketId) -> Result<BucketState> {
+            self.message_bucket_check_suspension(bucket_id)
+        }
+
+        /// Bring the rate of a bucket to the current rent of its cluster. Anyone can call it.
+        ///
+        /// The rate of a bucket is computed from the rent of its cluster when resources are allocated,
+        /// and the rent changes with the nodes of the cluster. A decrease of the rate applies at once.
+        /// An increase is announced, and applies on a call after the notice period (see `get_bucket_rerate_notice_ms`).
+        /// The payment flow is settled at the old rate before it restarts at the new rate.
+        /// The paused flow of a suspended bucket is not re-rated until it resumes.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the bucket.
+        ///
+        /// # Events
+        ///
+        /// * `BucketRerated` event if the rate of the bucket changed.
+        /// * `BucketRerateScheduled` event if an increase of the rate was announced.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        /// * `InsufficientBalance` error if the payer cannot settle the payment flow.
+        #[ink(message)]
+        pub fn bucket_rerate(&mut self, bucket_id: BucketId) -> Result<()> {
+            self.message_bucket_rerate(bucket_id)
+        }
+
+        /// Get the announced increase of the rate of a bucket, if any.
+        #[ink(message)]
+        pub fn bucket_get_pending_rate(&self, bucket_id: BucketId) -> Option<PendingRate> {
+            self.message_bucket_get_pending_rate(bucket_id)
         }
 
         /// Iterate through all buckets.
@@ -217,12 +660,28 @@ pub mod ddc_bucket {
             self.message_bucket_list(offset, limit, filter_owner_id)
         }
 
-        /// Iterate through all buckets and return only those owned by owner
+        /// Iterate through the buckets of an owner.
+        ///
+        /// The buckets are read from an index of owners, so the cost does not depend on the total number of buckets.
+        /// The order of buckets changes when a bucket leaves the owner, as the last bucket takes its place.
+        ///
+        /// # Parameters
+        ///
+        /// * `owner_id` - Account of the owner.
+        /// * `offset` - Position in the list of buckets of the owner to start from.
+        /// * `limit` - Maximum number of buckets to return.
+        ///
+        /// # Output
         ///
-        /// This method returns bucket struct, not the status
+        /// Returns `(results, total)`, where `total` is the number of buckets of the owner. Call again with `offset += limit`, until `offset >= total`.
         #[ink(message)]
-        pub fn bucket_list_for_account(&self, owner_id: AccountId) -> Vec<Bucket> {
-            self.message_bucket_list_for_account(owner_id)
+        pub fn bucket_list_for_account(
+            &self,
+            owner_id: AccountId,
+            offset: u32,
+            limit: u32,
+        ) -> (Vec<BucketStatus>, u32) {
+            self.message_bucket_list_for_account(owner_id, offset, limit)
         }
 
         /// Set availiablity of the bucket
@@ -252,13 +711,16 @@ pub mod ddc_bucket {
         }
 
         /// Set permission for the writer of the bucket
+        ///
+        /// The permission is active until `expires_at_ms`, or forever if None. Granting again changes the expiry.
         #[ink(message)]
         pub fn bucket_set_writer_perm(
             &mut self,
             bucket_id: BucketId,
             writer: AccountId,
+            expires_at_ms: Option<u64>,
         ) -> Result<()> {
-            self.message_grant_writer_permission(bucket_id, writer)
+            self.message_grant_writer_permission(bucket_id, writer, expires_at_ms)
         }
 
         /// Revoke permission for the writer of the bucket
@@ -278,13 +740,16 @@ pub mod ddc_bucket {
         }
 
         /// Set permission for the reader of the bucket
+        ///
+        /// The permission is active until `expires_at_ms`, or forever if None. Granting again changes the expiry.
         #[ink(message)]
         pub fn bucket_set_reader_perm(
             &mut self,
             bucket_id: BucketId,
             reader: AccountId,
+            expires_at_ms: Option<u64>,
         ) -> Result<()> {
-            self.message_grant_reader_permission(bucket_id, reader)
+            self.message_grant_reader_permission(bucket_id, reader, expires_at_ms)
         }
 
         /// Revoke permission for the reader of the bucket
@@ -296,6 +761,26 @@ pub mod ddc_bucket {
         ) -> Result<()> {
             self.message_revoke_reader_permission(bucket_id, writer)
         }
+
+        /// Remove the expired reader and writer permissions of a bucket.
+        ///
+        /// Expired permissions are already ignored, this only frees their storage. Anyone can call it.
+        ///
+        /// # Parameters
+        ///
+        /// * `bucket_id` - ID of the bucket.
+        ///
+        /// # Output
+        ///
+        /// Returns the number of removed permissions.
+        ///
+        /// # Errors
+        ///
+        /// * `BucketDoesNotExist` error if the bucket does not exist.
+        #[ink(message)]
+        pub fn bucket_purge_expired_grants(&mut self, bucket_id: BucketId) -> Result<u32> {
+            self.message_bucket_purge_expired_grants(bucket_id)
+        }
     }
     // ---- End Bucket ----
 
@@ -363,6 +848,93 @@ pub mod ddc_bucket {
         cluster_id: ClusterId,
     }
 
+    /// A new manager was proposed for a cluster.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct ClusterManagerTransferProposed {
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        #[ink(topic)]
+        manager_id: AccountId,
+    }
+
+    /// The proposed manager of a cluster accepted it.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct ClusterManagerTransferred {
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        #[ink(topic)]
+        manager_id: AccountId,
+    }
+
+    /// A validator submitted the hash of the CDN usage of a cluster during an era.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct CdnReportSubmitted {
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        era: u64,
+        #[ink(topic)]
+        validator_id: AccountId,
+        report_hash: Hash,
+    }
+
+    /// A quorum of validators agreed on the CDN usage of a cluster during an era.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct CdnReportAgreed {
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        era: u64,
+        report_hash: Hash,
+    }
+
+    /// The agreed CDN usage of a cluster during an era was charged.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct CdnReportApplied {
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        era: u64,
+        report_hash: Hash,
+    }
+
+    /// The CDN usage of a cluster during an era was settled.
+    /// The accounts paid `accounts_payment`, which the nodes and the protocol received up to rounding.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct CdnRevenueSettled {
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        era: u64,
+        accounts_payment: Balance,
+        nodes_payment: Balance,
+        protocol_fee: Balance,
+    }
+
+    /// A role on a cluster was granted to an operator.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct ClusterOperatorRoleGranted {
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        #[ink(topic)]
+        operator_id: AccountId,
+        role: ClusterRole,
+    }
+
+    /// A role on a cluster was revoked from an operator.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct ClusterOperatorRoleRevoked {
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        #[ink(topic)]
+        operator_id: AccountId,
+        role: ClusterRole,
+    }
+
     #[ink(event)]
     #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
     pub struct ClusterNodeStatusSet {
@@ -414,7 +986,7 @@ pub mod ddc_bucket {
         resource: Resource,
     }
 
-    /// The share of revenues of a cluster for a provider was distributed.
+    /// The share of revenues of a cluster for a provider was distributed, for all its nodes.
     #[ink(event)]
     #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
     pub struct ClusterDistributeRevenues {
@@ -422,6 +994,26 @@ pub mod ddc_bucket {
         cluster_id: ClusterId,
         #[ink(topic)]
         provider_id: AccountId,
+        amount: Balance,
+        nodes_keys: Vec<NodeKey>,
+    }
+
+    /// Some providers of a cluster were paid in the current round of distribution of revenues.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct ClusterDistributionProgress {
+        #[ink(topic)]
+        cluster_id: ClusterId,
+        paid_providers: u32,
+        total_providers: u32,
+    }
+
+    /// All the nodes of a cluster were paid in the current round of distribution of revenues.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct ClusterDistributionCompleted {
+        #[ink(topic)]
+        cluster_id: ClusterId,
     }
 
     #[ink(event)]
@@ -431,6 +1023,8 @@ pub mod ddc_bucket {
         cluster_id: ClusterId,
         #[ink(topic)]
         provider_id: AccountId,
+        amount: Balance,
+        cdn_nodes_keys: Vec<CdnNodeKey>,
     }
 
     impl DdcBucket {
@@ -487,12 +1081,14 @@ pub mod ddc_bucket {
         /// # Errors
         ///
         /// * `ClusterDoesNotExist` error if the cluster does not exist.
-        /// * `OnlyTrustedClusterManager` error if the caller is not a trusted cluster manager.
+        /// * `OnlyClusterManager` error if the caller is neither the cluster manager nor an operator with the `ManageNodes` role.
+        /// * `OnlyTrustedClusterManager` error if the cluster manager is not trusted by the node provider.
         /// * `NodeDoesNotExist` error if the adding Storage node does not exist.
         /// * `NodeIsAddedToCluster(ClusterId)` error if the adding Storage node is already added to this or another cluster.
         /// * `AtLeastOneVNodeHasToBeAssigned(ClusterId, NodeKey)` error if there is a Storage node without any virtual nodes in the cluster.
-        /// * `VNodesSizeExceedsLimit` error if virtual nodes length exceeds storage capacity.
+        /// * `VNodesSizeExceedsLimit` error if the virtual nodes of a Storage node exceed storage capacity.
         /// * `InsufficientNodeResources` - error if there is not enough resources in a physical node.
+        /// * `ClusterNodesAreLocked` error if a distribution of revenues or an update of the resource per vnode is going through the nodes of the cluster.
         #[ink(message, payable)]
         pub fn cluster_add_node(
             &mut self,
@@ -527,6 +1123,7 @@ pub mod ddc_bucket {
         /// * `OnlyClusterManagerOrNodeProvider` error if the caller is not the cluster manager or node owner.
         /// * `NodeDoesNotExist` error if the removing Storage node does not exist.
         /// * `NodeIsNotAddedToCluster(ClusterId)` error if the removing Storage node is not in this cluster.
+        /// * `ClusterNodesAreLocked` error if a distribution of revenues or an update of the resource per vnode is going through the nodes of the cluster.
         #[ink(message)]
         pub fn cluster_remove_node(
             &mut self,
@@ -565,7 +1162,8 @@ pub mod ddc_bucket {
         /// * `VNodeIsNotAssignedToNode(ClusterId, VNodeToken)` error if the there is some virtual node that is being reasigned, but this virtual node is not assigned to any physical node.
         /// * `VNodeIsAlreadyAssignedToNode(NodeKey)` - error if there is some virtual node that is already assigned to other physical node within the same cluster.
         /// * `AtLeastOneVNodeHasToBeAssigned(ClusterId, NodeKey)` error if there is a Storage node without any virtual nodes in the cluster.
-        /// * `VNodesSizeExceedsLimit` error if virtual nodes length exceeds storage capacity.
+        /// * `VNodesSizeExceedsLimit` error if the virtual nodes of a Storage node exceed storage capacity.
+        /// * `ClusterNodesAreLocked` error if a distribution of revenues or an update of the resource per vnode is going through the nodes of the cluster.
         #[ink(message)]
         pub fn cluster_replace_node(
             &mut self,
@@ -599,12 +1197,14 @@ pub mod ddc_bucket {
         /// # Errors
         ///
         /// * `ClusterDoesNotExist` error if the cluster does not exist.
-        /// * `OnlyTrustedClusterManager` error if the caller is not a trusted cluster manager.
+        /// * `OnlyClusterManager` error if the caller is neither the cluster manager nor an operator with the `ManageNodes` role.
+        /// * `OnlyTrustedClusterManager` error if the cluster manager is not trusted by the node provider.
         /// * `NodeDoesNotExist` error if the adding Storage node does not exist.
         /// * `NodeIsAddedToCluster(ClusterId)` error if the adding Storage node is already added to this or another cluster.
         /// * `AtLeastOneVNodeHasToBeAssigned(ClusterId, NodeKey)` error if there is a Storage node without any virtual nodes in the cluster.
-        /// * `VNodesSizeExceedsLimit` error if virtual nodes length exceeds storage capacity.
+        /// * `VNodesSizeExceedsLimit` error if the virtual nodes of a Storage node exceed storage capacity.
         /// * `InsufficientNodeResources` - error if there is not enough resources in a physical node.
+        /// * `ClusterNodesAreLocked` error if a distribution of revenues or an update of the resource per vnode is going through the nodes of the cluster.
         #[ink(message)]
         pub fn cluster_reset_node(
             &mut self,
@@ -615,6 +1215,70 @@ pub mod ddc_bucket {
             self.message_cluster_reset_node(cluster_id, node_key, new_v_nodes)
         }
 
+        /// Add a Storage node to the targeting cluster, with virtual nodes generated by the contract.
+        ///
+        /// Same as `cluster_add_node`, but each token is derived from the hash of the cluster ID, the node key and the index
+        /// of the token, so that nodes own shares of the ring in proportion to their tokens. Taken tokens are moved to the next
+        /// free position.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the targeting cluster.
+        /// * `node_key` - Public Key associated with the Storage node.
+        /// * `v_nodes_count` - How many virtual nodes to assign to the Storage node.
+        ///
+        /// # Output
+        ///
+        /// Returns the generated tokens.
+        ///
+        /// # Events
+        ///
+        /// * `ClusterNodeAdded` event on successful Storage node addition.
+        ///
+        /// # Errors
+        ///
+        /// Same as `cluster_add_node`.
+        #[ink(message, payable)]
+        pub fn cluster_add_node_auto(
+            &mut self,
+            cluster_id: ClusterId,
+            node_key: NodeKey,
+            v_nodes_count: u32,
+        ) -> Result<Vec<VNodeToken>> {
+            self.message_cluster_add_node_auto(cluster_id, node_key, v_nodes_count)
+        }
+
+        /// Reset a Storage node in the targeting cluster, with virtual nodes generated by the contract.
+        ///
+        /// Same as `cluster_reset_node`, with tokens generated as in `cluster_add_node_auto`.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the targeting cluster.
+        /// * `node_key` - Public Key associated with the Storage node.
+        /// * `v_nodes_count` - How many virtual nodes to assign to the Storage node.
+        ///
+        /// # Output
+        ///
+        /// Returns the generated tokens.
+        ///
+        /// # Events
+        ///
+        /// * `ClusterNodeReset` event on successful Storage node reset.
+        ///
+        /// # Errors
+        ///
+        /// Same as `cluster_reset_node`.
+        #[ink(message)]
+        pub fn cluster_reset_node_auto(
+            &mut self,
+            cluster_id: ClusterId,
+            node_key: NodeKey,
+            v_nodes_count: u32,
+        ) -> Result<Vec<VNodeToken>> {
+            self.message_cluster_reset_node_auto(cluster_id, node_key, v_nodes_count)
+        }
+
         /// Adds a CDN node to the targeting cluster.
         ///
         /// This endpoint adds a CDN node to the targeting cluster.
@@ -636,7 +1300,8 @@ pub mod ddc_bucket {
         /// # Errors
         ///
         /// * `ClusterDoesNotExist` error if the cluster does not exist.
-        /// * `OnlyTrustedClusterManager` error if the caller is not a trusted cluster manager.
+        /// * `OnlyClusterManager` error if the caller is neither the cluster manager nor an operator with the `ManageNodes` role.
+        /// * `OnlyTrustedClusterManager` error if the cluster manager is not trusted by the CDN node provider.
         /// * `CdnNodeDoesNotExist` error if the adding CDN node does not exist.
         /// * `CdnNodeIsAddedToCluster(ClusterId)` error if the adding CDN node is already added to this or another cluster.
         #[ink(message, payable)]
@@ -733,12 +1398,184 @@ pub mod ddc_bucket {
         ///
         /// * `OnlyClusterManager` error if the caller is not the cluster manager.
         /// * `ClusterDoesNotExist` error if the cluster does not exist.
-        /// * `ClusterIsNotEmpty` error if the removing cluster contains some Storage or CDN nodes.
+        /// * `ClusterIsNotEmpty` error if the removing cluster contains some Storage or CDN nodes, or a distribution of its revenues is in progress.
         #[ink(message)]
         pub fn cluster_remove(&mut self, cluster_id: ClusterId) -> Result<()> {
             self.message_cluster_remove(cluster_id)
         }
 
+        /// Propose a new manager for a cluster.
+        ///
+        /// The cluster is transferred once the new manager accepts it with `cluster_accept_manager`.
+        /// A previous proposal is replaced. The new manager must be trusted by providers to add their nodes.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `manager_id` - Account of the new manager.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `ClusterManagerTransferProposed` event on successful proposal.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        /// * `OnlyClusterManager` error if the caller is not the cluster manager.
+        #[ink(message)]
+        pub fn cluster_transfer_manager(
+            &mut self,
+            cluster_id: ClusterId,
+            manager_id: AccountId,
+        ) -> Result<()> {
+            self.message_cluster_transfer_manager(cluster_id, manager_id)
+        }
+
+        /// Cancel a pending transfer of a cluster to a new manager.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        /// * `OnlyClusterManager` error if the caller is not the cluster manager.
+        #[ink(message)]
+        pub fn cluster_cancel_manager_transfer(&mut self, cluster_id: ClusterId) -> Result<()> {
+            self.message_cluster_cancel_manager_transfer(cluster_id)
+        }
+
+        /// Accept to become the manager of a cluster.
+        ///
+        /// The roles of the operators of the cluster are revoked.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `ClusterManagerTransferred` event on successful transfer.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        /// * `OnlyPendingClusterManager` error if the caller was not proposed as the manager of the cluster.
+        #[ink(message)]
+        pub fn cluster_accept_manager(&mut self, cluster_id: ClusterId) -> Result<()> {
+            self.message_cluster_accept_manager(cluster_id)
+        }
+
+        /// Grant a role on a cluster to an operator.
+        ///
+        /// Operators act as the manager within the scope of their roles. Only the manager can remove
+        /// the cluster, transfer it, or change the operators. Granting a role twice has no effect.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `operator_id` - Account of the operator.
+        /// * `role` - The role to grant.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `ClusterOperatorRoleGranted` event on successful grant.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        /// * `OnlyClusterManager` error if the caller is not the cluster manager.
+        /// * `OperatorsSizeExceedsLimit` error if the cluster already has the maximum number of operators.
+        #[ink(message)]
+        pub fn cluster_grant_operator_role(
+            &mut self,
+            cluster_id: ClusterId,
+            operator_id: AccountId,
+            role: ClusterRole,
+        ) -> Result<()> {
+            self.message_cluster_grant_operator_role(cluster_id, operator_id, role)
+        }
+
+        /// Revoke a role on a cluster from an operator.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `operator_id` - Account of the operator.
+        /// * `role` - The role to revoke.
+        ///
+        /// # Output
+        ///
+        /// Returns nothing.
+        ///
+        /// # Events
+        ///
+        /// * `ClusterOperatorRoleRevoked` event on successful revocation.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        /// * `OnlyClusterManager` error if the caller is not the cluster manager.
+        #[ink(message)]
+        pub fn cluster_revoke_operator_role(
+            &mut self,
+            cluster_id: ClusterId,
+            operator_id: AccountId,
+            role: ClusterRole,
+        ) -> Result<()> {
+            self.message_cluster_revoke_operator_role(cluster_id, operator_id, role)
+        }
+
+        /// Get the roles of an operator on a cluster.
+        #[ink(message)]
+        pub fn cluster_get_operator_roles(
+            &self,
+            cluster_id: ClusterId,
+            operator_id: AccountId,
+        ) -> Vec<ClusterRole> {
+            self.message_cluster_get_operator_roles(cluster_id, operator_id)
+        }
+
+        /// Gets a paginated list of the operators of a cluster, i.e. the accounts with at least one role.
+        ///
+        /// The algorithm for paging is: start with `offset = 0` and `limit = 32`. The function returns a `(results, total)`. Call again with `offset += limit`, until `offset >= total`.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `offset` - starting offset.
+        /// * `limit` - page limit.
+        ///
+        /// # Errors
+        ///
+        /// No errors. In case a pagination param is out of bounds or the cluster does not exist, an empty list will be returned.
+        #[ink(message)]
+        pub fn cluster_list_operators(
+            &self,
+            cluster_id: ClusterId,
+            offset: u32,
+            limit: u32,
+        ) -> (Vec<AccountId>, u32) {
+            self.message_cluster_list_operators(cluster_id, offset, limit)
+        }
+
         /// Changes Storage node status.
         ///
         /// This endpoint changes Storage node status in a cluster.
@@ -808,27 +1645,30 @@ pub mod ddc_bucket {
         /// Sets the resource used per virual node in cluster.
         ///
         /// This endpoint sets the resource value that is being used by each virtual node in the cluster.
-        /// If there are existing virtual nodes in the cluster the resource for its physical nodes will be recalculated.
+        /// The resource of the physical nodes is recalculated in pages over several calls with the same value:
+        /// all nodes are checked first, then the cluster switches to the new value and the nodes are updated.
+        /// The nodes of the cluster cannot change until the update is completed. While the nodes are checked,
+        /// a call with another value restarts the update, e.g. with the current value to cancel it.
         ///
         /// # Parameters
         ///
         /// * `cluster_id` - ID of the targeting cluster.
         /// * `new_resource_per_v_node` - Resource value that will be allocated for every virtual node in the cluster.
+        /// * `limit` - The maximum number of nodes to process in this call.
         ///
         /// # Output
         ///
-        /// Returns nothing.
+        /// Returns whether the update is completed.
         ///
         /// # Events
         ///
-        /// * `ClusterNodeReplaced` event on successful virtual node reassignment.
+        /// * `ClusterReserveResource` event when the update is completed.
         ///
         /// # Errors
         ///
         /// * `ClusterDoesNotExist` error if the cluster does not exist.
         /// * `OnlyClusterManager` error if the caller is not the cluster manager.
-        /// * `NodeDoesNotExist` error if the new Storage node does not exist.
-        /// * `VNodeIsNotAssignedToNode(ClusterId, VNodeToken)` error if the there is some virtual node that is being reasigned, but this virtual node is not assigned to any physical node.
+        /// * `ClusterNodesAreLocked` error if an update to another value is being applied.
         /// * `InsufficientClusterResources` - error if there is not enough resources in the cluster.
         /// * `InsufficientNodeResources` - error if there is not enough resources in a physical node.
         #[ink(message)]
@@ -836,13 +1676,15 @@ pub mod ddc_bucket {
             &mut self,
             cluster_id: ClusterId,
             new_resource_per_v_node: Resource,
-        ) -> Result<()> {
-            self.message_cluster_set_resource_per_v_node(cluster_id, new_resource_per_v_node)
+            limit: u32,
+        ) -> Result<bool> {
+            self.message_cluster_set_resource_per_v_node(cluster_id, new_resource_per_v_node, limit)
         }
 
         /// Gets a cluster.
         ///
-        /// This endpoint gets the targeting cluster along with its parameters, Storage and CDN nodes.
+        /// This endpoint gets the targeting cluster along with its parameters, CDN nodes and number of Storage nodes.
+        /// The Storage nodes are listed with `cluster_list_nodes` or `cluster_list_nodes_v_nodes`.
         ///
         /// # Parameters
         ///
@@ -862,7 +1704,7 @@ pub mod ddc_bucket {
 
         /// Gets a paginated list of clusters.
         ///
-        /// This endpoint gets a paginated list of clusters along with their parameters, Storage and CDN nodes.
+        /// This endpoint gets a paginated list of clusters along with their parameters, CDN nodes and number of Storage nodes.
         /// The algorithm for paging is: start with `offset = 1` and `limit = 20`. The function returns a `(results, max_id)`. Call again with `offset += limit`, until `offset >= max_id`.
         /// The optimal `limit` depends on the size of params.
         ///
@@ -885,10 +1727,125 @@ pub mod ddc_bucket {
             self.message_cluster_list(offset, limit, filter_manager_id)
         }
 
+        /// Gets a paginated list of the Storage nodes of a cluster.
+        ///
+        /// The algorithm for paging is: start with `offset = 0` and `limit = 100`. The function returns a `(results, total)`. Call again with `offset += limit`, until `offset >= total`.
+        /// The order of nodes changes when a node is removed, as the last node takes its place.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `offset` - starting offset.
+        /// * `limit` - page limit.
+        ///
+        /// # Errors
+        ///
+        /// No errors. In case a pagination param is out of bounds or the cluster does not exist, an empty list will be returned.
+        #[ink(message)]
+        pub fn cluster_list_nodes(
+            &self,
+            cluster_id: ClusterId,
+            offset: u32,
+            limit: u32,
+        ) -> (Vec<NodeKey>, u32) {
+            self.message_cluster_list_nodes(cluster_id, offset, limit)
+        }
+
+        /// Gets a paginated list of the Storage nodes of a cluster along with their virtual nodes.
+        ///
+        /// The algorithm for paging is: start with `offset = 0` and `limit = 20`. The function returns a `(results, total)`. Call again with `offset += limit`, until `offset >= total`.
+        /// The order of nodes changes when a node is removed, as the last node takes its place.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `offset` - starting offset.
+        /// * `limit` - page limit.
+        ///
+        /// # Errors
+        ///
+        /// No errors. In case a pagination param is out of bounds or the cluster does not exist, an empty list will be returned.
+        #[ink(message)]
+        pub fn cluster_list_nodes_v_nodes(
+            &self,
+            cluster_id: ClusterId,
+            offset: u32,
+            limit: u32,
+        ) -> (Vec<NodeVNodesInfo>, u32) {
+            self.message_cluster_list_nodes_v_nodes(cluster_id, offset, limit)
+        }
+
         /// Trigger the distribution of revenues from the cluster to the providers.
+        ///
+        /// Anyone can call this method. A round of distribution takes the fees at once, then goes in pages over several calls:
+        /// it weighs the nodes, sets aside the share of each node for its provider, and pays the providers.
+        /// A new round starts on the next call after the last page. The nodes of the cluster cannot change until they are
+        /// all shared.
+        ///
+        /// The share of a node is proportional to its contribution to the rent of the cluster: its number of vnodes
+        /// times its rent per vnode. The remainder of rounding is kept for the next round.
+        /// A provider is paid once for all its nodes. Payments and fees are credited to the earnings of their
+        /// recipients, who withdraw them with `claim_earnings`.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `limit` - Maximum number of nodes to weigh or share and of providers to pay in this call.
+        ///
+        /// # Output
+        ///
+        /// Returns true if all the providers of the round have been paid.
+        ///
+        /// # Events
+        ///
+        /// * `ClusterDistributeRevenues` event for each provider paid.
+        /// * `ClusterDistributionProgress` event on every call.
+        /// * `ClusterDistributionCompleted` event when the round is completed.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        #[ink(message)]
+        pub fn cluster_distribute_revenues(
+            &mut self,
+            cluster_id: ClusterId,
+            limit: u32,
+        ) -> Result<bool> {
+            self.message_cluster_distribute_revenues(cluster_id, limit)
+        }
+
+        /// Bring the rates of the buckets of a cluster to its current rent, in pages. Anyone can call it.
+        ///
+        /// Each bucket is re-rated as with `bucket_rerate`. Buckets whose payment flow cannot be settled are reported and skipped.
+        /// The buckets are paged over the index of the buckets of the cluster, where a removed bucket is replaced by the last one.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `offset` - The position of the first bucket in the index of the cluster.
+        /// * `limit` - How many buckets to re-rate.
+        ///
+        /// # Output
+        ///
+        /// Returns the total number of buckets of the cluster, to know when to stop paging.
+        ///
+        /// # Events
+        ///
+        /// * `BucketRerated` event for each bucket whose rate changed.
+        /// * `BucketRerateScheduled` event for each bucket whose rate increase was announced.
+        /// * `BucketRerateFailed` event for each bucket whose payment flow could not be settled.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
         #[ink(message)]
-        pub fn cluster_distribute_revenues(&mut self, cluster_id: ClusterId) -> Result<()> {
-            self.message_cluster_distribute_revenues(cluster_id)
+        pub fn cluster_rerate_buckets(
+            &mut self,
+            cluster_id: ClusterId,
+            offset: u32,
+            limit: u32,
+        ) -> Result<u32> {
+            self.message_cluster_rerate_buckets(cluster_id, offset, limit)
         }
 
         /// Set rate for streaming (price per gb)
@@ -903,9 +1860,68 @@ pub mod ddc_bucket {
             self.message_cdn_get_rate(cluster_id)
         }
 
+        /// As validator, submit the hash of the CDN usage of a cluster during an era.
+        ///
+        /// The hash is the Blake2x256 hash of the SCALE encoding of
+        /// `(cluster_id, era, aggregates_accounts, aggregates_nodes, aggregates_buckets)`, as passed to `cluster_put_cdn_revenue`.
+        /// The usage is charged once `get_cdn_report_quorum` validators submitted the same hash and the aggregates are put.
+        /// A validator may submit again to replace its hash.
+        ///
+        /// # Events
+        ///
+        /// * `CdnReportSubmitted` event on successful submission.
+        /// * `CdnReportAgreed` event if the hash reached the quorum.
+        ///
+        /// # Errors
+        ///
+        /// * `OnlyValidator` error if the caller is not a validator.
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        /// * `EraIsNotInValidationOrPayoutPhase` error if eras are not set or the current era is in its commit phase.
+        /// * `EraIsInTheFuture` error if the era is after the current era.
+        /// * `EraIsOlderThanLastSettled` error if the era was not charged and the usage of a later era was already charged.
+        #[ink(message)]
+        pub fn cluster_submit_cdn_report(
+            &mut self,
+            cluster_id: ClusterId,
+            era: u64,
+            report_hash: Hash,
+        ) -> Result<()> {
+            self.message_cluster_submit_cdn_report(cluster_id, era, report_hash)
+        }
+
+        /// Gets the votes of the validators on the CDN usage of a cluster during an era.
+        ///
+        /// The validators that disagree with the agreed hash are listed for review.
+        #[ink(message)]
+        pub fn cluster_get_cdn_report(&self, cluster_id: ClusterId, era: u64) -> Option<CdnReport> {
+            self.message_cluster_get_cdn_report(cluster_id, era)
+        }
+
         /// As validator, charge payments from users and allocate undistributed payments to CDN nodes.
         ///
         /// As a result CDN cluster revenue increases, which can be distributed between CDN node providers via method cdn_cluster_distribute_revenues.
+        ///
+        /// The call submits the hash of the aggregates for the caller, as `cluster_submit_cdn_report`. The aggregates are charged only
+        /// if a quorum of validators agreed on their hash, otherwise only the vote is recorded.
+        /// The usage of each era is charged once per cluster, in order of eras, during the validation or payout phase of the current era.
+        /// Later votes on a charged era are only recorded, and listed as dissenters if they disagree.
+        ///
+        /// # Events
+        ///
+        /// * `CdnReportSubmitted` event on successful submission.
+        /// * `CdnReportAgreed` event if the hash reached the quorum.
+        /// * `CdnRevenueSettled` event with the amounts charged, if the aggregates were charged.
+        /// * `CdnReportApplied` event if the aggregates were charged.
+        ///
+        /// # Errors
+        ///
+        /// * `OnlyValidator` error if the caller is not a validator.
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        /// * `EraIsOlderThanLastSettled` error if the era was not charged and the usage of a later era was already charged.
+        /// * `EraIsNotInValidationOrPayoutPhase` error if eras are not set or the current era is in its commit phase.
+        /// * `EraIsInTheFuture` error if the era is after the current era.
+        /// * `UnbalancedCdnReport` error if the payments of the accounts and of the nodes differ beyond rounding,
+        ///   or if the usage of the buckets differs from the usage of the accounts.
         #[ink(message)]
         pub fn cluster_put_cdn_revenue(
             &mut self,
@@ -928,7 +1944,8 @@ pub mod ddc_bucket {
         ///
         /// Anyone can call this method.
         ///
-        /// Undistributed payments will be trasnferred, CDN cluster revenue will decrease.
+        /// Undistributed payments will be credited to the earnings of the providers, CDN cluster revenue will decrease.
+        /// A provider is paid once for all its CDN nodes, with a `ClusterDistributeCdnRevenues` event.
         #[ink(message)]
         pub fn cluster_distribute_cdn_revenue(&mut self, cluster_id: ClusterId) -> Result<()> {
             self.message_cluster_distribute_cdn_revenue(cluster_id)
@@ -940,6 +1957,21 @@ pub mod ddc_bucket {
 
     impl DdcBucket {
         /// CDN node operator sets the commit for current era.
+        ///
+        /// The commit replaces the latest commit of the CDN node, and is kept in the history of the current era.
+        ///
+        /// # Parameters
+        ///
+        /// * `cdn_owner` - Provider of the CDN node, who must be the caller.
+        /// * `cdn_node_key` - Public Key associated with the CDN node.
+        /// * `commit` - Summary of the logs of the CDN node.
+        ///
+        /// # Errors
+        ///
+        /// * `CdnNodeDoesNotExist` error if the CDN node does not exist.
+        /// * `OnlyCdnNodeProvider` error if the caller or `cdn_owner` is not the provider of the CDN node.
+        /// * `CdnNodeIsNotAddedToAnyCluster` error if the CDN node is not in a cluster.
+        /// * `EraIsNotInCommitPhase` error if eras are not set, or the current era is not in its commit phase.
         #[ink(message)]
         pub fn set_commit(
             &mut self,
@@ -956,6 +1988,49 @@ pub mod ddc_bucket {
             self.message_get_commit(cdn_owner)
         }
 
+        /// Gets a paginated list of the commits set during an era.
+        ///
+        /// The algorithm for paging is: start with `cursor = 0` and `limit = 100`. The function returns a `(results, total)`. Call again with `cursor += limit`, until `cursor >= total`.
+        /// Commits are kept for `COMMIT_HISTORY_ERAS` eras.
+        ///
+        /// # Parameters
+        ///
+        /// * `era` - The era during which the commits were set.
+        /// * `cursor` - starting offset.
+        /// * `limit` - page limit.
+        ///
+        /// # Errors
+        ///
+        /// No errors. In case a pagination param is out of bounds or the era is pruned, an empty list will be returned.
+        #[ink(message)]
+        pub fn get_commits_for_era(
+            &self,
+            era: u64,
+            cursor: u32,
+            limit: u32,
+        ) -> (Vec<(CdnNodeKey, Commit)>, u32) {
+            self.message_get_commits_for_era(era, cursor, limit)
+        }
+
+        /// Gets the commits of a CDN node by era, oldest first.
+        ///
+        /// Only the eras that are not pruned yet are returned.
+        ///
+        /// # Parameters
+        ///
+        /// * `cdn_node_key` - Public Key associated with the CDN node.
+        /// * `from_era` - The first era to return.
+        /// * `to_era` - The last era to return.
+        #[ink(message)]
+        pub fn get_commit_history(
+            &self,
+            cdn_node_key: CdnNodeKey,
+            from_era: u64,
+            to_era: u64,
+        ) -> Vec<(u64, Commit)> {
+            self.message_get_commit_history(cdn_node_key, from_era, to_era)
+        }
+
         /// Return last era validated per CDN node
         #[ink(message)]
         pub fn get_validated_commit(&self, cdn_node_key: CdnNodeKey) -> EraAndTimestamp {
@@ -1327,6 +2402,24 @@ pub mod ddc_bucket {
             self.message_get_protocol_fee_bp()
         }
 
+        /// Get how long buckets are served after their rent is not covered anymore.
+        #[ink(message)]
+        pub fn get_bucket_grace_period_ms(&self) -> u64 {
+            self.message_get_bucket_grace_period_ms()
+        }
+
+        /// Get how long before an increase of the rent of a cluster applies to its existing buckets.
+        #[ink(message)]
+        pub fn get_bucket_rerate_notice_ms(&self) -> u64 {
+            self.message_get_bucket_rerate_notice_ms()
+        }
+
+        /// Get how many validators must agree on the CDN usage of a cluster before it is charged.
+        #[ink(message)]
+        pub fn get_cdn_report_quorum(&self) -> u32 {
+            self.message_get_cdn_report_quorum()
+        }
+
         /// Return fees accumulated by the protocol
         #[ink(message)]
         pub fn get_protocol_revenues(&self) -> Cash {
@@ -1352,9 +2445,21 @@ pub mod ddc_bucket {
         value: Balance,
     }
 
+    /// Earnings were transferred from the contract to their recipient.
+    #[ink(event)]
+    #[cfg_attr(feature = "std", derive(PartialEq, Debug, scale_info::TypeInfo))]
+    pub struct EarningsClaimed {
+        #[ink(topic)]
+        account_id: AccountId,
+        value: Balance,
+    }
+
     impl DdcBucket {
         /// As user, deposit tokens on the account of the caller from the transaction value. This deposit
         /// can be used to pay for the services to buckets of the account.
+        ///
+        /// Some buckets of the account in grace period or suspended are reactivated if they are covered again,
+        /// the others can be reactivated with `bucket_check_suspension`.
         #[ink(message, payable)]
         pub fn account_deposit(&mut self) -> Result<()> {
             self.message_account_deposit()
@@ -1386,6 +2491,34 @@ pub mod ddc_bucket {
             self.accounts.get(&account_id)
         }
 
+        /// Get the earnings of an account that can be claimed.
+        ///
+        /// Earnings are credited by the distribution of cluster revenues to providers and managers,
+        /// and by the network fee.
+        #[ink(message)]
+        pub fn account_get_earnings(&self, account_id: AccountId) -> Balance {
+            self.accounts.get_earnings(account_id)
+        }
+
+        /// As provider, cluster manager or fee destination, withdraw all the earnings of the caller.
+        ///
+        /// # Output
+        ///
+        /// Returns the amount transferred to the caller.
+        ///
+        /// # Events
+        ///
+        /// * `EarningsClaimed` event on successful withdrawal.
+        ///
+        /// # Errors
+        ///
+        /// * `InsufficientBalance` error if the caller has no earnings.
+        /// * `TransferFailed` error if the transfer to the caller fails. The earnings are kept.
+        #[ink(message)]
+        pub fn claim_earnings(&mut self) -> Result<Balance> {
+            self.message_claim_earnings()
+        }
+
         /// Get the current conversion rate between the native currency and an external currency (USD).
         #[ink(message)]
         pub fn account_get_usd_per_cere(&self) -> Balance {
@@ -1660,6 +2793,29 @@ pub mod ddc_bucket {
             self.message_admin_set_network_fee_config(config)
         }
 
+        /// As SuperAdmin, set how long buckets are served after their rent is not covered anymore.
+        #[ink(message)]
+        pub fn admin_set_bucket_grace_period_ms(&mut self, grace_period_ms: u64) -> Result<()> {
+            self.message_admin_set_bucket_grace_period_ms(grace_period_ms)
+        }
+
+        /// As SuperAdmin, set how long before an increase of the rent of a cluster applies to its existing buckets.
+        #[ink(message)]
+        pub fn admin_set_bucket_rerate_notice_ms(&mut self, notice_ms: u64) -> Result<()> {
+            self.message_admin_set_bucket_rerate_notice_ms(notice_ms)
+        }
+
+        /// As SuperAdmin, set how many validators must agree on the CDN usage of a cluster before it is charged.
+        ///
+        /// # Errors
+        ///
+        /// * `OnlySuperAdmin` error if the caller is not the Super-admin.
+        /// * `InvalidCdnReportQuorum` error if the quorum is 0.
+        #[ink(message)]
+        pub fn admin_set_cdn_report_quorum(&mut self, quorum: u32) -> Result<()> {
+            self.message_admin_set_cdn_report_quorum(quorum)
+        }
+
         #[ink(message)]
         pub fn admin_set_protocol_fee_bp(&mut self, protocol_fee_bp: BasisPoints) -> Result<()> {
             self.message_admin_set_protocol_fee_bp(protocol_fee_bp)
@@ -1679,11 +2835,46 @@ pub mod ddc_bucket {
 
     // ---- Topology ----
     impl DdcBucket {
+        /// Gets all the virtual nodes of a cluster at once.
+        ///
+        /// The whole ring is read in a single call, use `list_v_nodes_by_cluster` to read the ring of a large cluster by pages.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        ///
+        /// # Errors
+        ///
+        /// No errors. If the cluster does not exist, an empty list will be returned.
         #[ink(message)]
         pub fn get_v_nodes_by_cluster(&self, cluster_id: ClusterId) -> Vec<VNodeToken> {
             self.message_get_v_nodes_by_cluster(cluster_id)
         }
 
+        /// Gets a paginated list of the virtual nodes of a cluster.
+        ///
+        /// The algorithm for paging is: start with `offset = 0` and `limit = 1000`. The function returns a `(results, total)`. Call again with `offset += limit`, until `offset >= total`.
+        /// The order of virtual nodes changes when a node is removed, as the last virtual nodes take their places.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `offset` - starting offset.
+        /// * `limit` - page limit.
+        ///
+        /// # Errors
+        ///
+        /// No errors. In case a pagination param is out of bounds or the cluster does not exist, an empty list will be returned.
+        #[ink(message)]
+        pub fn list_v_nodes_by_cluster(
+            &self,
+            cluster_id: ClusterId,
+            offset: u32,
+            limit: u32,
+        ) -> (Vec<VNodeToken>, u32) {
+            self.message_list_v_nodes_by_cluster(cluster_id, offset, limit)
+        }
+
         #[ink(message)]
         pub fn get_v_nodes_by_node(
             &self,
@@ -1701,6 +2892,87 @@ pub mod ddc_bucket {
         ) -> Result<NodeKey> {
             self.message_get_node_by_v_node(cluster_id, v_node)
         }
+
+        /// Gets the version of the topology of a cluster.
+        ///
+        /// The version starts at 0 and increases by one on every change of the virtual nodes of the cluster.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        #[ink(message)]
+        pub fn get_topology_version(&self, cluster_id: ClusterId) -> Result<TopologyVersion> {
+            self.message_get_topology_version(cluster_id)
+        }
+
+        /// Gets the virtual nodes of a cluster that changed since a version of its topology.
+        ///
+        /// A virtual node changes when it is assigned, reassigned to another physical node, or unassigned. Use `get_node_by_v_node`
+        /// to read its current physical node.
+        /// The algorithm for paging is: start with `offset = 0` and `limit = 1000`. The function returns the changes with their `total`
+        /// and the current `version`. Call again with `offset += limit`, until `offset >= total`, then sync from the last `version`.
+        /// Only the latest changes are kept. If they do not reach back to `since_version`, read the whole ring with
+        /// `list_v_nodes_by_cluster` instead.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `since_version` - The version known by the caller, 0 for the changes since the creation of the cluster.
+        /// * `offset` - starting offset.
+        /// * `limit` - page limit.
+        ///
+        /// # Output
+        ///
+        /// Returns `TopologyChanges` data transfer object.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        /// * `TopologyVersionIsTooOld` error if some changes since the version are not kept anymore.
+        #[ink(message)]
+        pub fn get_topology_changes(
+            &self,
+            cluster_id: ClusterId,
+            since_version: TopologyVersion,
+            offset: u32,
+            limit: u32,
+        ) -> Result<TopologyChanges> {
+            self.message_get_topology_changes(cluster_id, since_version, offset, limit)
+        }
+
+        /// Find the nodes that store a key in a cluster.
+        ///
+        /// The ring of tokens is walked clockwise from the hash of the key, wrapping around. Virtual nodes of
+        /// a physical node already selected, and of nodes that are not `ACTIVE`, are skipped.
+        ///
+        /// # Parameters
+        ///
+        /// * `cluster_id` - ID of the cluster.
+        /// * `key_hash` - Position of the key on the ring.
+        /// * `replication_factor` - How many nodes to select.
+        ///
+        /// # Output
+        ///
+        /// Returns the selected virtual nodes and their physical nodes, in ring order. The list is shorter than
+        /// the replication factor if there are not enough active nodes.
+        ///
+        /// # Errors
+        ///
+        /// * `ClusterDoesNotExist` error if the cluster does not exist.
+        /// * `InvalidReplicationFactor` error if the replication factor is 0.
+        #[ink(message)]
+        pub fn cluster_resolve_key(
+            &self,
+            cluster_id: ClusterId,
+            key_hash: VNodeToken,
+            replication_factor: u32,
+        ) -> Result<Vec<(VNodeToken, NodeKey)>> {
+            self.message_cluster_resolve_key(cluster_id, key_hash, replication_factor)
+        }
     }
     // ---- End Topology ----
 
@@ -1713,6 +2985,9 @@ pub mod ddc_bucket {
     pub const DEFAULT_PROTOCOL_FEE_BP: BasisPoints = 500; // 5 %
     pub const DEFAULT_NETWORK_FEE_BP: BasisPoints = 0; // 0 %
     pub const DEFAULT_CLUSTER_FEE_BP: BasisPoints = 0; // 0 %
+    pub const DEFAULT_BUCKET_GRACE_PERIOD_MS: u64 = 7 * 24 * 3600 * 1000; // 7 days
+    pub const DEFAULT_BUCKET_RERATE_NOTICE_MS: u64 = 14 * 24 * 3600 * 1000; // 14 days
+    pub const DEFAULT_CDN_REPORT_QUORUM: u32 = 1;
 
     #[derive(Debug, PartialEq, Eq, Encode, Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
@@ -1759,6 +3034,25 @@ pub mod ddc_bucket {
         InsufficientNodeResources,
         InsufficientClusterResources,
         EraSettingFailed,
+        OnlyOwnerOrPayer,
+        OnlyPendingPayer,
+        OnlyPendingOwner,
+        OnlyPendingClusterManager,
+        ClusterNodesAreLocked,
+        TopologyVersionIsTooOld,
+        CdnNodeIsNotAddedToAnyCluster,
+        BucketIsAlreadyInCluster,
+        BucketIsSuspended,
+        InsufficientBucketResources,
+        InvalidReplicationFactor,
+        EraIsNotInCommitPhase,
+        InvalidCdnReportQuorum,
+        EraIsNotInValidationOrPayoutPhase,
+        EraIsInTheFuture,
+        EraIsOlderThanLastSettled,
+        UnbalancedCdnReport,
+        OperatorsSizeExceedsLimit,
+        InvalidBucketParams,
     }
 
     pub type Result<T> = core::result::Result<T, Error>;
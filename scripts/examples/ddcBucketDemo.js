@@ -55,7 +55,7 @@ async function main() {
     const anyAccountId = account.address;
 
     const bucketResource = 5;
-    const bucketParams = "{}";
+    const bucketParams = "{\"replicationFactor\":1}";
 
     let nodeKey;
     {
@@ -270,9 +270,11 @@ async function main() {
 
     {
         log("Distribute payment from Cluster to Providers...");
+        const maxProviders = 20;
         const tx = bucketContract.tx.clusterDistributeRevenues(
             txOptions, 
-            clusterId
+            clusterId,
+            maxProviders
         );
 
         const result = await sendTx(account, tx);